@@ -0,0 +1,155 @@
+use clap::ValueEnum;
+use ssh_key::private::Ed25519Keypair;
+
+use crate::slip10;
+
+/// The coins whose accounts are ed25519 keys derived with SLIP-0010.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Coin {
+    /// Stellar accounts at `m/44'/148'/<account>'`, as in SEP-0005
+    Stellar,
+    /// Solana accounts at `m/44'/501'/<account>'/0'`, as most wallets use
+    Solana,
+}
+
+impl Coin {
+    pub(crate) fn path(&self, account: u32) -> slip10::DerivationPath {
+        let path = match self {
+            Coin::Stellar => format!("m/44'/148'/{account}'"),
+            Coin::Solana => format!("m/44'/501'/{account}'/0'"),
+        };
+        path.parse().expect("coin paths are valid")
+    }
+
+    fn address(&self, public_key: &[u8; 32]) -> String {
+        match self {
+            Coin::Stellar => stellar_address(public_key),
+            Coin::Solana => base58(public_key),
+        }
+    }
+}
+
+/// Derives the address of `account` of `coin` from a BIP39 seed.
+pub(crate) fn address(seed: &[u8; 64], coin: Coin, account: u32) -> anyhow::Result<String> {
+    let key = slip10::ExtendedKey::master(seed).derive_path(&coin.path(account))?;
+    let keypair = Ed25519Keypair::from_seed(key.key());
+    Ok(coin.address(&keypair.public.0))
+}
+
+/// Encodes an ed25519 public key as a Stellar `G...` account ID.
+fn stellar_address(public_key: &[u8; 32]) -> String {
+    const ACCOUNT_ID: u8 = 6 << 3;
+
+    let mut payload = Vec::with_capacity(35);
+    payload.push(ACCOUNT_ID);
+    payload.extend_from_slice(public_key);
+    let checksum = crc16_xmodem(&payload);
+    payload.extend_from_slice(&checksum.to_le_bytes());
+    base32(&payload)
+}
+
+fn crc16_xmodem(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in data {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// RFC 4648 base32 without padding.
+fn base32(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+    let mut out = String::with_capacity((data.len() * 8).div_ceil(5));
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in data {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+    out
+}
+
+/// Base58 with the Bitcoin alphabet, as Solana addresses use.
+fn base58(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    // Little-endian base58 digits of `data` read as a big-endian number.
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in data {
+        let mut carry = u32::from(byte);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = data.iter().take_while(|&&b| b == 0).count();
+    std::iter::repeat('1')
+        .take(zeros)
+        .chain(digits.iter().rev().map(|&d| ALPHABET[d as usize] as char))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bip39::Mnemonic;
+
+    use super::*;
+
+    #[test]
+    fn stellar_accounts() {
+        // The first test vector of SEP-0005.
+        let seed = Mnemonic::parse(
+            "illness spike retreat truth genius clock brain pass fit cave bargain toe",
+        )
+        .unwrap()
+        .to_seed("");
+
+        let addresses: Vec<String> = (0..3)
+            .map(|account| address(&seed, Coin::Stellar, account).unwrap())
+            .collect();
+        assert_eq!(addresses, [
+            "GDRXE2BQUC3AZNPVFSCEZ76NJ3WWL25FYFK6RGZGIEKWE4SOOHSUJUJ6",
+            "GBAW5XGWORWVFE2XTJYDTLDHXTY2Q2MO73HYCGB3XMFMQ562Q2W2GJQX",
+            "GAY5PRAHJ2HIYBYCLZXTHID6SPVELOOYH2LBPH3LD4RUMXUW3DOYTLXW",
+        ]);
+    }
+
+    #[test]
+    fn solana_accounts() {
+        let seed = Mnemonic::parse(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+             abandon about",
+        )
+        .unwrap()
+        .to_seed("");
+
+        assert_eq!(
+            address(&seed, Coin::Solana, 0).unwrap(),
+            "HAgk14JpMQLgt6rVgv7cBQFJWFto5Dqxi472uT3DKpqk"
+        );
+        assert_eq!(Coin::Solana.path(2).to_string(), "m/44'/501'/2'/0'");
+        assert_eq!(base58(&[0, 0, 1]), "112");
+        assert_eq!(base58(b"hello world"), "StV1DL6CwTryKyV");
+    }
+}
@@ -0,0 +1,124 @@
+use std::process::Command;
+
+use anyhow::bail;
+use anyhow::Context;
+use secrecy::SecretString;
+use zeroize::Zeroizing;
+
+use crate::entropy;
+
+/// The gpg-agent cache entry of the passphrase.
+const CACHE_ID: &str = "bip39-keygen:passphrase";
+
+/// Gets the passphrase from gpg-agent, which asks for it with its pinentry,
+/// showing `label`, only if it has not cached it yet, and with `confirm` asks
+/// for it twice. The agent keeps it cached for the next runs, as long as its
+/// cache TTL.
+pub(crate) fn get_passphrase(label: &str, confirm: bool) -> anyhow::Result<SecretString> {
+    get_passphrase_with(label, confirm, |command| {
+        let output = Command::new("gpg-connect-agent")
+            .arg(command)
+            .arg("/bye")
+            .output()
+            .context("failed to run gpg-connect-agent, is GnuPG installed?")?;
+        if !output.status.success() {
+            bail!("gpg-connect-agent failed with {}", output.status);
+        }
+        Ok(Zeroizing::new(String::from_utf8(output.stdout)?))
+    })
+}
+
+/// Like [`get_passphrase`], sending each Assuan command with `send`, which
+/// returns the response of the agent.
+fn get_passphrase_with(
+    label: &str,
+    confirm: bool,
+    mut send: impl FnMut(&str) -> anyhow::Result<Zeroizing<String>>,
+) -> anyhow::Result<SecretString> {
+    if let Some(passphrase) =
+        parse_response(&send(&format!("GET_PASSPHRASE --no-ask {CACHE_ID} X X X"))?)?
+    {
+        return Ok(passphrase);
+    }
+
+    let repeat = if confirm { "--repeat=1 " } else { "" };
+    let command = format!(
+        "GET_PASSPHRASE {repeat}{CACHE_ID} X {} {}",
+        escape("Passphrase:"),
+        escape(label)
+    );
+    match parse_response(&send(&command)?)? {
+        Some(passphrase) => Ok(passphrase),
+        None => bail!("gpg-agent did not return a passphrase"),
+    }
+}
+
+/// Returns the passphrase of an `OK <hex>` response, or `None` for an error
+/// such as an uncached passphrase or a cancelled pinentry.
+fn parse_response(response: &str) -> anyhow::Result<Option<SecretString>> {
+    for line in response.lines() {
+        if let Some(hex) = line.strip_prefix("OK ") {
+            let bytes = entropy::from_hex(hex).context("gpg-agent returned invalid hex")?;
+            let passphrase = std::str::from_utf8(&bytes).context("the passphrase is not UTF-8")?;
+            return Ok(Some(SecretString::new(passphrase.to_string())));
+        }
+        if line == "OK" {
+            return Ok(Some(SecretString::new(String::new())));
+        }
+        if line.starts_with("ERR ") {
+            return Ok(None);
+        }
+    }
+    bail!("unexpected response from gpg-agent")
+}
+
+/// Escapes an Assuan argument, where `+` stands for a space.
+fn escape(s: &str) -> String {
+    s.replace('%', "%25").replace('+', "%2B").replace(' ', "+")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use secrecy::ExposeSecret;
+
+    use super::*;
+
+    #[test]
+    fn cached_passphrase_is_reused() {
+        let mut cached: Option<String> = None;
+        let mut asked = 0;
+        let mut agent = |command: &str| {
+            let hex = match (command.contains("--no-ask"), &cached) {
+                (_, Some(hex)) => hex.clone(),
+                (true, None) => return Ok(Zeroizing::new("ERR 67108922 No data\n".to_string())),
+                (false, None) => {
+                    assert!(command.contains("--repeat=1"));
+                    asked += 1;
+                    let hex = hex::encode("hunter2");
+                    cached = Some(hex.clone());
+                    hex
+                }
+            };
+            Ok(Zeroizing::new(format!("OK {hex}\n")))
+        };
+
+        let first = get_passphrase_with("Passphrase:", true, &mut agent).unwrap();
+        let second = get_passphrase_with("Passphrase:", true, &mut agent).unwrap();
+        assert_eq!(first.expose_secret(), "hunter2");
+        assert_eq!(second.expose_secret(), "hunter2");
+        assert_eq!(asked, 1);
+    }
+
+    #[test]
+    fn cancelled_pinentry() {
+        let err = get_passphrase_with("Passphrase:", false, |_| {
+            Ok(Zeroizing::new(
+                "ERR 83886179 Operation cancelled\n".to_string(),
+            ))
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "gpg-agent did not return a passphrase");
+        assert_eq!(escape("a b+c"), "a+b%2Bc");
+    }
+}
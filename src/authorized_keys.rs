@@ -0,0 +1,190 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::Context;
+use ssh_key::PublicKey;
+
+use crate::transaction::Transaction;
+
+/// Adds `public_key` to the `authorized_keys` file at `path`, which is only
+/// readable and writable by its owner afterwards.
+///
+/// If the key is already listed, its line is updated in place: the options
+/// before the key are kept, except that a `from="..."` option given as
+/// `from` replaces the one listed, and the comment is replaced, so that the
+/// key is never listed twice.
+pub(crate) fn append(
+    tx: &mut Transaction,
+    path: &Path,
+    public_key: &PublicKey,
+    from: Option<&str>,
+) -> anyhow::Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    tx.write_private_file(path, upsert(&contents, public_key, from)?)?;
+    Ok(())
+}
+
+fn upsert(contents: &str, public_key: &PublicKey, from: Option<&str>) -> ssh_key::Result<String> {
+    let line = public_key.to_openssh()?;
+    let key = PublicKey::new(public_key.key_data().clone(), "").to_openssh()?;
+
+    let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+    let existing = lines.iter_mut().find_map(|l| {
+        let trimmed = l.trim_start();
+        if trimmed.starts_with('#') {
+            return None;
+        }
+        // The key is followed by whitespace before a comment, or nothing.
+        let at = l.find(key.as_str())?;
+        let rest = &l[at + key.len()..];
+        (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some((l, at))
+    });
+    match existing {
+        Some((l, at)) => {
+            let options = l[..at].trim();
+            let options = match from {
+                Some(from) => set_option(options, from),
+                None => options.to_string(),
+            };
+            *l = if options.is_empty() {
+                line
+            } else {
+                format!("{options} {line}")
+            };
+        }
+        None => lines.push(match from {
+            Some(from) => format!("{from} {line}"),
+            None => line,
+        }),
+    }
+
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    Ok(contents)
+}
+
+/// Replaces the option with the name of `option`, such as `from=`, in the
+/// comma-separated `options`, or adds it in front if it is not listed.
+fn set_option(options: &str, option: &str) -> String {
+    let name = option.split_inclusive('=').next().unwrap_or(option);
+    let mut replaced = false;
+    let mut list: Vec<&str> = split_options(options)
+        .into_iter()
+        .map(|o| {
+            if o.starts_with(name) {
+                replaced = true;
+                option
+            } else {
+                o
+            }
+        })
+        .collect();
+    if !replaced {
+        list.insert(0, option);
+    }
+    list.join(",")
+}
+
+/// Splits `options` at the commas outside of double quotes.
+fn split_options(options: &str) -> Vec<&str> {
+    if options.is_empty() {
+        return Vec::new();
+    }
+    let mut list = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in options.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                list.push(&options[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    list.push(&options[start..]);
+    list
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ssh_key::private::Ed25519Keypair;
+    use ssh_key::public::KeyData;
+
+    use super::*;
+
+    fn public_key(seed: u8, comment: &str) -> PublicKey {
+        let keypair = Ed25519Keypair::from_seed(&[seed; 32]);
+        PublicKey::new(KeyData::Ed25519(keypair.public), comment)
+    }
+
+    #[test]
+    fn reappending_updates_the_line() {
+        let testdir = tempfile::tempdir().unwrap();
+        let path = testdir.path().join("authorized_keys");
+        let other = public_key(8, "other").to_openssh().unwrap();
+        fs::write(&path, format!("# keys\n{other}\n")).unwrap();
+
+        let old = public_key(7, "old").to_openssh().unwrap();
+        let mut tx = Transaction::new(tempfile::tempdir().unwrap());
+        append(&mut tx, &path, &public_key(7, "old"), None).unwrap();
+        tx.commit();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("# keys\n{other}\n{old}\n")
+        );
+
+        // Options added by hand are kept when the key is written again.
+        fs::write(&path, format!("# keys\n{other}\nfrom=\"10.0.0.1\" {old}\n")).unwrap();
+        let mut tx = Transaction::new(tempfile::tempdir().unwrap());
+        append(&mut tx, &path, &public_key(7, "new"), None).unwrap();
+        tx.commit();
+
+        let new = public_key(7, "new").to_openssh().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("# keys\n{other}\nfrom=\"10.0.0.1\" {new}\n")
+        );
+
+        // A given `from=` option replaces the listed one.
+        let from = "from=\"192.0.2.0/24\"";
+        let mut tx = Transaction::new(tempfile::tempdir().unwrap());
+        append(&mut tx, &path, &public_key(7, "new"), Some(from)).unwrap();
+        tx.commit();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            format!("# keys\n{other}\n{from} {new}\n")
+        );
+    }
+
+    #[test]
+    fn from_keeps_other_options() {
+        let new = public_key(7, "new").to_openssh().unwrap();
+        let old = public_key(7, "old").to_openssh().unwrap();
+        let from = "from=\"192.0.2.0/24\"";
+
+        let contents = format!("command=\"/usr/bin/backup\",restrict,no-pty {old}\n");
+        assert_eq!(
+            upsert(&contents, &public_key(7, "new"), Some(from)).unwrap(),
+            format!("{from},command=\"/usr/bin/backup\",restrict,no-pty {new}\n")
+        );
+
+        let contents = format!(
+            "command=\"/usr/bin/backup --from=a,b\",from=\"10.0.0.1,10.0.0.2\",no-pty {old}\n"
+        );
+        assert_eq!(
+            upsert(&contents, &public_key(7, "new"), Some(from)).unwrap(),
+            format!("command=\"/usr/bin/backup --from=a,b\",{from},no-pty {new}\n")
+        );
+    }
+}
@@ -0,0 +1,548 @@
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::io::Write;
+use std::path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use anyhow::bail;
+use anyhow::Context;
+use bip39::Mnemonic;
+use secrecy::ExposeSecret;
+use secrecy::SecretString;
+use serde::Deserialize;
+use serde::Serialize;
+use zeroize::Zeroizing;
+
+use crate::derivation;
+use crate::slip10;
+use crate::KeyType;
+use crate::PassphraseUse;
+use crate::TransactionArgs;
+
+/// One line of the batch input.
+///
+/// Secrets are never inlined: `mnemonic_ref` and `passphrase_ref` are either
+/// `env:<NAME>` or `file:<PATH>`. The passphrase is used as `passphrase_use`
+/// says, like `ssh --passphrase-use`, both deriving the seed and encrypting
+/// the private key by default.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Job {
+    mnemonic_ref: String,
+    #[serde(default)]
+    passphrase_ref: Option<String>,
+    #[serde(default)]
+    passphrase_use: Option<PassphraseUse>,
+    #[serde(rename = "type")]
+    key_type: KeyType,
+    /// A SLIP-0010 path from the BIP39 seed. Without it the key is derived
+    /// like the `ssh` command does.
+    #[serde(default)]
+    path: Option<String>,
+    output: PathBuf,
+    #[serde(default)]
+    comment: Option<String>,
+}
+
+/// One line of the batch output, in the order of the jobs.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+enum JobResult {
+    Ok {
+        job: usize,
+        output: PathBuf,
+        fingerprint: String,
+    },
+    /// With `--resume`, the key of the job was already written.
+    Skipped {
+        job: usize,
+        output: PathBuf,
+        fingerprint: String,
+    },
+    Error {
+        job: usize,
+        error: String,
+    },
+}
+
+/// The default of `--max-jobs`.
+pub(crate) const DEFAULT_MAX_JOBS: usize = 1000;
+
+/// A rough upper bound of the disk space taken by the two files of one job,
+/// each rounded up to a 4 KiB block.
+const BYTES_PER_JOB: u64 = 2 * 4096;
+
+/// Runs the jobs read from `input`, one JSON object per line, and writes one
+/// result line per job to `output`.
+///
+/// The whole input is read first, and nothing is written if it has more than
+/// `max_jobs` jobs, where reading stops, or if the outputs of the jobs do not
+/// fit on their file systems. Each job is written in its own transaction, so a failing
+/// job only rolls back its own files. If `progress` is given, a status line
+/// is rewritten on it after each job. With `resume`, jobs whose key files
+/// already hold the key they would derive are skipped, so an interrupted run
+/// can be started again. Returns the number of failed jobs.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn run(
+    input: impl BufRead,
+    mut output: impl Write,
+    mut progress: Option<&mut dyn Write>,
+    tx_args: &TransactionArgs,
+    allow_system_path: bool,
+    mtime: Option<SystemTime>,
+    max_jobs: usize,
+    resume: bool,
+) -> anyhow::Result<usize> {
+    let mut lines = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            lines.push(line);
+        }
+        if lines.len() > max_jobs {
+            bail!(
+                "the input has more than --max-jobs {max_jobs} jobs, they would take more than {}",
+                format_size(max_jobs as u64 * BYTES_PER_JOB)
+            );
+        }
+    }
+    let estimate = format_size(lines.len() as u64 * BYTES_PER_JOB);
+    #[cfg(unix)]
+    check_free_space(&lines)?;
+    if let Some(progress) = &mut progress {
+        if !lines.is_empty() {
+            writeln!(
+                progress,
+                "Running {} job(s), taking up to {estimate}",
+                lines.len()
+            )?;
+        }
+    }
+
+    let mut failed = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let job = i + 1;
+        let result = match run_job(line, tx_args, allow_system_path, mtime, resume) {
+            Ok(Outcome::Written(output, fingerprint)) => JobResult::Ok {
+                job,
+                output,
+                fingerprint,
+            },
+            Ok(Outcome::Skipped(output, fingerprint)) => JobResult::Skipped {
+                job,
+                output,
+                fingerprint,
+            },
+            Err(e) => {
+                failed += 1;
+                JobResult::Error {
+                    job,
+                    error: format!("{e:#}"),
+                }
+            }
+        };
+        serde_json::to_writer(&mut output, &result)?;
+        writeln!(output)?;
+        if let Some(progress) = &mut progress {
+            write!(progress, "\rProcessed {job} job(s), {failed} failed")?;
+            progress.flush()?;
+        }
+    }
+    match progress {
+        Some(progress) if !lines.is_empty() => writeln!(progress)?,
+        _ => {}
+    }
+    Ok(failed)
+}
+
+/// Fails unless the file system of each output directory of the jobs in
+/// `lines` has room for the two files of each of its jobs. Jobs that cannot
+/// be parsed are left to fail on their own.
+#[cfg(unix)]
+fn check_free_space(lines: &[String]) -> anyhow::Result<()> {
+    let mut dirs: BTreeMap<PathBuf, (PathBuf, usize)> = BTreeMap::new();
+    for line in lines {
+        let Ok(job) = serde_json::from_str::<Job>(line) else {
+            continue;
+        };
+        let Ok(output) = path::absolute(&job.output) else {
+            continue;
+        };
+        let dir = output.parent().map(PathBuf::from).unwrap_or_default();
+        dirs.entry(dir).or_insert((output, 0)).1 += 2;
+    }
+    for (output, files) in dirs.values() {
+        if let Some(warning) = crate::diskspace::check_free_space(output, *files) {
+            bail!("{warning}");
+        }
+    }
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    const MIB: u64 = 1024 * 1024;
+    if bytes >= MIB {
+        format!("{:.1} MiB", bytes as f64 / MIB as f64)
+    } else {
+        format!("{} KiB", bytes.div_ceil(1024))
+    }
+}
+
+/// Whether to report progress on stderr: only for an interactive terminal
+/// and unless `--quiet` is given.
+pub(crate) fn show_progress(quiet: bool, stderr_is_terminal: bool) -> bool {
+    !quiet && stderr_is_terminal
+}
+
+/// What a successful job did, with the private key path and the fingerprint.
+enum Outcome {
+    Written(PathBuf, String),
+    Skipped(PathBuf, String),
+}
+
+fn run_job(
+    line: &str,
+    tx_args: &TransactionArgs,
+    allow_system_path: bool,
+    mtime: Option<SystemTime>,
+    resume: bool,
+) -> anyhow::Result<Outcome> {
+    let job: Job = serde_json::from_str(line).context("invalid job")?;
+    let path = job
+        .path
+        .as_deref()
+        .map(str::parse::<slip10::DerivationPath>)
+        .transpose()?;
+    if let (KeyType::Ed25519, Some(path)) = (job.key_type, &path) {
+        path.check_hardened()?;
+    }
+    let mnemonic = read_ref(&job.mnemonic_ref).context("failed to read mnemonic")?;
+    let mnemonic = Zeroizing::new(crate::mnemonic::parse(mnemonic.expose_secret(), None)?);
+    let passphrase = match &job.passphrase_ref {
+        Some(reference) => read_ref(reference).context("failed to read passphrase")?,
+        None => SecretString::new(String::new()),
+    };
+    let (passphrase, encryption) = crate::split_passphrase(
+        job.passphrase_use.unwrap_or(PassphraseUse::Both),
+        passphrase,
+    );
+    let keypair = match (job.key_type, &path) {
+        (KeyType::Ed25519, Some(path)) => derive_path(&mnemonic, &passphrase, path)?,
+        (KeyType::Ed25519, None) => crate::derive_ed25519_keypair(
+            &mnemonic,
+            &passphrase,
+            derivation::Derivation::Legacy,
+            derivation::SSH_ED25519,
+            None,
+        ),
+    };
+
+    let seckey_path = path::absolute(&job.output)?;
+    if !allow_system_path {
+        crate::check_not_system_path(&seckey_path)?;
+    }
+    let pubkey_path = seckey_path.with_extension("pub");
+    if resume && seckey_path.exists() && pubkey_path.exists() {
+        let expected = ssh_key::public::KeyData::Ed25519(keypair.public);
+        let secret_key = ssh_key::PrivateKey::read_openssh_file(&seckey_path)
+            .with_context(|| format!("failed to read {}", seckey_path.display()))?;
+        let public_key = ssh_key::PublicKey::read_openssh_file(&pubkey_path)
+            .with_context(|| format!("failed to read {}", pubkey_path.display()))?;
+        if secret_key.public_key().key_data() != &expected || public_key.key_data() != &expected {
+            bail!(
+                "{} already exists with another key, not resuming it",
+                seckey_path.display()
+            );
+        }
+        let fingerprint = public_key.fingerprint(Default::default()).to_string();
+        return Ok(Outcome::Skipped(seckey_path, fingerprint));
+    }
+    for path in [&seckey_path, &pubkey_path] {
+        if path.exists() {
+            bail!("{} already exists", path.display());
+        }
+    }
+
+    let mut tx = tx_args.new_transaction(mtime)?;
+    let public_key = crate::write_ssh_keypair(
+        &mut tx,
+        &seckey_path,
+        &pubkey_path,
+        keypair,
+        job.comment.unwrap_or_else(crate::ssh_default_comment),
+        None,
+        encryption.as_ref(),
+        false,
+    )?;
+    tx.commit();
+    Ok(Outcome::Written(
+        seckey_path,
+        public_key.fingerprint(Default::default()).to_string(),
+    ))
+}
+
+fn derive_path(
+    mnemonic: &Mnemonic,
+    passphrase: &SecretString,
+    path: &slip10::DerivationPath,
+) -> anyhow::Result<ssh_key::private::Ed25519Keypair> {
+    let seed = Zeroizing::new(mnemonic.to_seed(passphrase.expose_secret()));
+    let key = slip10::ExtendedKey::master(seed.as_ref()).derive_path(path)?;
+    Ok(ssh_key::private::Ed25519Keypair::from_seed(key.key()))
+}
+
+fn read_ref(reference: &str) -> anyhow::Result<SecretString> {
+    match reference.split_once(':') {
+        Some(("env", name)) => Ok(SecretString::new(std::env::var(name)?)),
+        Some(("file", path)) => {
+            let contents = Zeroizing::new(std::fs::read_to_string(path)?);
+            Ok(SecretString::new(contents.trim().to_string()))
+        }
+        _ => bail!("expected env:<NAME> or file:<PATH>, got \"{reference}\""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn tx_args() -> TransactionArgs {
+        TransactionArgs {
+            no_backup: false,
+            max_backup_memory: None,
+            follow_symlinks: false,
+            io_retries: 0,
+        }
+    }
+
+    #[test]
+    fn two_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mnemonic_path = dir.path().join("mnemonic");
+        std::fs::write(
+            &mnemonic_path,
+            "legal winner thank year wave sausage worth useful legal winner thank yellow\n",
+        )
+        .unwrap();
+        let first = dir.path().join("first");
+        std::fs::write(dir.path().join("second.pub"), "existing").unwrap();
+
+        let job = |path: &str, output: &std::path::Path| {
+            serde_json::json!({
+                "mnemonic_ref": format!("file:{}", mnemonic_path.display()),
+                "type": "ed25519",
+                "path": path,
+                "output": output,
+                "comment": "me",
+            })
+            .to_string()
+        };
+        let input = format!(
+            "{}\n{}\n",
+            job("m/0'", &first),
+            job("m/1'", &dir.path().join("second"))
+        );
+
+        let mut output = Vec::new();
+        let mut progress = Vec::new();
+        let failed = run(
+            input.as_bytes(),
+            &mut output,
+            Some(&mut progress),
+            &tx_args(),
+            false,
+            None,
+            DEFAULT_MAX_JOBS,
+            false,
+        )
+        .unwrap();
+
+        let results: Vec<serde_json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(failed, 1);
+
+        assert_eq!(results[0]["status"], "ok");
+        assert_eq!(results[0]["job"], 1);
+        let public_key =
+            ssh_key::PublicKey::read_openssh_file(&first.with_extension("pub")).unwrap();
+        assert_eq!(
+            results[0]["fingerprint"],
+            public_key.fingerprint(Default::default()).to_string()
+        );
+
+        assert_eq!(results[1]["status"], "error");
+        assert_eq!(results[1]["job"], 2);
+        assert!(!dir.path().join("second").exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("second.pub")).unwrap(),
+            "existing"
+        );
+
+        let progress = String::from_utf8(progress).unwrap();
+        assert!(progress.contains("Processed 1 job(s), 0 failed"));
+        assert!(progress.ends_with("Processed 2 job(s), 1 failed\n"));
+    }
+
+    #[test]
+    fn passphrase_encrypts_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let mnemonic_path = dir.path().join("mnemonic");
+        std::fs::write(
+            &mnemonic_path,
+            "legal winner thank year wave sausage worth useful legal winner thank yellow\n",
+        )
+        .unwrap();
+        let passphrase_path = dir.path().join("passphrase");
+        std::fs::write(&passphrase_path, "correct horse battery staple").unwrap();
+        let job = |output: &std::path::Path, passphrase_use: &str| {
+            serde_json::json!({
+                "mnemonic_ref": format!("file:{}", mnemonic_path.display()),
+                "passphrase_ref": format!("file:{}", passphrase_path.display()),
+                "passphrase_use": passphrase_use,
+                "type": "ed25519",
+                "output": output,
+            })
+            .to_string()
+        };
+        let encrypted = dir.path().join("encrypted");
+        let plain = dir.path().join("plain");
+        let input = format!("{}\n{}\n", job(&encrypted, "both"), job(&plain, "seed"));
+
+        let mut output = Vec::new();
+        let failed = run(
+            input.as_bytes(),
+            &mut output,
+            None,
+            &tx_args(),
+            false,
+            None,
+            DEFAULT_MAX_JOBS,
+            false,
+        )
+        .unwrap();
+        assert_eq!(failed, 0);
+
+        let encrypted = ssh_key::PrivateKey::read_openssh_file(&encrypted).unwrap();
+        let plain = ssh_key::PrivateKey::read_openssh_file(&plain).unwrap();
+        assert!(encrypted.is_encrypted());
+        assert!(!plain.is_encrypted());
+        // The same passphrase derives the same key either way.
+        assert_eq!(
+            encrypted
+                .decrypt("correct horse battery staple")
+                .unwrap()
+                .key_data(),
+            plain.key_data()
+        );
+    }
+
+    #[test]
+    fn too_many_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let job = |i: usize| {
+            serde_json::json!({
+                "mnemonic_ref": "env:BIP39_KEYGEN_UNUSED",
+                "type": "ed25519",
+                "output": dir.path().join(i.to_string()),
+            })
+            .to_string()
+        };
+        // Reading stops at the third job, before the invalid UTF-8.
+        let mut input = (0..3)
+            .map(|i| job(i) + "\n")
+            .collect::<String>()
+            .into_bytes();
+        input.extend(b"\xff\n");
+
+        let mut output = Vec::new();
+        let err = run(
+            input.as_slice(),
+            &mut output,
+            None,
+            &tx_args(),
+            false,
+            None,
+            2,
+            false,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "the input has more than --max-jobs 2 jobs, they would take more than 16 KiB"
+        );
+        assert!(output.is_empty());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+        assert_eq!(format_size(1_000_000_000), "953.7 MiB");
+    }
+
+    #[test]
+    fn resume_writes_missing_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let mnemonic_path = dir.path().join("mnemonic");
+        std::fs::write(
+            &mnemonic_path,
+            "legal winner thank year wave sausage worth useful legal winner thank yellow\n",
+        )
+        .unwrap();
+        let job = |i: usize| {
+            serde_json::json!({
+                "mnemonic_ref": format!("file:{}", mnemonic_path.display()),
+                "type": "ed25519",
+                "path": format!("m/{i}'"),
+                "output": dir.path().join(i.to_string()),
+            })
+            .to_string()
+        };
+        let input: String = (0..3).map(|i| job(i) + "\n").collect();
+        let run = |input: &str| {
+            let mut output = Vec::new();
+            let failed = run(
+                input.as_bytes(),
+                &mut output,
+                None,
+                &tx_args(),
+                false,
+                None,
+                DEFAULT_MAX_JOBS,
+                true,
+            )
+            .unwrap();
+            assert_eq!(failed, 0);
+            String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .map(|line| {
+                    serde_json::from_str::<serde_json::Value>(line).unwrap()["status"].clone()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // The first run was interrupted after the first job.
+        run(&job(0));
+        let written = std::fs::read(dir.path().join("0")).unwrap();
+
+        assert_eq!(run(&input), ["skipped", "ok", "ok"]);
+        assert_eq!(std::fs::read(dir.path().join("0")).unwrap(), written);
+        assert!(dir.path().join("2.pub").exists());
+        assert_eq!(run(&input), ["skipped", "skipped", "skipped"]);
+    }
+
+    #[test]
+    fn progress_is_quiet_without_tty() {
+        assert!(show_progress(false, true));
+        assert!(!show_progress(true, true));
+        assert!(!show_progress(false, false));
+    }
+
+    #[test]
+    fn read_ref_rejects_inline_secrets() {
+        assert!(read_ref("legal winner thank year").is_err());
+    }
+}
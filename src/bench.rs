@@ -0,0 +1,140 @@
+use std::fmt;
+use std::time::Duration;
+use std::time::Instant;
+
+use bip39::Language;
+use bip39::Mnemonic;
+use clap::ValueEnum;
+use secrecy::SecretString;
+
+use crate::derivation;
+use crate::derivation::Derivation;
+use crate::KeyType;
+
+/// How long deriving `iterations` keys took, and encrypting them if asked.
+#[derive(Debug)]
+pub(crate) struct Stats {
+    key_type: KeyType,
+    derivation: Derivation,
+    iterations: u32,
+    elapsed: Duration,
+    encryption: Option<Duration>,
+}
+
+impl Stats {
+    pub(crate) fn ops_per_sec(&self) -> f64 {
+        f64::from(self.iterations) / self.elapsed.as_secs_f64()
+    }
+
+    /// How many keys per second were encrypted, with the bcrypt KDF and
+    /// cipher `ssh` encrypts private keys with.
+    pub(crate) fn encryptions_per_sec(&self) -> Option<f64> {
+        self.encryption
+            .map(|elapsed| f64::from(self.iterations) / elapsed.as_secs_f64())
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} derivation: {} keys in {:.3}s, {:.1} keys/s",
+            self.key_type.as_ssh_keygen_type(),
+            self.derivation
+                .to_possible_value()
+                .expect("no derivation is skipped")
+                .get_name(),
+            self.iterations,
+            self.elapsed.as_secs_f64(),
+            self.ops_per_sec()
+        )?;
+        if let (Some(elapsed), Some(ops)) = (self.encryption, self.encryptions_per_sec()) {
+            write!(
+                f,
+                "\nencryption: {} keys in {:.3}s, {:.1} keys/s",
+                self.iterations,
+                elapsed.as_secs_f64(),
+                ops
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Times deriving `iterations` keys, from the mnemonic to the keypair, each
+/// from a new random mnemonic that is thrown away, and with `encrypt` also
+/// encrypting each private key with a throwaway passphrase.
+pub(crate) fn run(
+    key_type: KeyType,
+    derivation: Derivation,
+    iterations: u32,
+    encrypt: bool,
+) -> anyhow::Result<Stats> {
+    let passphrase = SecretString::new(String::new());
+    let encryption_passphrase = SecretString::new("bench".to_string());
+    let mut elapsed = Duration::ZERO;
+    let mut encryption = encrypt.then_some(Duration::ZERO);
+    for _ in 0..iterations {
+        let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+        let start = Instant::now();
+        let keypair = match key_type {
+            KeyType::Ed25519 => crate::derive_ed25519_keypair(
+                &mnemonic,
+                &passphrase,
+                derivation,
+                derivation::SSH_ED25519,
+                None,
+            ),
+        };
+        elapsed += start.elapsed();
+        if let Some(encryption) = &mut encryption {
+            let secret_key =
+                ssh_key::PrivateKey::new(ssh_key::private::KeypairData::Ed25519(keypair), "")?;
+            let start = Instant::now();
+            let encoded = crate::encode_openssh(&secret_key, Some(&encryption_passphrase))?;
+            *encryption += start.elapsed();
+            std::hint::black_box(encoded);
+        } else {
+            std::hint::black_box(keypair);
+        }
+    }
+    Ok(Stats {
+        key_type,
+        derivation,
+        iterations,
+        elapsed,
+        encryption,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn small_run() {
+        let stats = run(KeyType::Ed25519, Derivation::Hkdf, 3, false).unwrap();
+        assert!(stats.ops_per_sec() > 0.0);
+        assert!(stats.encryptions_per_sec().is_none());
+
+        let output = stats.to_string();
+        let ops = output
+            .strip_suffix(" keys/s")
+            .and_then(|s| s.rsplit(' ').next())
+            .unwrap();
+        assert!(ops.parse::<f64>().unwrap() > 0.0);
+        assert!(output.starts_with("ed25519 hkdf derivation: 3 keys in "));
+    }
+
+    #[test]
+    fn encryption_run() {
+        let stats = run(KeyType::Ed25519, Derivation::Legacy, 1, true).unwrap();
+        assert!(stats.encryptions_per_sec().unwrap() > 0.0);
+
+        let output = stats.to_string();
+        let (_, encryption) = output.split_once('\n').unwrap();
+        assert!(encryption.starts_with("encryption: 1 keys in "));
+        assert!(encryption.ends_with(" keys/s"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt::Write;
+
+use anyhow::bail;
+use anyhow::Context;
+use bip39::Mnemonic;
+use sha2::Digest;
+use sha2::Sha256;
+
+const DOMAIN: &[u8] = b"bip39-keygen/v1/checksum-sheet";
+
+const HEADER: &str = "# bip39-keygen checksum sheet v1";
+
+/// The characters of the codes, without the easily confused 0, 1, I and O.
+const ALPHABET: &[u8; 32] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+
+/// Returns the two-character code of `word` at `position`, counting from 1.
+fn code(position: usize, word: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN);
+    hasher.update((position as u32).to_be_bytes());
+    hasher.update(word.as_bytes());
+    let digest = hasher.finalize();
+    [digest[0], digest[1]]
+        .iter()
+        .map(|b| char::from(ALPHABET[usize::from(b >> 3)]))
+        .collect()
+}
+
+/// Renders the checksum sheet of `mnemonic`: one numbered code per word, to
+/// check each transcribed word against.
+///
+/// Each code narrows its word down to about two candidates, so the sheet
+/// has to be kept as secret as the mnemonic.
+pub(crate) fn render(mnemonic: &Mnemonic) -> String {
+    let mut sheet = format!(
+        "{HEADER}\n# Keep it as secret as the mnemonic, the codes reveal most of its words.\n"
+    );
+    for (i, word) in mnemonic.word_iter().enumerate() {
+        writeln!(sheet, "{:>2}. {}", i + 1, code(i + 1, word))
+            .expect("writing to a String cannot fail");
+    }
+    sheet
+}
+
+/// Fails unless each of the transcribed `words` has its code in `sheet`,
+/// naming the words that do not. The words need not be a valid mnemonic, so
+/// that a mistyped one is found even if it breaks the BIP39 checksum.
+pub(crate) fn verify(words: &[&str], sheet: &str) -> anyhow::Result<()> {
+    let mut lines = sheet.lines().filter(|line| !line.trim().is_empty());
+    if lines.next() != Some(HEADER) {
+        bail!("not a checksum sheet, it does not start with \"{HEADER}\"");
+    }
+    let mut codes = Vec::new();
+    for line in lines.filter(|line| !line.starts_with('#')) {
+        let Some((position, code)) = line.trim().split_once(". ") else {
+            bail!("invalid checksum sheet line \"{line}\"");
+        };
+        let position: usize = position
+            .parse()
+            .with_context(|| format!("invalid word number in \"{line}\""))?;
+        if position != codes.len() + 1 {
+            bail!(
+                "expected word {} in the checksum sheet, found {position}",
+                codes.len() + 1
+            );
+        }
+        codes.push(code.trim().to_ascii_uppercase());
+    }
+    if codes.len() != words.len() {
+        bail!(
+            "the checksum sheet has {} words, but the mnemonic has {}",
+            codes.len(),
+            words.len()
+        );
+    }
+    let mismatches: Vec<String> = words
+        .iter()
+        .zip(&codes)
+        .enumerate()
+        .filter(|(i, (word, expected))| code(i + 1, word) != **expected)
+        .map(|(i, _)| (i + 1).to_string())
+        .collect();
+    if !mismatches.is_empty() {
+        bail!(
+            "the words {} do not match the checksum sheet",
+            mismatches.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const MNEMONIC: &str =
+        "legal winner thank year wave sausage worth useful legal winner thank yellow";
+
+    #[test]
+    fn swapped_words_fail() {
+        let mnemonic = Mnemonic::parse(MNEMONIC).unwrap();
+        let sheet = render(&mnemonic);
+        assert_eq!(sheet.lines().count(), 2 + 12);
+        let words: Vec<&str> = MNEMONIC.split(' ').collect();
+        verify(&words, &sheet).unwrap();
+
+        let mut swapped = words.clone();
+        swapped.swap(1, 2);
+        let err = verify(&swapped, &sheet).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the words 2, 3 do not match the checksum sheet"
+        );
+
+        let err = verify(&words[..11], &sheet).unwrap_err();
+        assert!(err.to_string().contains("has 12 words"));
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use anyhow::bail;
+
+/// Comma-separated CIDR blocks such as `192.0.2.0/24,2001:db8::/32`, which
+/// the `from=` option of an `authorized_keys` line restricts a key to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CidrList(Vec<Cidr>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cidr {
+    addr: IpAddr,
+    prefix_len: u32,
+}
+
+impl FromStr for CidrList {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(parse_cidr)
+            .collect::<anyhow::Result<_>>()
+            .map(Self)
+    }
+}
+
+/// Parses `<address>/<prefix length>`, rejecting host bits after the prefix,
+/// which `sshd` refuses as well.
+fn parse_cidr(s: &str) -> anyhow::Result<Cidr> {
+    let Some((addr, prefix_len)) = s.split_once('/') else {
+        bail!("invalid CIDR \"{s}\", expected <address>/<prefix length>");
+    };
+    let Ok(addr) = addr.parse::<IpAddr>() else {
+        bail!("invalid CIDR \"{s}\", \"{addr}\" is not an IP address");
+    };
+    let (bits, width) = match addr {
+        IpAddr::V4(addr) => (u128::from(u32::from(addr)), 32),
+        IpAddr::V6(addr) => (u128::from(addr), 128),
+    };
+    let prefix_len = match prefix_len.parse::<u32>() {
+        Ok(len) if len <= width && prefix_len.bytes().all(|b| b.is_ascii_digit()) => len,
+        _ => bail!("invalid CIDR \"{s}\", the prefix length must be 0 to {width}"),
+    };
+    let host_mask = u128::MAX.checked_shr(128 - width + prefix_len).unwrap_or(0);
+    if bits & host_mask != 0 {
+        bail!("invalid CIDR \"{s}\", the address has bits set after the first {prefix_len}");
+    }
+    Ok(Cidr { addr, prefix_len })
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+impl CidrList {
+    /// Returns the `from="..."` option of the blocks.
+    pub(crate) fn to_option(&self) -> String {
+        let blocks: Vec<String> = self.0.iter().map(Cidr::to_string).collect();
+        format!("from=\"{}\"", blocks.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn from_option() {
+        let list: CidrList = "192.0.2.0/24,10.0.0.1/32,2001:db8::/32,0.0.0.0/0"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            list.to_option(),
+            "from=\"192.0.2.0/24,10.0.0.1/32,2001:db8::/32,0.0.0.0/0\""
+        );
+
+        let err = "192.0.2.1/24".parse::<CidrList>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid CIDR \"192.0.2.1/24\", the address has bits set after the first 24"
+        );
+        for invalid in [
+            "192.0.2.0",
+            "192.0.2.0/33",
+            "2001:db8::/129",
+            "192.0.2.0/+8",
+            "example.com/24",
+            "192.0.2.0/24,",
+            "192.0.2.0/24\"",
+        ] {
+            assert!(invalid.parse::<CidrList>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::bail;
+use anyhow::Context;
+use zeroize::Zeroizing;
+
+const MAGIC: &str = "bip39-keygen-combined v1";
+
+const PARTS: [&str; 3] = ["private-key", "public-key", "metadata"];
+
+/// The outputs of `ssh` in a single file, for secret managers that store one
+/// blob per secret.
+///
+/// The file starts with the line `bip39-keygen-combined v1`, followed by one
+/// frame per part: a line `<name> <length>`, the `<length>` bytes of the part
+/// and a newline. The parts are `private-key`, `public-key` and `metadata`,
+/// in this order, each exactly as it is written as a separate file.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Combined {
+    /// The OpenSSH private key.
+    pub(crate) private_key: Zeroizing<String>,
+    /// The OpenSSH public key.
+    pub(crate) public_key: String,
+    /// The JSON key metadata, as `--metadata-sidecar` writes it.
+    pub(crate) metadata: String,
+}
+
+impl Combined {
+    pub(crate) fn encode(&self) -> Zeroizing<String> {
+        let mut encoded = Zeroizing::new(format!("{MAGIC}\n"));
+        for (name, part) in
+            PARTS
+                .iter()
+                .zip([self.private_key.as_str(), &self.public_key, &self.metadata])
+        {
+            encoded.push_str(&format!("{name} {}\n{part}\n", part.len()));
+        }
+        encoded
+    }
+
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Self> {
+        let Some(mut rest) = s.strip_prefix(MAGIC).and_then(|s| s.strip_prefix('\n')) else {
+            bail!("not a combined file, it does not start with \"{MAGIC}\"");
+        };
+        let mut parts = Vec::with_capacity(PARTS.len());
+        for expected in PARTS {
+            let Some((header, after)) = rest.split_once('\n') else {
+                bail!("the {expected} part is missing");
+            };
+            let Some((name, len)) = header.split_once(' ') else {
+                bail!("invalid part header \"{header}\"");
+            };
+            if name != expected {
+                bail!("expected the {expected} part, found \"{name}\"");
+            }
+            let len: usize = len
+                .parse()
+                .with_context(|| format!("invalid length of the {name} part"))?;
+            let Some(part) = after.get(..len) else {
+                bail!("the {name} part is truncated");
+            };
+            let Some(after) = after[len..].strip_prefix('\n') else {
+                bail!("the {name} part is not followed by a newline");
+            };
+            parts.push(part);
+            rest = after;
+        }
+        if !rest.is_empty() {
+            bail!("unexpected data after the last part");
+        }
+        Ok(Self {
+            private_key: Zeroizing::new(parts[0].to_string()),
+            public_key: parts[1].to_string(),
+            metadata: parts[2].to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ssh_key::private::Ed25519Keypair;
+    use ssh_key::private::KeypairData;
+    use ssh_key::PrivateKey;
+
+    use super::*;
+    use crate::derivation;
+    use crate::derivation::Derivation;
+    use crate::metadata::KeyMetadata;
+
+    #[test]
+    fn round_trip() {
+        let secret_key = PrivateKey::new(
+            KeypairData::Ed25519(Ed25519Keypair::from_seed(&[7; 32])),
+            "me",
+        )
+        .unwrap();
+        let metadata = KeyMetadata::new(
+            secret_key.public_key(),
+            Derivation::Legacy,
+            derivation::SSH_ED25519,
+        );
+        let combined = Combined {
+            private_key: secret_key.to_openssh(Default::default()).unwrap(),
+            public_key: secret_key.public_key().to_openssh().unwrap(),
+            metadata: metadata.to_json().unwrap(),
+        };
+
+        let encoded = combined.encode();
+        assert!(encoded.starts_with("bip39-keygen-combined v1\nprivate-key "));
+        assert_eq!(Combined::parse(&encoded).unwrap(), combined);
+
+        let err = Combined::parse(&encoded[..encoded.len() - 2]).unwrap_err();
+        assert_eq!(err.to_string(), "the metadata part is truncated");
+    }
+}
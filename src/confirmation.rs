@@ -0,0 +1,78 @@
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use anyhow::bail;
+use anyhow::Context;
+use sha2::Digest;
+use sha2::Sha256;
+use ssh_key::Fingerprint;
+
+const DOMAIN: &[u8] = b"bip39-keygen/v1/confirmation";
+
+/// Returns the nonce a `--dry-run` prints for writing the key with
+/// `fingerprint` to `seckey_path`. It only depends on what is written where,
+/// so a confirmation file cannot be reused for another key or path.
+pub(crate) fn nonce(fingerprint: &Fingerprint, seckey_path: &Path) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN);
+    hasher.update(fingerprint.as_bytes());
+    hasher.update(seckey_path.as_os_str().as_encoded_bytes());
+
+    let mut nonce = String::with_capacity(32);
+    for b in &hasher.finalize()[..16] {
+        write!(nonce, "{b:02x}").expect("writing to a String cannot fail");
+    }
+    nonce
+}
+
+/// Fails unless the file at `path` exists and contains `nonce`.
+pub(crate) fn check(path: &Path, nonce: &str) -> anyhow::Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => bail!(
+            "{} does not exist, run with --dry-run first and write the nonce it prints there",
+            path.display()
+        ),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    if contents.trim() != nonce {
+        bail!(
+            "{} does not contain the nonce of this run, run with --dry-run again",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ssh_key::private::Ed25519Keypair;
+    use ssh_key::public::KeyData;
+
+    use super::*;
+
+    #[test]
+    fn requires_matching_nonce() {
+        let testdir = tempfile::tempdir().unwrap();
+        let confirmation_path = testdir.path().join("confirm");
+        let seckey_path = testdir.path().join("id_ed25519");
+        let fingerprint = KeyData::Ed25519(Ed25519Keypair::from_seed(&[7; 32]).public)
+            .fingerprint(Default::default());
+        let nonce = nonce(&fingerprint, &seckey_path);
+        assert_eq!(nonce.len(), 32);
+
+        let err = check(&confirmation_path, &nonce).unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+
+        let other = self::nonce(&fingerprint, &testdir.path().join("id_other"));
+        fs::write(&confirmation_path, &other).unwrap();
+        let err = check(&confirmation_path, &nonce).unwrap_err();
+        assert!(err.to_string().contains("does not contain the nonce"));
+
+        fs::write(&confirmation_path, format!("{nonce}\n")).unwrap();
+        check(&confirmation_path, &nonce).unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use clap::ValueEnum;
+use hkdf::Hkdf;
+use serde::Serialize;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// How key material is obtained from the 64-byte BIP39 seed.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Derivation {
+    /// Use the first 32 bytes of the seed for every kind of key, as earlier
+    /// versions did
+    #[default]
+    Legacy,
+    /// Expand the seed with HKDF-SHA256 using a distinct label per purpose
+    /// and curve, so keys for different purposes are independent
+    Hkdf,
+}
+
+/// Separates keys derived for different purposes from the same seed.
+///
+/// With [`Derivation::Hkdf`] the key material is
+/// `HKDF-SHA256(salt = "bip39-keygen", ikm = seed, info = label)` where the
+/// label is `bip39-keygen/v1/<purpose>/<curve>`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Domain {
+    purpose: &'static str,
+    curve: &'static str,
+}
+
+/// The domain of SSH keys, also used for signing with them.
+pub(crate) const SSH_ED25519: Domain = Domain::new("ssh", "ed25519");
+/// The domain of minisign keys.
+pub(crate) const MINISIGN_ED25519: Domain = Domain::new("minisign", "ed25519");
+/// The domain of WireGuard keys.
+pub(crate) const WIREGUARD_X25519: Domain = Domain::new("wireguard", "x25519");
+
+const SALT: &[u8] = b"bip39-keygen";
+
+impl Domain {
+    pub(crate) const fn new(purpose: &'static str, curve: &'static str) -> Self {
+        Self { purpose, curve }
+    }
+
+    pub(crate) fn label(&self) -> String {
+        format!("bip39-keygen/v1/{}/{}", self.purpose, self.curve)
+    }
+}
+
+/// Derives 32 bytes of key material for `domain` from a BIP39 seed.
+pub(crate) fn derive_key(
+    seed: &[u8; 64],
+    derivation: Derivation,
+    domain: Domain,
+) -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    match derivation {
+        Derivation::Legacy => key.copy_from_slice(&seed[..32]),
+        Derivation::Hkdf => Hkdf::<Sha256>::new(Some(SALT), seed)
+            .expand(domain.label().as_bytes(), key.as_mut())
+            .expect("32 bytes is a valid length for HKDF-SHA256"),
+    }
+    key
+}
+
+const USER_DOMAIN_SALT: &[u8] = b"bip39-keygen/v1/user-domain";
+
+/// Separates keys derived from the same seed by a domain the user picks,
+/// such as `github` or `personal`, on top of either [`Derivation`].
+///
+/// The key material is replaced with
+/// `HKDF-SHA256(salt = "bip39-keygen/v1/user-domain", ikm = key, info = user_domain)`.
+pub(crate) fn separate_user_domain(key: &[u8; 32], user_domain: &str) -> Zeroizing<[u8; 32]> {
+    let mut separated = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(USER_DOMAIN_SALT), key)
+        .expand(user_domain.as_bytes(), separated.as_mut())
+        .expect("32 bytes is a valid length for HKDF-SHA256");
+    separated
+}
+
+#[cfg(test)]
+mod tests {
+
+    use hkdf::Hkdf;
+
+    use super::*;
+
+    const SEED: [u8; 64] = [7; 64];
+
+    #[test]
+    fn labels() {
+        assert_eq!(SSH_ED25519.label(), "bip39-keygen/v1/ssh/ed25519");
+        assert_eq!(MINISIGN_ED25519.label(), "bip39-keygen/v1/minisign/ed25519");
+    }
+
+    #[test]
+    fn legacy_ignores_domain() {
+        let ssh = derive_key(&SEED, Derivation::Legacy, SSH_ED25519);
+        let minisign = derive_key(&SEED, Derivation::Legacy, MINISIGN_ED25519);
+
+        assert_eq!(ssh.as_ref(), &SEED[..32]);
+        assert_eq!(ssh, minisign);
+    }
+
+    #[test]
+    fn legacy_matches_split_array_ref() {
+        // The first half of the seed, byte for byte as the earlier
+        // `seed.split_array_ref::<32>()` returned it.
+        let seed = bip39::Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap()
+        .to_seed("");
+        let key = derive_key(&seed, Derivation::Legacy, SSH_ED25519);
+
+        assert_eq!(
+            hex::encode(key.as_ref()),
+            "878386efb78845b3355bd15ea4d39ef97d179cb712b77d5c12b6be415fffeffe"
+        );
+    }
+
+    #[test]
+    fn user_domains_are_independent() {
+        let key = derive_key(&SEED, Derivation::Legacy, SSH_ED25519);
+
+        let github = separate_user_domain(&key, "github");
+        let personal = separate_user_domain(&key, "personal");
+        assert_ne!(github, personal);
+        assert_ne!(github, key);
+        assert_eq!(github, separate_user_domain(&key, "github"));
+    }
+
+    #[test]
+    fn hkdf_domains_are_independent() {
+        let eth = Domain::new("eth", "secp256k1");
+
+        let ssh_key = derive_key(&SEED, Derivation::Hkdf, SSH_ED25519);
+        let eth_key = derive_key(&SEED, Derivation::Hkdf, eth);
+
+        assert_ne!(ssh_key, eth_key);
+        assert_ne!(ssh_key.as_ref(), &SEED[..32]);
+        assert_eq!(ssh_key, derive_key(&SEED, Derivation::Hkdf, SSH_ED25519));
+
+        // Each key is only reachable from the seed: feeding one key into
+        // the construction in place of the seed does not yield the other.
+        let mut from_ssh_key = [0u8; 32];
+        Hkdf::<Sha256>::new(Some(SALT), ssh_key.as_ref())
+            .expand(eth.label().as_bytes(), &mut from_ssh_key)
+            .unwrap();
+        assert_ne!(&from_ssh_key, eth_key.as_ref());
+    }
+}
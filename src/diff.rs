@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use ssh_key::Fingerprint;
+
+/// How a key differs between two directories, matched by the file name of
+/// its public key.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Change {
+    Identical(Fingerprint),
+    Changed { from: Fingerprint, to: Fingerprint },
+    Added(Fingerprint),
+    Removed(Fingerprint),
+}
+
+/// A key of either directory and how it differs.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub(crate) name: String,
+    pub(crate) change: Change,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = &self.name;
+        match &self.change {
+            Change::Identical(fingerprint) => write!(f, "  {name} {fingerprint}"),
+            Change::Changed { from, to } => write!(f, "~ {name} {from} -> {to}"),
+            Change::Added(fingerprint) => write!(f, "+ {name} {fingerprint}"),
+            Change::Removed(fingerprint) => write!(f, "- {name} {fingerprint}"),
+        }
+    }
+}
+
+/// Compares the `.pub` public keys of the directories `a` and `b` by their
+/// SHA256 fingerprints, sorted by file name.
+pub(crate) fn diff(a: &Path, b: &Path) -> anyhow::Result<Vec<Entry>> {
+    let a = fingerprints(a)?;
+    let b = fingerprints(b)?;
+    let names: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            let change = match (a.get(name), b.get(name)) {
+                (Some(from), Some(to)) if from == to => Change::Identical(*to),
+                (Some(from), Some(to)) => Change::Changed {
+                    from: *from,
+                    to: *to,
+                },
+                (None, Some(to)) => Change::Added(*to),
+                (Some(from), None) => Change::Removed(*from),
+                (None, None) => unreachable!("the name is from either directory"),
+            };
+            Entry {
+                name: name.clone(),
+                change,
+            }
+        })
+        .collect())
+}
+
+fn fingerprints(dir: &Path) -> anyhow::Result<BTreeMap<String, Fingerprint>> {
+    let mut fingerprints = BTreeMap::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))? {
+        let path = entry?.path();
+        if path.extension() != Some("pub".as_ref()) || !path.is_file() {
+            continue;
+        }
+        let public_key = ssh_key::PublicKey::read_openssh_file(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let name = path
+            .file_name()
+            .expect("read_dir entries have a file name")
+            .to_string_lossy()
+            .into_owned();
+        fingerprints.insert(name, public_key.fingerprint(Default::default()));
+    }
+    Ok(fingerprints)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ssh_key::private::Ed25519Keypair;
+    use ssh_key::public::KeyData;
+    use ssh_key::PublicKey;
+
+    use super::*;
+
+    fn write_key(dir: &Path, name: &str, seed: u8) -> Fingerprint {
+        let public_key = PublicKey::new(
+            KeyData::Ed25519(Ed25519Keypair::from_seed(&[seed; 32]).public),
+            "me",
+        );
+        fs::write(dir.join(name), public_key.to_openssh().unwrap()).unwrap();
+        public_key.fingerprint(Default::default())
+    }
+
+    #[test]
+    fn classifies_keys() {
+        let a = tempfile::tempdir().unwrap();
+        let b = tempfile::tempdir().unwrap();
+        let same = write_key(a.path(), "id_same.pub", 1);
+        write_key(b.path(), "id_same.pub", 1);
+        let old = write_key(a.path(), "id_rotated.pub", 2);
+        let new = write_key(b.path(), "id_rotated.pub", 3);
+        let removed = write_key(a.path(), "id_removed.pub", 4);
+        let added = write_key(b.path(), "id_added.pub", 5);
+        fs::write(b.path().join("id_added"), "not a public key").unwrap();
+
+        let entry = |name: &str, change| Entry {
+            name: name.to_string(),
+            change,
+        };
+        assert_eq!(diff(a.path(), b.path()).unwrap(), [
+            entry("id_added.pub", Change::Added(added)),
+            entry("id_removed.pub", Change::Removed(removed)),
+            entry("id_rotated.pub", Change::Changed { from: old, to: new }),
+            entry("id_same.pub", Change::Identical(same)),
+        ]);
+    }
+}
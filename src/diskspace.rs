@@ -0,0 +1,77 @@
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// What a written file takes at least on most file systems: one block.
+const BYTES_PER_FILE: u64 = 4096;
+
+/// Returns a warning if the file system that `files` files are about to be
+/// written to, in the directory of `path`, does not have room for them, so
+/// that the user learns it before a write fails halfway and is rolled back.
+///
+/// This is best-effort: if the free space cannot be read, there is no
+/// warning.
+pub(crate) fn check_free_space(path: &Path, files: usize) -> Option<String> {
+    free_space_warning(path, files, available_bytes)
+}
+
+fn free_space_warning(
+    path: &Path,
+    files: usize,
+    available: impl FnOnce(&Path) -> io::Result<u64>,
+) -> Option<String> {
+    // The directory itself may only be created by the transaction.
+    let dir = path.ancestors().skip(1).find(|dir| dir.is_dir())?;
+    let available = available(dir).ok()?;
+    let needed = BYTES_PER_FILE * files as u64;
+    (available < needed).then(|| {
+        format!(
+            "only {available} bytes are free on the file system of {}, writing the keys \
+             needs about {needed}",
+            dir.display()
+        )
+    })
+}
+
+/// Returns the bytes an unprivileged user can still write to the file
+/// system of `dir`.
+fn available_bytes(dir: &Path) -> io::Result<u64> {
+    let dir = CString::new(dir.as_os_str().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `dir` is NUL-terminated and `stat` is only read once filled.
+    if unsafe { libc::statvfs(dir.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn warns_when_space_is_low() {
+        let testdir = tempfile::tempdir().unwrap();
+        let path = testdir.path().join("keys/id_ed25519");
+
+        let mut checked = None;
+        let warning = free_space_warning(&path, 2, |dir| {
+            checked = Some(dir.to_path_buf());
+            Ok(4096)
+        })
+        .unwrap();
+        assert!(warning.contains("only 4096 bytes are free"));
+        assert_eq!(checked.as_deref(), Some(testdir.path()));
+
+        assert_eq!(free_space_warning(&path, 2, |_| Ok(1 << 30)), None);
+        assert_eq!(
+            free_space_warning(&path, 2, |_| Err(io::Error::from(io::ErrorKind::Other))),
+            None
+        );
+        assert!(available_bytes(testdir.path()).unwrap() > 0);
+    }
+}
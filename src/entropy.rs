@@ -0,0 +1,100 @@
+use anyhow::bail;
+use zeroize::Zeroizing;
+
+/// Returns the number of entropy bytes behind a mnemonic of `word_count` words.
+pub(crate) fn len_for_word_count(word_count: usize) -> anyhow::Result<usize> {
+    match word_count {
+        12 | 15 | 18 | 21 | 24 => Ok(word_count * 4 / 3),
+        _ => bail!("word count must be 12, 15, 18, 21 or 24, got {word_count}"),
+    }
+}
+
+/// Parses hex encoded entropy.
+pub(crate) fn from_hex(s: &str) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let s = s.trim();
+    if s.len() % 2 != 0 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        bail!("entropy should be an even number of hex digits");
+    }
+    let mut entropy = Zeroizing::new(Vec::with_capacity(s.len() / 2));
+    for i in (0..s.len()).step_by(2) {
+        entropy.push(u8::from_str_radix(&s[i..i + 2], 16)?);
+    }
+    Ok(entropy)
+}
+
+/// Converts dice rolls into `len` bytes of entropy.
+///
+/// Each roll yields bits without bias: 1, 2, 3 and 4 give `00`, `01`, `10`
+/// and `11`, while 5 and 6 give a single `0` or `1`. Bits fill the bytes
+/// from the most significant bit on, and rolls left over once `len` bytes
+/// are filled are ignored. Whitespace between rolls is ignored.
+pub(crate) fn from_dice(rolls: &str, len: usize) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let mut entropy = Zeroizing::new(vec![0u8; len]);
+    let mut bits = 0;
+    for roll in rolls.chars().filter(|c| !c.is_whitespace()) {
+        let (value, width) = match roll {
+            '1'..='4' => (roll as u8 - b'1', 2),
+            '5'..='6' => (roll as u8 - b'5', 1),
+            _ => bail!("invalid dice roll '{roll}', expected 1 to 6"),
+        };
+        for shift in (0..width).rev() {
+            if bits == len * 8 {
+                break;
+            }
+            entropy[bits / 8] |= ((value >> shift) & 1) << (7 - bits % 8);
+            bits += 1;
+        }
+    }
+    if bits < len * 8 {
+        bail!(
+            "not enough dice rolls: got {bits} of {} bits, about {} more rolls are needed",
+            len * 8,
+            ((len * 8 - bits) * 3).div_ceil(5)
+        );
+    }
+    Ok(entropy)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bip39::Mnemonic;
+
+    use super::*;
+
+    #[test]
+    fn dice_mnemonic_is_deterministic() {
+        // 64 rolls of 2 bits give the 128 bits of a 12-word mnemonic.
+        let rolls = "1234".repeat(16);
+        let entropy = from_dice(&rolls, len_for_word_count(12).unwrap()).unwrap();
+        assert_eq!(entropy.as_slice(), [0x1b; 16]);
+
+        let mnemonic = Mnemonic::from_entropy(&entropy).unwrap();
+        assert_eq!(
+            mnemonic.to_string(),
+            Mnemonic::from_entropy(&[0x1b; 16]).unwrap().to_string()
+        );
+        Mnemonic::parse(mnemonic.to_string()).unwrap();
+    }
+
+    #[test]
+    fn dice_single_bit_rolls() {
+        let entropy = from_dice("5 6 5 6 6 6 5 5 4", 1).unwrap();
+        assert_eq!(entropy.as_slice(), [0b0101_1100]);
+    }
+
+    #[test]
+    fn dice_validation() {
+        assert!(from_dice(&"1".repeat(63), 16).is_err());
+        assert!(from_dice(&"1".repeat(64), 16).is_ok());
+        assert!(from_dice("7", 1).is_err());
+        assert!(len_for_word_count(13).is_err());
+    }
+
+    #[test]
+    fn hex_entropy() {
+        assert_eq!(from_hex("00ff7f").unwrap().as_slice(), [0x00, 0xff, 0x7f]);
+        assert!(from_hex("0").is_err());
+        assert!(from_hex("zz").is_err());
+    }
+}
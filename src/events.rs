@@ -0,0 +1,93 @@
+use std::fs;
+use std::io::Write;
+use std::os::fd::FromRawFd;
+use std::os::fd::RawFd;
+use std::path::Path;
+
+use anyhow::bail;
+use serde::Serialize;
+
+/// A step of `ssh`, reported to a parent process as one NDJSON line on
+/// `--event-fd`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub(crate) enum Event<'a> {
+    /// The key pair was derived, but nothing was written yet.
+    Derived { fingerprint: String },
+    /// A file was written, which still goes away if the run fails.
+    Written { path: &'a Path },
+    /// Every file was written and is kept.
+    Committed,
+}
+
+/// Where [`Event`]s go, if anywhere.
+pub(crate) struct Events {
+    file: Option<fs::File>,
+}
+
+impl Events {
+    /// Takes over the inherited file descriptor `fd`, closing it once done.
+    pub(crate) fn open(fd: Option<RawFd>) -> anyhow::Result<Self> {
+        let Some(fd) = fd else {
+            return Ok(Self { file: None });
+        };
+        // Only a descriptor that is open is safe to take over.
+        if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+            bail!("--event-fd {fd} is not an open file descriptor");
+        }
+        Ok(Self {
+            file: Some(unsafe { fs::File::from_raw_fd(fd) }),
+        })
+    }
+
+    pub(crate) fn emit(&mut self, event: Event<'_>) -> anyhow::Result<()> {
+        if let Some(file) = &mut self.file {
+            let mut line = serde_json::to_string(&event)?;
+            line.push('\n');
+            file.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn ssh_events_on_pipe() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let [read_fd, write_fd] = fds;
+
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519");
+        let pubkey_path = testdir.path().join("id_ed25519.pub");
+        // The run takes over the write end and closes it when done.
+        crate::tests::run_ssh(&seckey_path, &["-N", "--event-fd", &write_fd.to_string()]).unwrap();
+
+        let mut output = String::new();
+        unsafe { fs::File::from_raw_fd(read_fd) }
+            .read_to_string(&mut output)
+            .unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let fingerprint = ssh_key::PublicKey::read_openssh_file(&pubkey_path)
+            .unwrap()
+            .fingerprint(Default::default())
+            .to_string();
+        assert_eq!(lines, [
+            serde_json::json!({"event": "derived", "fingerprint": fingerprint}),
+            serde_json::json!({"event": "written", "path": seckey_path}),
+            serde_json::json!({"event": "written", "path": pubkey_path}),
+            serde_json::json!({"event": "committed"}),
+        ]);
+
+        assert!(Events::open(Some(-1)).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use crate::transaction::Transaction;
+
+/// Returns the global git config file: `~/.gitconfig`, unless only
+/// `$XDG_CONFIG_HOME/git/config` exists, as git itself decides.
+pub(crate) fn global_config_path() -> anyhow::Result<PathBuf> {
+    let home = home::home_dir()
+        .filter(|path| !path.as_os_str().is_empty())
+        .context("cannot find the home directory, specify --git-config-path")?;
+    let gitconfig = home.join(".gitconfig");
+    let xdg_config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|path| !path.is_empty())
+        .map_or_else(|| home.join(".config"), PathBuf::from);
+    let xdg = xdg_config_home.join("git").join("config");
+    if !gitconfig.exists() && xdg.exists() {
+        Ok(xdg)
+    } else {
+        Ok(gitconfig)
+    }
+}
+
+/// Points git at `signing_key` for signing commits with SSH, by setting
+/// `user.signingkey` and `gpg.format = ssh` in the config at `config_path`.
+/// Other settings in the file are kept as they are, and like git, a
+/// symlinked config file is updated where it points to.
+pub(crate) fn write_signing_config(
+    tx: &mut Transaction,
+    config_path: &Path,
+    signing_key: &Path,
+) -> anyhow::Result<()> {
+    let signing_key = std::path::absolute(signing_key)?;
+    let Some(signing_key) = signing_key.to_str() else {
+        anyhow::bail!(
+            "{} is not valid UTF-8, which git config cannot store",
+            signing_key.display()
+        );
+    };
+    let config_path = match fs::canonicalize(config_path) {
+        Ok(path) => path,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => config_path.to_path_buf(),
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to read {}", config_path.display()));
+        }
+    };
+    let mut config = match fs::read_to_string(&config_path) {
+        Ok(config) => config,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(e).with_context(|| format!("failed to read {}", config_path.display()));
+        }
+    };
+    config = set(&config, "user", "signingkey", signing_key);
+    config = set(&config, "gpg", "format", "ssh");
+    tx.write_file(&config_path, config)?;
+    Ok(())
+}
+
+/// Sets `<section>.<key>` to `value` in the git config `config`, replacing
+/// the first existing value, or adding the key or the section if missing.
+fn set(config: &str, section: &str, key: &str, value: &str) -> String {
+    let entry = format!("\t{key} = {}", quote(value));
+    let mut lines: Vec<String> = config.lines().map(str::to_string).collect();
+
+    let mut current = None;
+    let mut section_end = None;
+    let mut existing = None;
+    for (i, line) in lines.iter().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header.split([']', ' ', '"']).next().unwrap_or_default();
+            // Subsections such as `[gpg "ssh"]` are distinct sections.
+            let plain = !header.contains('"');
+            current = Some(plain && name.eq_ignore_ascii_case(section));
+            if current == Some(true) {
+                section_end = Some(i + 1);
+            }
+            continue;
+        }
+        if current != Some(true) {
+            continue;
+        }
+        section_end = Some(i + 1);
+        let name = line.split(['=', ' ', '\t']).next().unwrap_or_default();
+        if name.eq_ignore_ascii_case(key) {
+            existing = Some(i);
+            break;
+        }
+    }
+
+    match (existing, section_end) {
+        (Some(i), _) => lines[i] = entry,
+        (None, Some(end)) => lines.insert(end, entry),
+        (None, None) => {
+            lines.push(format!("[{section}]"));
+            lines.push(entry);
+        }
+    }
+    join(lines)
+}
+
+fn join(lines: Vec<String>) -> String {
+    let mut config = lines.join("\n");
+    config.push('\n');
+    config
+}
+
+/// Quotes `value` if git would otherwise read it differently.
+fn quote(value: &str) -> String {
+    if value.is_empty()
+        || value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+        || value.contains(['"', '\\', ';', '#'])
+    {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn set_adds_and_replaces() {
+        assert_eq!(
+            set("", "user", "signingkey", "/k.pub"),
+            "[user]\n\tsigningkey = /k.pub\n"
+        );
+        assert_eq!(
+            set(
+                "[user]\n\tname = Me\n[core]\n\teditor = vi\n",
+                "user",
+                "signingkey",
+                "/k.pub"
+            ),
+            "[user]\n\tname = Me\n\tsigningkey = /k.pub\n[core]\n\teditor = vi\n"
+        );
+        assert_eq!(
+            set(
+                "[user]\n\tsigningKey = /old.pub\n[gpg \"ssh\"]\n\tformat = x\n",
+                "user",
+                "signingkey",
+                "/k.pub"
+            ),
+            "[user]\n\tsigningkey = /k.pub\n[gpg \"ssh\"]\n\tformat = x\n"
+        );
+        assert_eq!(
+            set("[gpg \"ssh\"]\n\tprogram = x\n", "gpg", "format", "ssh"),
+            "[gpg \"ssh\"]\n\tprogram = x\n[gpg]\n\tformat = ssh\n"
+        );
+        assert_eq!(quote("/a b;c"), "\"/a b;c\"");
+    }
+
+    #[test]
+    fn signing_config() {
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+        let config_path = testdir.path().join("gitconfig");
+        let signing_key = testdir.path().join("id_ed25519.pub");
+        fs::write(&config_path, "[user]\n\tname = Me\n").unwrap();
+
+        let mut tx = Transaction::new(txdir);
+        write_signing_config(&mut tx, &config_path, &signing_key).unwrap();
+        tx.commit();
+
+        let config = fs::read_to_string(&config_path).unwrap();
+        assert!(
+            config
+                .lines()
+                .any(|line| line == format!("\tsigningkey = {}", signing_key.display()))
+        );
+        assert!(config.contains("\tname = Me\n"));
+        assert!(config.ends_with("[gpg]\n\tformat = ssh\n"));
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::Path;
+
+use aes_gcm::aead::Aead;
+use aes_gcm::aead::OsRng;
+use aes_gcm::aead::Payload;
+use aes_gcm::AeadCore;
+use aes_gcm::Aes256Gcm;
+use aes_gcm::KeyInit;
+use anyhow::bail;
+use anyhow::Context;
+use base64ct::Base64UrlUnpadded;
+use base64ct::Encoding;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::traits::PublicKeyParts;
+use rsa::Oaep;
+use rsa::RsaPublicKey;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// The protected header of every JWE this writes.
+const HEADER: &str = r#"{"alg":"RSA-OAEP-256","enc":"A256GCM"}"#;
+
+/// Smaller RSA keys are too weak to wrap a private key with.
+const MIN_RSA_BITS: usize = 2048;
+
+/// Reads the RSA public key of the recipient from a PEM file, either as
+/// `PUBLIC KEY` (SPKI) or `RSA PUBLIC KEY` (PKCS#1).
+pub(crate) fn read_recipient(path: &Path) -> anyhow::Result<RsaPublicKey> {
+    let pem = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let key = RsaPublicKey::from_public_key_pem(&pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(&pem))
+        .with_context(|| format!("{} is not a PEM RSA public key", path.display()))?;
+    if key.size() * 8 < MIN_RSA_BITS {
+        bail!(
+            "{} is a {}-bit RSA key, at least {MIN_RSA_BITS} bits are needed",
+            path.display(),
+            key.size() * 8
+        );
+    }
+    Ok(key)
+}
+
+/// Encrypts `plaintext` to `recipient` as a compact JWE (RFC 7516): a random
+/// AES-256-GCM key encrypts `plaintext` and is itself wrapped with
+/// RSA-OAEP-256, so any JOSE library can decrypt it with the RSA private key.
+pub(crate) fn encrypt(recipient: &RsaPublicKey, plaintext: &[u8]) -> anyhow::Result<String> {
+    let cek = Zeroizing::new(Aes256Gcm::generate_key(OsRng));
+    let encrypted_key = recipient.encrypt(&mut OsRng, Oaep::new::<Sha256>(), &cek)?;
+    let iv = Aes256Gcm::generate_nonce(OsRng);
+
+    let header = Base64UrlUnpadded::encode_string(HEADER.as_bytes());
+    let mut ciphertext = Aes256Gcm::new(&cek)
+        .encrypt(&iv, Payload {
+            msg: plaintext,
+            aad: header.as_bytes(),
+        })
+        .map_err(|_| anyhow::anyhow!("failed to encrypt with AES-256-GCM"))?;
+    let tag = ciphertext.split_off(ciphertext.len() - 16);
+
+    Ok([
+        header,
+        Base64UrlUnpadded::encode_string(&encrypted_key),
+        Base64UrlUnpadded::encode_string(&iv),
+        Base64UrlUnpadded::encode_string(&ciphertext),
+        Base64UrlUnpadded::encode_string(&tag),
+    ]
+    .join("."))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rsa::pkcs8::EncodePublicKey;
+    use rsa::RsaPrivateKey;
+    use ssh_key::private::Ed25519Keypair;
+
+    use super::*;
+
+    fn decrypt(key: &RsaPrivateKey, jwe: &str) -> Vec<u8> {
+        let parts: Vec<&str> = jwe.split('.').collect();
+        let [header, encrypted_key, iv, ciphertext, tag] = parts[..] else {
+            panic!("expected 5 parts, got {}", parts.len());
+        };
+        let decode = |part: &str| Base64UrlUnpadded::decode_vec(part).unwrap();
+        assert_eq!(decode(header), HEADER.as_bytes());
+
+        let cek = key
+            .decrypt(Oaep::new::<Sha256>(), &decode(encrypted_key))
+            .unwrap();
+        let mut msg = decode(ciphertext);
+        msg.extend(decode(tag));
+        Aes256Gcm::new_from_slice(&cek)
+            .unwrap()
+            .decrypt(decode(iv).as_slice().into(), Payload {
+                msg: &msg,
+                aad: header.as_bytes(),
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn decrypts_to_plaintext() {
+        let testdir = tempfile::tempdir().unwrap();
+        let key = RsaPrivateKey::new(&mut OsRng, MIN_RSA_BITS).unwrap();
+        let recipient_path = testdir.path().join("recipient.pem");
+        key.to_public_key()
+            .write_public_key_pem_file(&recipient_path, Default::default())
+            .unwrap();
+
+        let secret_key = ssh_key::PrivateKey::new(
+            ssh_key::private::KeypairData::Ed25519(Ed25519Keypair::from_seed(&[7; 32])),
+            "comment",
+        )
+        .unwrap();
+        let openssh = secret_key.to_openssh(Default::default()).unwrap();
+
+        let recipient = read_recipient(&recipient_path).unwrap();
+        let jwe = encrypt(&recipient, openssh.as_bytes()).unwrap();
+        assert!(!jwe.contains("OPENSSH"));
+        let decrypted = String::from_utf8(decrypt(&key, &jwe)).unwrap();
+        assert_eq!(
+            ssh_key::PrivateKey::from_openssh(decrypted).unwrap(),
+            secret_key
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+use bip39::Mnemonic;
+use keyring::Entry;
+use secrecy::SecretString;
+use zeroize::Zeroizing;
+
+/// A mnemonic stored in the OS keyring, given as `<service>:<account>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct KeyringRef {
+    service: String,
+    account: String,
+}
+
+impl FromStr for KeyringRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((service, account)) if !service.is_empty() && !account.is_empty() => Ok(Self {
+                service: service.to_string(),
+                account: account.to_string(),
+            }),
+            _ => bail!("expected <service>:<account>"),
+        }
+    }
+}
+
+impl KeyringRef {
+    pub(crate) fn entry(&self) -> keyring::Result<Entry> {
+        Entry::new(&self.service, &self.account)
+    }
+}
+
+/// Reads the mnemonic stored in `entry`.
+pub(crate) fn load(entry: &Entry) -> keyring::Result<SecretString> {
+    entry.get_password().map(SecretString::new)
+}
+
+/// Stores `mnemonic` in `entry`, replacing any previous value.
+pub(crate) fn store(entry: &Entry, mnemonic: &Mnemonic) -> keyring::Result<()> {
+    let words = Zeroizing::new(mnemonic.to_string());
+    entry.set_password(&words)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ::keyring::mock;
+    use secrecy::ExposeSecret;
+
+    use super::*;
+
+    #[test]
+    fn parse_ref() {
+        let keyring_ref: KeyringRef = "bip39-keygen:me@example.com".parse().unwrap();
+        assert_eq!(keyring_ref.service, "bip39-keygen");
+        assert_eq!(keyring_ref.account, "me@example.com");
+
+        assert!("bip39-keygen".parse::<KeyringRef>().is_err());
+        assert!(":me".parse::<KeyringRef>().is_err());
+    }
+
+    #[test]
+    fn store_then_derive() {
+        let credential = mock::default_credential_builder()
+            .build(None, "bip39-keygen", "me")
+            .unwrap();
+        let entry = Entry::new_with_credential(credential);
+        let mnemonic = Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+
+        store(&entry, &mnemonic).unwrap();
+        let loaded = load(&entry).unwrap();
+        let loaded = crate::mnemonic::parse(loaded.expose_secret(), None).unwrap();
+
+        let derive = |mnemonic: &Mnemonic| {
+            crate::derive_ed25519_keypair(
+                mnemonic,
+                &SecretString::new(String::new()),
+                crate::derivation::Derivation::Legacy,
+                crate::derivation::SSH_ED25519,
+                None,
+            )
+            .public
+        };
+        assert_eq!(loaded, mnemonic);
+        assert_eq!(derive(&loaded), derive(&mnemonic));
+    }
+}
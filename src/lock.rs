@@ -0,0 +1,139 @@
+use std::fs;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use anyhow::bail;
+use anyhow::Context;
+
+/// How long to wait between attempts to take a held lock.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// An exclusive `flock` on a directory, so that runs writing into the same
+/// directory take turns. It is released when dropped, or when the process
+/// exits.
+#[derive(Debug)]
+pub(crate) struct DirLock {
+    _dir: fs::File,
+}
+
+impl DirLock {
+    /// Locks the directory `path` will be written to, waiting up to `timeout`
+    /// for another run to release it.
+    ///
+    /// The directory is created first if it is missing, and kept even if the
+    /// run fails: locking an existing ancestor instead would let a run that
+    /// starts once the directory exists lock another directory.
+    pub(crate) fn acquire(path: &Path, timeout: Duration) -> anyhow::Result<Self> {
+        let Some(dir) = path.parent() else {
+            bail!("no directory to lock for {}", path.display());
+        };
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        let file =
+            fs::File::open(dir).with_context(|| format!("failed to open {}", dir.display()))?;
+        let start = Instant::now();
+        loop {
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+                return Ok(Self { _dir: file });
+            }
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::WouldBlock {
+                return Err(e).with_context(|| format!("failed to lock {}", dir.display()));
+            }
+            if start.elapsed() >= timeout {
+                bail!(
+                    "another run is writing to {}, gave up waiting after {}s",
+                    dir.display(),
+                    timeout.as_secs_f64()
+                );
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::process::Command;
+
+    use super::*;
+    use crate::transaction::Transaction;
+
+    /// The environment variable that makes [`lock_holder`] take the lock in
+    /// the directory it names.
+    const HOLDER_DIR: &str = "BIP39_KEYGEN_LOCK_HOLDER_DIR";
+
+    fn log(dir: &Path, line: String) {
+        use std::io::Write;
+
+        let mut log = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join("log"))
+            .unwrap();
+        writeln!(log, "{line}").unwrap();
+    }
+
+    /// One of the runs of `concurrent_runs_take_turns`, in a process of its
+    /// own. Does nothing when the tests are run as usual.
+    #[test]
+    fn lock_holder() {
+        let Some(dir) = std::env::var_os(HOLDER_DIR).map(std::path::PathBuf::from) else {
+            return;
+        };
+        let run = std::process::id().to_string();
+        // The transaction creates the directory, as it does for new keys.
+        let seckey_path = dir.join("keys").join("id_ed25519");
+        let _lock = DirLock::acquire(&seckey_path, Duration::from_secs(10)).unwrap();
+        log(&dir, format!("{run} start"));
+        let mut tx =
+            Transaction::without_temp_dir().with_backup_mode(crate::transaction::BackupMode::None);
+        tx.create_dir_all(seckey_path.parent().unwrap()).unwrap();
+        tx.write_file(&seckey_path, run.repeat(4096)).unwrap();
+        // The other run would replace the file right now.
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(fs::read_to_string(&seckey_path).unwrap(), run.repeat(4096));
+        tx.commit();
+        log(&dir, format!("{run} end"));
+    }
+
+    #[test]
+    fn concurrent_runs_take_turns() {
+        let testdir = tempfile::tempdir().unwrap();
+        let spawn = || {
+            Command::new(std::env::current_exe().unwrap())
+                .args(["lock::tests::lock_holder", "--exact", "--nocapture"])
+                .env(HOLDER_DIR, testdir.path())
+                .spawn()
+                .unwrap()
+        };
+
+        let mut first = spawn();
+        // The second run starts while the first holds the lock.
+        let started = Instant::now();
+        while !testdir.path().join("log").exists() {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(10));
+        }
+        let mut second = spawn();
+        assert!(first.wait().unwrap().success());
+        assert!(second.wait().unwrap().success());
+
+        let log = fs::read_to_string(testdir.path().join("log")).unwrap();
+        let log: Vec<&str> = log.lines().collect();
+        assert_eq!(log.len(), 4);
+        for turn in log.chunks(2) {
+            let run = turn[0].strip_suffix(" start").unwrap();
+            assert_eq!(turn[1], format!("{run} end"));
+        }
+
+        let seckey_path = testdir.path().join("keys").join("id_ed25519");
+        let _lock = DirLock::acquire(&seckey_path, Duration::ZERO).unwrap();
+        let err = DirLock::acquire(&seckey_path, Duration::ZERO).unwrap_err();
+        assert!(err.to_string().starts_with("another run is writing to"));
+    }
+}
@@ -1,25 +1,75 @@
-#![feature(split_array, io_error_more)]
-
+use std::io::IsTerminal;
 use std::path;
 use std::path::Path;
 use std::path::PathBuf;
-use std::str::FromStr;
+use std::time::Duration;
+use std::time::SystemTime;
 
+use aes_gcm::aead::OsRng;
 use anyhow::bail;
+use anyhow::Context;
 use bip39::Language;
 use bip39::Mnemonic;
+use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
 use clap::ValueEnum;
+use derivation::Derivation;
+use mnemonic::WordlistLanguage;
 use secrecy::ExposeSecret;
 use secrecy::SecretString;
+use serde::Deserialize;
+use serde::Serialize;
+use transaction::BackupMode;
 use transaction::Transaction;
 use zeroize::Zeroizing;
 
+mod accounts;
+mod agent;
+mod authorized_keys;
+mod batch;
+mod bench;
+mod checksum_sheet;
+mod cidr;
+mod combined;
+mod confirmation;
+mod derivation;
+mod diff;
+#[cfg(unix)]
+mod diskspace;
+mod entropy;
+#[cfg(unix)]
+mod events;
+mod gitconfig;
+mod jwe;
+mod keyring;
+#[cfg(unix)]
+mod lock;
+mod metadata;
+mod minisign;
+mod mnemonic;
+#[cfg(unix)]
+mod mode;
+#[cfg(target_os = "linux")]
+mod mounts;
+mod paper;
+mod passphrase;
+mod probe;
+mod recover;
+#[cfg(target_os = "linux")]
+mod rng;
+#[cfg(target_os = "linux")]
+mod selinux;
+mod sign;
+mod slip10;
+mod totp;
 mod transaction;
 mod version;
+mod wireguard;
+mod wordlist;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 enum KeyType {
     Ed25519,
 }
@@ -30,6 +80,352 @@ impl KeyType {
             KeyType::Ed25519 => "id_ed25519",
         }
     }
+
+    fn as_ssh_keygen_type(&self) -> &'static str {
+        match self {
+            KeyType::Ed25519 => "ed25519",
+        }
+    }
+}
+
+/// What `ssh --key-type` generates.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum KeyTypeArg {
+    Ed25519,
+    /// Every key type, each saved to its conventional file name in `~/.ssh`
+    All,
+}
+
+impl KeyTypeArg {
+    fn key_types(self) -> Vec<KeyType> {
+        match self {
+            KeyTypeArg::Ed25519 => vec![KeyType::Ed25519],
+            KeyTypeArg::All => KeyType::value_variants().to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum KeyFormat {
+    /// The OpenSSH private key format and an `authorized_keys` line
+    Openssh,
+    /// Raw libsodium `crypto_sign` keys, saved with the `.sk` and `.pk` extensions
+    Raw,
+    /// The OpenSSH private key base64-encoded into one line, saved with the `.b64` extension, and an `authorized_keys` line. `import` decodes it
+    Base64Openssh,
+}
+
+/// What the passphrase of `ssh` protects.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PassphraseUse {
+    /// Derive the seed from it as the BIP39 passphrase and encrypt the private key file with it
+    Both,
+    /// Only derive the seed from it, the private key file is written unencrypted
+    Seed,
+    /// Only encrypt the private key file with it, the seed is derived without a BIP39 passphrase
+    Encryption,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum RawSecretKey {
+    /// The 32-byte seed followed by the 32-byte public key, as libsodium's `crypto_sign_keypair` returns
+    Keypair,
+    /// The 32-byte seed, as passed to libsodium's `crypto_sign_seed_keypair`
+    Seed,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum LongCommentPolicy {
+    /// Abort with an error
+    Error,
+    /// Truncate the comment and print a warning
+    Truncate,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Preset {
+    /// A key for GitHub
+    Github,
+    /// A key for GitLab
+    Gitlab,
+    /// A key for logging into servers
+    Generic,
+}
+
+impl Preset {
+    fn comment(&self) -> String {
+        match self {
+            Preset::Github => format!("{} (github)", ssh_default_comment()),
+            Preset::Gitlab => format!("{} (gitlab)", ssh_default_comment()),
+            Preset::Generic => ssh_default_comment(),
+        }
+    }
+
+    /// Tells the user where to upload the public key.
+    fn upload_hint(&self, pubkey_path: &Path) -> String {
+        match self {
+            Preset::Github => format!(
+                "Add the contents of {} at https://github.com/settings/ssh/new",
+                pubkey_path.display()
+            ),
+            Preset::Gitlab => format!(
+                "Add the contents of {} at https://gitlab.com/-/user_settings/ssh_keys",
+                pubkey_path.display()
+            ),
+            Preset::Generic => format!(
+                "Install the public key on a server with `ssh-copy-id -i {} <user>@<host>`",
+                pubkey_path.display()
+            ),
+        }
+    }
+}
+
+/// Returns the commands that install the public key at `pubkey_path` where
+/// `preset` and `target` say it is used, to print after writing the key.
+fn next_steps(
+    preset: Option<Preset>,
+    pubkey_path: &Path,
+    comment: &str,
+    target: Option<&probe::Target>,
+) -> Vec<String> {
+    let pubkey_path = shell_quote(&pubkey_path.to_string_lossy());
+    let mut steps = Vec::new();
+    match preset {
+        Some(Preset::Github) => steps.push(format!(
+            "gh ssh-key add {pubkey_path} --title {}",
+            shell_quote(comment)
+        )),
+        Some(Preset::Gitlab) => steps.push(format!(
+            "glab ssh-key add {pubkey_path} --title {}",
+            shell_quote(comment)
+        )),
+        Some(Preset::Generic) | None => {}
+    }
+    match target {
+        Some(target) => steps.push(format!(
+            "ssh-copy-id -i {pubkey_path} {}",
+            target.ssh_destination()
+        )),
+        None if matches!(preset, Some(Preset::Generic) | None) => {
+            steps.push(format!("ssh-copy-id -i {pubkey_path} <user>@<host>"))
+        }
+        None => {}
+    }
+    steps
+}
+
+/// Quotes `s` for a POSIX shell, unless it only has characters that need no
+/// quoting.
+fn shell_quote(s: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./_-~".contains(c);
+    if !s.is_empty() && s.chars().all(is_plain) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+/// The shells `--emit-shell-env` prints variable assignments for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum ShellEnv {
+    /// `export` statements, for bash, zsh and other POSIX shells
+    Bash,
+    /// `set -gx` commands
+    Fish,
+}
+
+/// Returns the statements that set each of `vars` in `shell`, one per line.
+fn shell_env(shell: ShellEnv, vars: &[(&str, &str)]) -> String {
+    vars.iter()
+        .map(|(name, value)| match shell {
+            ShellEnv::Bash => format!("export {name}={}\n", shell_quote(value)),
+            // Fish only treats `\\` and `\'` specially in single quotes.
+            ShellEnv::Fish => format!(
+                "set -gx {name} '{}'\n",
+                value.replace('\\', r"\\").replace('\'', r"\'")
+            ),
+        })
+        .collect()
+}
+
+/// The SSH-keys APIs `--api-payload` writes a request body for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum ApiProvider {
+    /// `POST /user/keys` of the GitHub REST API
+    Github,
+    /// `POST /user/keys` of the GitLab REST API
+    Gitlab,
+}
+
+/// Returns the JSON body that adds `public_key` with the SSH-keys API of
+/// `provider`, titled with its comment. Both APIs take the same body for now.
+fn api_payload(provider: ApiProvider, public_key: &ssh_key::PublicKey) -> anyhow::Result<String> {
+    let key = public_key.to_openssh()?;
+    let payload = match provider {
+        ApiProvider::Github | ApiProvider::Gitlab => serde_json::json!({
+            "title": public_key.comment(),
+            "key": key,
+        }),
+    };
+    Ok(serde_json::to_string(&payload)?)
+}
+
+#[derive(Args)]
+struct MnemonicArgs {
+    /// Specify the mnemonic, split by spaces. If not specified, it will be prompted
+    #[arg(short = 'm', long, env)]
+    mnemonic: Option<SecretString>,
+    /// Specify the language of the mnemonic to try first. Other languages are detected from the words if it fails
+    #[arg(
+        short = 'l',
+        long,
+        env = "MNEMONIC_LANGUAGE",
+        alias = "input-language-hint"
+    )]
+    language: Option<WordlistLanguage>,
+    /// Read the mnemonic from the OS keyring entry `<service>:<account>`
+    #[arg(long, env, conflicts_with = "mnemonic")]
+    mnemonic_keyring: Option<keyring::KeyringRef>,
+    /// Read the mnemonic from this file, which may be a named pipe that another process writes it to
+    #[arg(long, env, conflicts_with_all = ["mnemonic", "mnemonic_keyring"])]
+    mnemonic_file: Option<PathBuf>,
+    /// Specify the total number of attempts at entering a prompted mnemonic, including the first, before failing
+    #[arg(long, env, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    max_mnemonic_attempts: u32,
+    /// Use the first generated mnemonic without asking whether to regenerate it. Overwriting files is still confirmed
+    #[arg(long, env, default_value_t = false)]
+    assume_yes_for_generation: bool,
+}
+
+/// The labels of the interactive passphrase prompts.
+#[derive(Args)]
+struct PromptLabels {
+    /// Specify the label of the passphrase prompt
+    #[arg(
+        long,
+        env,
+        default_value = "Enter passphrase (empty for no passphrase):"
+    )]
+    seed_passphrase_prompt_label: String,
+    /// Specify the label of the prompt that confirms the passphrase
+    #[arg(long, env, default_value = "Confirmation passphrase:")]
+    seed_passphrase_confirm_label: String,
+}
+
+#[derive(Args)]
+struct SeedArgs {
+    #[command(flatten)]
+    mnemonic_args: MnemonicArgs,
+    /// Specify an empty passphrase
+    #[arg(short = 'N', long, env, default_value_t = false)]
+    no_passphrase: bool,
+    /// Specify the passphrase, if empty it will be prompted. Repeat it to require several passphrases
+    #[arg(short, long, env)]
+    passphrase: Vec<SecretString>,
+    /// Read the passphrase from the first line of stdin
+    #[arg(long, env, conflicts_with_all = ["no_passphrase", "passphrase"])]
+    passphrase_stdin: bool,
+    /// Enter the prompted passphrase only once, without typing it again to confirm it
+    #[arg(long, env, default_value_t = false)]
+    no_passphrase_confirm: bool,
+    /// Get the prompted passphrase from gpg-agent, which asks for it once and caches it for the next runs
+    #[arg(
+        long,
+        env,
+        default_value_t = false,
+        conflicts_with = "passphrase_stdin"
+    )]
+    use_agent: bool,
+    #[command(flatten)]
+    prompt_labels: PromptLabels,
+    /// Specify how several passphrases are combined into one
+    #[arg(long, env, value_enum, default_value_t = passphrase::Combine::SortedConcat)]
+    combine_passphrases: passphrase::Combine,
+    /// Specify how the key is derived from the BIP39 seed
+    #[arg(long, env, value_enum, default_value_t = Derivation::Legacy)]
+    derivation: Derivation,
+    /// Derive an independent key for this domain, such as `github` or `personal`, from the same mnemonic
+    #[arg(long, env = "KEY_DOMAIN", value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    domain: Option<String>,
+}
+
+#[derive(Args)]
+struct OverwriteArgs {
+    /// Fail instead of prompting if a file to be written already exists
+    #[arg(long, env, default_value_t = false)]
+    fail_if_exists: bool,
+    /// Overwrite a file to be written without prompting if it is empty, such as a placeholder created by a deployment tool
+    #[arg(long, env, default_value_t = false)]
+    allow_existing_empty_file: bool,
+}
+
+#[derive(Args)]
+struct KeyPassphraseArgs {
+    /// Specify the passphrase of an encrypted private key, if not given it is prompted when the key is encrypted
+    #[arg(long, env)]
+    key_passphrase: Option<SecretString>,
+}
+
+impl KeyPassphraseArgs {
+    /// Returns the given passphrase, or prompts for the one of the private
+    /// key at `path`.
+    fn passphrase(&self, path: &Path) -> anyhow::Result<SecretString> {
+        if let Some(passphrase) = &self.key_passphrase {
+            return Ok(passphrase.clone());
+        }
+        Ok(SecretString::new(
+            inquire::Password::new(&format!("Enter passphrase for {}:", path.display()))
+                .with_display_mode(inquire::PasswordDisplayMode::Masked)
+                .without_confirmation()
+                .prompt()?,
+        ))
+    }
+}
+
+#[derive(Args)]
+struct TransactionArgs {
+    /// Delete existing files instead of backing them up. Their contents cannot be restored if writing fails
+    #[arg(long, env, default_value_t = false)]
+    no_backup: bool,
+    /// Keep backups of existing files in memory instead of in a temp dir, up to the given number of bytes in total
+    #[arg(long, env, conflicts_with = "no_backup")]
+    max_backup_memory: Option<usize>,
+    /// Write through an existing symlink to its target instead of replacing the link
+    #[arg(long, env, default_value_t = false)]
+    follow_symlinks: bool,
+    /// Specify how many times a transient IO error is retried
+    #[arg(long, env, default_value_t = 3)]
+    io_retries: u32,
+}
+
+impl TransactionArgs {
+    fn new_transaction(&self, mtime: Option<SystemTime>) -> anyhow::Result<Transaction> {
+        let backup_mode = match (self.no_backup, self.max_backup_memory) {
+            (true, _) => BackupMode::None,
+            (false, Some(max_bytes)) => BackupMode::Memory { max_bytes },
+            (false, None) => BackupMode::TempDir,
+        };
+        let tx = if backup_mode == BackupMode::TempDir {
+            let txdir = recover::create_temp_dir(&std::env::temp_dir())?;
+            Transaction::new(txdir)
+        } else {
+            Transaction::without_temp_dir()
+        };
+        Ok(tx
+            .with_backup_mode(backup_mode)
+            .with_follow_symlinks(self.follow_symlinks)
+            .with_io_retries(self.io_retries)
+            .with_mtime(mtime))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Theme {
+    /// No colors or attributes
+    Plain,
+    /// Colored prompts
+    Fancy,
 }
 
 #[derive(Parser)]
@@ -37,198 +433,4256 @@ impl KeyType {
 struct Cli {
     #[command(subcommand)]
     commands: Commands,
+    /// Specify the theme of interactive prompts
+    #[arg(long, global = true, env, value_enum, default_value_t = Theme::Fancy)]
+    theme: Theme,
+    /// Specify the modification and access time of written files, in seconds since the Unix epoch, for reproducible outputs
+    #[arg(long, global = true, env = "SOURCE_DATE_EPOCH")]
+    creation_time: Option<u64>,
+    /// Disable colors in interactive prompts. Also enabled by a non-empty `NO_COLOR` environment variable
+    #[arg(long, global = true, default_value_t = false)]
+    no_color: bool,
+    /// Strip the trailing newline of written text files such as OpenSSH private keys and minisign keys
+    #[arg(long, global = true, env, default_value_t = false)]
+    no_trailing_newline: bool,
+    /// Fail instead of printing a warning, such as for a key written into a directory others can write to
+    #[arg(long, global = true, env, default_value_t = false)]
+    strict: bool,
+    /// Write NDJSON events of `ssh`, such as `{"event":"written","path":...}`, to this inherited file descriptor
+    #[cfg(unix)]
+    #[arg(long, global = true, env)]
+    event_fd: Option<i32>,
+    /// Check the compiled-in BIP39 wordlists before running the command
+    #[arg(long, global = true, env, default_value_t = false)]
+    verify_wordlist_integrity: bool,
 }
 
-#[allow(clippy::upper_case_acronyms)]
+#[allow(clippy::upper_case_acronyms, clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
+    /// Runs self checks of the build, such as the integrity of the BIP39 wordlists
+    SelfTest,
     /// Generates a random mnemonic
     New {
         /// Specify the number of words in the mnemonic
         #[arg(short, long, env, default_value_t = 12)]
         word_count: usize,
+        /// Specify the wordlist of the mnemonic, or `all` to print the same entropy with every wordlist
+        #[arg(short, long, env = "MNEMONIC_LANGUAGE", default_value = "english")]
+        language: mnemonic::OutputLanguage,
+        /// Also write the entropy of the mnemonic in hex to this file, readable only by its owner
+        #[arg(long, env)]
+        entropy_out: Option<PathBuf>,
+        /// Also write a paper backup of the numbered words as an SVG page to print. Asks for confirmation first
+        #[arg(long, env)]
+        paper: Option<PathBuf>,
+        /// Also write a sheet of a two-character code per word, to check a transcription of the mnemonic with `verify`. It reveals most of the words, so it asks for confirmation first
+        #[arg(long, env)]
+        checksum_sheet: Option<PathBuf>,
+        /// Warn before generating if the kernel reports little entropy available, as early in the boot
+        #[cfg(target_os = "linux")]
+        #[arg(long, env, default_value_t = false)]
+        check_entropy_quality: bool,
+        #[command(flatten)]
+        overwrite_args: OverwriteArgs,
+        #[command(flatten)]
+        tx_args: TransactionArgs,
+    },
+    /// Converts entropy, given as hex or as dice rolls, into a mnemonic
+    #[command(group(clap::ArgGroup::new("source").required(true).args(["entropy", "dice"])))]
+    Entropy {
+        /// Specify the entropy in hex. The word count follows from its length
+        #[arg(long, env)]
+        entropy: Option<SecretString>,
+        /// Specify dice rolls from 1 to 6. 1 to 4 each give two bits and 5 and 6 one bit, so there is no bias
+        #[arg(long, env)]
+        dice: Option<SecretString>,
+        /// Specify the number of words of the mnemonic made from dice rolls
+        #[arg(short, long, env, default_value_t = 12, conflicts_with = "entropy")]
+        word_count: usize,
+    },
+    /// Prints a short identifier of a mnemonic, for labeling where it is stored.
+    ///
+    /// The identifier is the first 8 hex digits of the SHA256 of the normalized
+    /// mnemonic words. It is a labeling aid, not a security boundary: anyone
+    /// holding a candidate phrase can check it against the identifier.
+    MnemonicId {
+        #[command(flatten)]
+        mnemonic_args: MnemonicArgs,
+    },
+    /// Prints the 2048 words of a wordlist, numbered, to print as a recovery aid
+    Wordlist {
+        /// Specify the wordlist
+        #[arg(short, long, env = "MNEMONIC_LANGUAGE", value_enum, default_value_t = WordlistLanguage::English)]
+        language: WordlistLanguage,
+    },
+    /// Checks a transcribed mnemonic against the checksum sheet written by `new --checksum-sheet`, naming the words that do not match
+    Verify {
+        /// Specify the checksum sheet
+        #[arg(long, env)]
+        checksum_sheet: PathBuf,
+        /// Specify the transcribed mnemonic, split by spaces. If not specified, it will be prompted
+        #[arg(short = 'm', long, env)]
+        mnemonic: Option<SecretString>,
     },
     /// Generates an SSH key pair
     SSH {
         /// Specify the type of key you want to generate
         #[arg(short = 't', long, env)]
+        key_type: KeyTypeArg,
+        /// Specify the file path in which to save the key. `{fp}` is replaced with the hex SHA256 fingerprint of the key and `{id}` with its first 16 digits
+        #[arg(short = 'f', long, env)]
+        output_path: Option<PathBuf>,
+        /// Specify the file path the prompt offers when `--output-path` is not given, instead of `~/.ssh/id_<type>`
+        #[arg(long, env)]
+        prompt_default_path: Option<PathBuf>,
+        /// Resolve `.` and `..` in the output path without following symlinks, and print where the key is written
+        #[arg(long, env, default_value_t = false)]
+        canonicalize_output_path: bool,
+        /// Allow saving the key into a system directory such as `/etc` or `/proc`
+        #[arg(long, env, default_value_t = false)]
+        allow_system_path: bool,
+        /// Specify the format of the key files
+        #[arg(long, env, value_enum, default_value_t = KeyFormat::Openssh)]
+        format: KeyFormat,
+        /// Specify what the `.sk` file of `--format raw` contains
+        #[arg(long, env, value_enum, default_value_t = RawSecretKey::Keypair)]
+        raw_secret_key: RawSecretKey,
+        #[command(flatten)]
+        seed_args: SeedArgs,
+        /// Specify the comment for the key. If not specified, `<user>@<host>` or the comment of the preset is used
+        #[arg(short = 'C', long, env)]
+        comment: Option<String>,
+        /// Specify the comment of the public key, which may be empty, if it should differ from the comment of the private key
+        #[arg(long, env)]
+        pub_comment: Option<String>,
+        /// Use `key-<hash>` as the comment, from the fingerprint of the public key, so that it does not reveal the user or host name
+        #[arg(long, env, default_value_t = false, conflicts_with = "comment")]
+        deterministic_comment: bool,
+        /// Append the comment to the BIP39 passphrase, so that keys with different comments differ even with the same mnemonic and passphrase
+        #[arg(
+            long,
+            env,
+            default_value_t = false,
+            requires = "comment",
+            conflicts_with = "deterministic_comment"
+        )]
+        salt_with_comment: bool,
+        /// Specify whether the passphrase derives the seed, encrypts the private key file, or both. An empty passphrase never encrypts it. Defaults to `both`, or to `seed` with several passphrases or `--format raw`, which cannot be encrypted
+        #[arg(long, env, value_enum)]
+        passphrase_use: Option<PassphraseUse>,
+        /// Fail if the passphrase that encrypts the private key file is shorter than 12 characters or has only one kind of character
+        #[arg(long, env, default_value_t = false)]
+        fail_on_weak_passphrase_for_encryption: bool,
+        /// Append ` app=<uri>` to the comment, to note where the key is used, such as `ssh:github.com`
+        #[arg(long, env)]
+        application: Option<Application>,
+        /// Use a comment suited to where the key is used, and print where to upload the public key
+        #[arg(long, env, value_enum)]
+        preset: Option<Preset>,
+        /// Print the JSON body that adds the public key with the SSH-keys API of this provider
+        #[arg(long, env, value_enum, conflicts_with_all = ["encrypt_to_stdout", "combined", "dry_run"])]
+        api_payload: Option<ApiProvider>,
+        /// Print shell statements setting `SSH_KEY_PATH`, `SSH_PUBLIC_KEY_PATH` and `SSH_KEY_FINGERPRINT`, for `eval "$(bip39-keygen ssh ...)"`. Nothing else is printed
+        #[arg(long, env, value_enum, conflicts_with_all = ["encrypt_to_stdout", "combined", "dry_run", "api_payload", "preset", "print_derivation", "print_next_steps", "ssh_keygen_compat", "stage_dir", "test_host", "canonicalize_output_path"])]
+        emit_shell_env: Option<ShellEnv>,
+        /// Specify the maximum length of the comment in bytes
+        #[arg(long, env, default_value_t = 255)]
+        comment_max_len: usize,
+        /// Specify what to do if the comment is longer than `--comment-max-len`
+        #[arg(long, env, value_enum, default_value_t = LongCommentPolicy::Error)]
+        on_long_comment: LongCommentPolicy,
+        /// Abort without writing anything unless the derived key has this fingerprint, such as `SHA256:...`
+        #[arg(long, env)]
+        confirm_fingerprint: Option<ssh_key::Fingerprint>,
+        /// Also save the non-secret key metadata next to the key, with the `.meta.json` extension
+        #[arg(long, env, default_value_t = false)]
+        metadata_sidecar: bool,
+        /// Also save the SHA256 fingerprint of the key next to it, with the `.fingerprint` extension
+        #[arg(long, env, default_value_t = false)]
+        write_fingerprint: bool,
+        /// Also add the public key to this `authorized_keys` file. If the key is already listed, its comment is updated and its options are kept, except that `--from-cidr` replaces its `from=` option
+        #[arg(long, env)]
+        append_to_authorized_keys: Option<PathBuf>,
+        /// Only let clients from these comma-separated CIDR blocks, such as `192.0.2.0/24,2001:db8::/32`, use the key appended by `--append-to-authorized-keys`, with a `from=` option that replaces the `from=` option of its line and keeps the others
+        #[arg(long, env, requires = "append_to_authorized_keys")]
+        from_cidr: Option<cidr::CidrList>,
+        /// Configure git to sign commits with the key, by setting `user.signingkey` and `gpg.format = ssh`
+        #[arg(long, env, default_value_t = false)]
+        git_signing: bool,
+        /// Specify the git config file updated by `--git-signing`, instead of the global one
+        #[arg(long, env, requires = "git_signing")]
+        git_config_path: Option<PathBuf>,
+        /// Print how the key was derived as JSON, without any secret, to derive the same key again later
+        #[arg(long, env, default_value_t = false)]
+        print_derivation: bool,
+        /// Print the commands that install the public key, such as `ssh-copy-id` for `--test-host` or `gh` for `--preset github`
+        #[arg(long, env, default_value_t = false)]
+        print_next_steps: bool,
+        /// Read the key files back before finishing, and roll back if they do not hold the derived key
+        #[arg(long, env, default_value_t = false)]
+        verify_after_write: bool,
+        /// Print the same messages as `ssh-keygen`, including the randomart image
+        #[arg(long, env, default_value_t = false)]
+        ssh_keygen_compat: bool,
+        /// After writing the key, check that `[<user>@]<host>[:<port>]` accepts it, without opening a session. The host key must be in `~/.ssh/known_hosts`
+        #[arg(long, env)]
+        test_host: Option<probe::Target>,
+        /// Specify how many seconds to wait for `--test-host`
+        #[arg(long, env, default_value_t = 5)]
+        test_timeout: u64,
+        /// Reset the SELinux context of the written files with `restorecon`, for example for sshd host keys
+        #[cfg(target_os = "linux")]
+        #[arg(long, env, default_value_t = false)]
+        restorecon: bool,
+        /// Write the keys without a warning even if they are on a tmpfs or ramfs file system, which is lost on reboot
+        #[cfg(target_os = "linux")]
+        #[arg(long, env, default_value_t = false)]
+        allow_volatile_fs: bool,
+        /// Lock the output directory while writing, creating it if missing, so that concurrent runs writing to it take turns
+        #[cfg(unix)]
+        #[arg(long, env, default_value_t = false)]
+        lockfile: bool,
+        /// Specify how many seconds to wait for another run to release the lock of `--lockfile`
+        #[cfg(unix)]
+        #[arg(long, env, default_value_t = 10)]
+        lock_timeout: u64,
+        /// Set the permissions of the private key with a symbolic mode like `chmod`, for example `u=rw,go=`
+        #[cfg(unix)]
+        #[arg(long, env)]
+        output_permissions: Option<mode::SymbolicMode>,
+        /// Give the written files to the numeric `<uid>:<gid>`, which usually needs root
+        #[cfg(unix)]
+        #[arg(long, env)]
+        output_owner: Option<mode::Owner>,
+        /// Write the keys into this directory instead, to put them in place later with `promote`
+        #[arg(long, env, conflicts_with = "git_signing")]
+        stage_dir: Option<PathBuf>,
+        /// Print the private key encrypted to the RSA public key in this PEM file, as a compact JWE, instead of writing any file. The mnemonic is never generated then, so that it is not shown
+        #[arg(long, env, conflicts_with_all = [
+            "output_path",
+            "metadata_sidecar",
+            "write_fingerprint",
+            "append_to_authorized_keys",
+            "git_signing",
+            "print_derivation",
+            "print_next_steps",
+            "ssh_keygen_compat",
+            "test_host",
+            "stage_dir",
+            "dry_run",
+        ])]
+        encrypt_to_stdout: Option<PathBuf>,
+        /// Write the private key, the public key and the metadata into this one file instead, for secret managers. `split` writes them back as separate files
+        #[arg(long, env, conflicts_with_all = [
+            "output_path",
+            "metadata_sidecar",
+            "write_fingerprint",
+            "append_to_authorized_keys",
+            "git_signing",
+            "print_next_steps",
+            "ssh_keygen_compat",
+            "test_host",
+            "stage_dir",
+            "encrypt_to_stdout",
+            "dry_run",
+            "require_confirmation_file",
+            "output_permissions",
+            "output_owner",
+            "lockfile",
+            "verify_after_write",
+        ])]
+        combined: Option<PathBuf>,
+        /// Print what would be written and the nonce for `--require-confirmation-file`, without writing anything
+        #[arg(long, env, default_value_t = false)]
+        dry_run: bool,
+        /// Refuse to write the keys unless this file contains the nonce printed by `--dry-run` for the same key and path
+        #[arg(long, env)]
+        require_confirmation_file: Option<PathBuf>,
+        /// Write the keys without showing a summary to confirm first. The summary is only shown when stdin is a terminal
+        #[arg(short = 'y', long, env, default_value_t = false)]
+        yes: bool,
+        #[command(flatten)]
+        overwrite_args: OverwriteArgs,
+        #[command(flatten)]
+        tx_args: TransactionArgs,
+    },
+    /// Moves the files staged with `ssh --stage-dir` into the target directory, each with an atomic rename.
+    ///
+    /// Replaced files are backed up first, so if any file fails to move, all of them are put back.
+    Promote {
+        /// Specify the staging directory
+        stage: PathBuf,
+        /// Specify the directory the files are moved into
+        target: PathBuf,
+        #[command(flatten)]
+        tx_args: TransactionArgs,
+    },
+    /// Changes the comment of an existing OpenSSH key pair, keeping the key and the permissions of its files
+    SetComment {
+        /// Specify the private key. Its public key is next to it, with the `.pub` extension
+        key_path: PathBuf,
+        /// Specify the new comment
+        comment: String,
+        #[command(flatten)]
+        key_passphrase_args: KeyPassphraseArgs,
+        #[command(flatten)]
+        tx_args: TransactionArgs,
+    },
+    /// Writes a private key exported with `ssh --format base64-openssh` back as an OpenSSH key pair
+    Import {
+        /// Specify the file holding the base64 key, or `-` to read it from stdin
+        input: PathBuf,
+        /// Specify the file path in which to save the private key. The public key is saved next to it, with the `.pub` extension
+        #[arg(short = 'f', long, env)]
+        output_path: PathBuf,
+        #[command(flatten)]
+        overwrite_args: OverwriteArgs,
+        #[command(flatten)]
+        tx_args: TransactionArgs,
+    },
+    /// Writes the keys and metadata of a file written by `ssh --combined` back as separate files
+    Split {
+        /// Specify the combined file
+        combined: PathBuf,
+        /// Specify the file path in which to save the private key. The public key and the metadata are saved next to it, with the `.pub` and `.meta.json` extensions
+        #[arg(short = 'f', long, env)]
+        output_path: PathBuf,
+        #[command(flatten)]
+        overwrite_args: OverwriteArgs,
+        #[command(flatten)]
+        tx_args: TransactionArgs,
+    },
+    /// Compares the public keys of two directories by fingerprint, and fails if any key was changed, added or removed
+    Diff {
+        /// Specify the directory with the old keys
+        a: PathBuf,
+        /// Specify the directory with the new keys
+        b: PathBuf,
+    },
+    /// Finds the backups left in the temp dir by a run that died before finishing writing, and puts them back with `--restore`.
+    ///
+    /// A run in progress keeps its backups there too, so only use this while no other run is in progress.
+    CleanupRecover {
+        /// Move the backups back to where they were taken from, replacing what the interrupted run wrote there
+        #[arg(long, env, default_value_t = false)]
+        restore: bool,
+    },
+    /// Stores a mnemonic in the OS keyring, for use with `--mnemonic-keyring`
+    KeyringStore {
+        /// Specify the keyring entry as `<service>:<account>`
+        keyring: keyring::KeyringRef,
+        #[command(flatten)]
+        mnemonic_args: MnemonicArgs,
+    },
+    /// Generates SSH key pairs for jobs read from stdin, one JSON object per line.
+    ///
+    /// Each job looks like `{"mnemonic_ref": "env:MNEMONIC", "type": "ed25519", "path": "m/0'",
+    /// "output": "id_ed25519"}`. `mnemonic_ref` and the optional `passphrase_ref` are
+    /// `env:<NAME>` or `file:<PATH>`, and `path` is an optional SLIP-0010 derivation path.
+    /// One result line is printed per job. A failing job only rolls back its own files, and
+    /// existing files are never overwritten.
+    Batch {
+        /// Do not report progress on stderr. Progress is only reported to a terminal anyway
+        #[arg(short, long, env, default_value_t = false)]
+        quiet: bool,
+        /// Allow saving keys into a system directory such as `/etc` or `/proc`
+        #[arg(long, env, default_value_t = false)]
+        allow_system_path: bool,
+        /// Refuse to run more jobs than this, before writing anything
+        #[arg(long, env, default_value_t = batch::DEFAULT_MAX_JOBS)]
+        max_jobs: usize,
+        /// Skip the jobs whose key files already hold the key they would derive, to finish an interrupted run
+        #[arg(long, env, default_value_t = false)]
+        resume: bool,
+        #[command(flatten)]
+        tx_args: TransactionArgs,
+    },
+    /// Derives a child SSH key pair from a parent ed25519 private key
+    DeriveChild {
+        /// Specify the parent private key file
+        #[arg(short = 'k', long, env)]
+        parent_key: PathBuf,
+        /// Specify the hardened child index
+        #[arg(short, long, env, value_parser = clap::value_parser!(u32).range(..slip10::HARDENED_OFFSET as i64))]
+        index: u32,
+        /// Specify the file path in which to save the child key. If not specified, the index is appended to the parent key path
+        #[arg(short = 'f', long, env)]
+        output_path: Option<PathBuf>,
+        /// Specify the comment for the child key. If not specified, the parent comment is used
+        #[arg(short = 'C', long, env)]
+        comment: Option<String>,
+        #[command(flatten)]
+        key_passphrase_args: KeyPassphraseArgs,
+        #[command(flatten)]
+        overwrite_args: OverwriteArgs,
+        #[command(flatten)]
+        tx_args: TransactionArgs,
+    },
+    /// Prints the addresses of the first accounts of a coin, to find the accounts in use when recovering a wallet
+    DeriveAllAccounts {
+        /// Specify the coin
+        #[arg(long, env, value_enum)]
+        coin: accounts::Coin,
+        /// Specify how many accounts to print, starting from account 0
+        #[arg(short = 'n', long, env, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..=slip10::HARDENED_OFFSET as i64))]
+        count: u32,
+        #[command(flatten)]
+        seed_args: SeedArgs,
+    },
+    /// Prints the version, the supported wordlists and key types, and when this build was made, as JSON
+    Info,
+    /// Measures how many keys per second can be derived, using throwaway mnemonics
+    Bench {
+        /// Specify the type of key to derive
+        #[arg(short = 't', long, env, value_enum, default_value_t = KeyType::Ed25519)]
         key_type: KeyType,
-        /// Specify an empty passphrase
-        #[arg(short = 'N', long, env, default_value_t = false)]
-        no_passphrase: bool,
-        /// Specify the passphrase, if empty it will be prompted
-        #[arg(short, long, env, default_value = "")]
-        passphrase: SecretString,
-        /// Specify the file path in which to save the key
+        /// Specify how the keys are derived from the BIP39 seed
+        #[arg(long, env, value_enum, default_value_t = Derivation::Legacy)]
+        derivation: Derivation,
+        /// Specify how many keys to derive
+        #[arg(short = 'n', long, env, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+        iterations: u32,
+        /// Also measure encrypting the private keys with a passphrase, as `ssh` does with `--passphrase-use both` or `encryption`
+        #[arg(long, env, default_value_t = false)]
+        encrypt: bool,
+    },
+    /// Generates a minisign key pair
+    Minisign {
+        /// Specify the file path in which to save the secret key. The public key is saved next to it with the `.pub` extension
         #[arg(short = 'f', long, env)]
         output_path: Option<PathBuf>,
-        /// Specify the 12 words mnemonic, split by spaces. If not specified, it will be generated
-        #[arg(short = 'm', long, env)]
-        mnemonic: Option<SecretString>,
+        #[command(flatten)]
+        seed_args: SeedArgs,
+        #[command(flatten)]
+        overwrite_args: OverwriteArgs,
+        #[command(flatten)]
+        tx_args: TransactionArgs,
+    },
+    /// Generates a WireGuard key pair
+    Wireguard {
+        /// Specify the file path in which to save the private key. The public key is saved next to it with the `.pub` extension
+        #[arg(short = 'f', long, env, default_value = "wg.key")]
+        output_path: PathBuf,
+        #[command(flatten)]
+        seed_args: SeedArgs,
+        #[command(flatten)]
+        overwrite_args: OverwriteArgs,
+        #[command(flatten)]
+        tx_args: TransactionArgs,
+    },
+    /// Prints the `otpauth://` URI of a TOTP secret derived from the BIP39 seed for an account, to add to an authenticator app
+    Totp {
+        /// Specify the account label, such as `me@example.com`. Every label derives another secret
+        account: String,
+        /// Specify the service the account belongs to
+        #[arg(long, env)]
+        issuer: Option<String>,
+        #[command(flatten)]
+        seed_args: SeedArgs,
+    },
+    /// Prints an `sk-ssh-ed25519@openssh.com` placeholder for the derived public key.
+    ///
+    /// FIDO security keys generate their private key on the device, so they cannot be derived
+    /// from a mnemonic. The placeholder only helps migrating configuration templates; it is not
+    /// hardware-backed and no security key can sign for it.
+    SkStub {
+        /// Specify the FIDO application string
+        #[arg(short = 'O', long, env, default_value = "ssh:")]
+        application: String,
+        #[command(flatten)]
+        seed_args: SeedArgs,
         /// Specify the comment for the key
         #[arg(short = 'C', long, default_value_t = ssh_default_comment(), env)]
         comment: String,
     },
+    /// Signs a message with the key derived from a mnemonic, or verifies a signature
+    #[command(group(clap::ArgGroup::new("input").required(true).args(["message", "message_file"])))]
+    Sign {
+        /// Specify the namespace of the signature
+        #[arg(short = 'n', long, env, default_value = "file")]
+        namespace: String,
+        /// Specify the message
+        #[arg(short = 'M', long, env)]
+        message: Option<String>,
+        /// Specify the file containing the message
+        #[arg(long, env)]
+        message_file: Option<PathBuf>,
+        /// Verify the signature in the given file instead of signing
+        #[arg(long, env, requires = "public_key", conflicts_with = "mnemonic")]
+        verify: Option<PathBuf>,
+        /// Specify the public key file used to verify the signature
+        #[arg(long, env, requires = "verify")]
+        public_key: Option<PathBuf>,
+        #[command(flatten)]
+        seed_args: SeedArgs,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    run(Cli::parse())
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    let no_color = cli.no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    inquire::set_global_render_config(render_config(cli.theme, no_color));
+    let creation_time = cli
+        .creation_time
+        .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+    if cli.verify_wordlist_integrity {
+        wordlist::verify_integrity()?;
+    }
+    #[cfg(unix)]
+    let mut events = events::Events::open(cli.event_fd)?;
     match cli.commands {
-        Commands::New { word_count } => {
-            let mnemonic = Mnemonic::generate_in(Language::English, word_count)?;
-            println!("{mnemonic}");
+        Commands::SelfTest => {
+            wordlist::verify_integrity()?;
+            println!("Wordlists: ok");
+        }
+        Commands::New {
+            word_count,
+            language,
+            entropy_out,
+            paper,
+            checksum_sheet,
+            #[cfg(target_os = "linux")]
+            check_entropy_quality,
+            overwrite_args,
+            tx_args,
+        } => {
+            #[cfg(target_os = "linux")]
+            if check_entropy_quality {
+                warn(cli.strict, rng::check_entropy_quality())?;
+            }
+            let generate_language = match language {
+                mnemonic::OutputLanguage::One(language) => language.into(),
+                mnemonic::OutputLanguage::All => Language::English,
+            };
+            let mnemonic = Zeroizing::new(Mnemonic::generate_in(generate_language, word_count)?);
+            if let Some(paper) = &paper {
+                prompt_write_paper(paper)?;
+            }
+            if let Some(checksum_sheet) = &checksum_sheet {
+                prompt_write_checksum_sheet(checksum_sheet)?;
+            }
+            if let Some(entropy_out) = &entropy_out {
+                prompt_overwrite_path(entropy_out, &overwrite_args)?;
+            }
+            if let Some(paper) = &paper {
+                prompt_overwrite_path(paper, &overwrite_args)?;
+            }
+            if let Some(checksum_sheet) = &checksum_sheet {
+                prompt_overwrite_path(checksum_sheet, &overwrite_args)?;
+            }
+            if entropy_out.is_some() || paper.is_some() || checksum_sheet.is_some() {
+                let mut tx = tx_args.new_transaction(creation_time)?;
+                if let Some(entropy_out) = &entropy_out {
+                    write_entropy(&mut tx, entropy_out, &mnemonic, cli.no_trailing_newline)?;
+                }
+                if let Some(paper) = &paper {
+                    tx.write_private_file(
+                        paper,
+                        paper::render_svg(&mnemonic, &mnemonic_id(&mnemonic)).as_str(),
+                    )?;
+                }
+                if let Some(checksum_sheet) = &checksum_sheet {
+                    tx.write_private_file(checksum_sheet, checksum_sheet::render(&mnemonic))?;
+                }
+                tx.commit();
+            }
+            match language {
+                mnemonic::OutputLanguage::One(_) => println!("{}", *mnemonic),
+                mnemonic::OutputLanguage::All => {
+                    for translation in mnemonic::translations(&mnemonic) {
+                        let translation = Zeroizing::new(translation);
+                        println!("{}:", WordlistLanguage::from(translation.language()).name());
+                        println!("  {}", *translation);
+                    }
+                }
+            }
+        }
+        Commands::Entropy {
+            entropy,
+            dice,
+            word_count,
+        } => {
+            let entropy = match (entropy, dice) {
+                (Some(entropy), _) => entropy::from_hex(entropy.expose_secret())?,
+                (None, Some(dice)) => entropy::from_dice(
+                    dice.expose_secret(),
+                    entropy::len_for_word_count(word_count)?,
+                )?,
+                (None, None) => unreachable!("source group is required"),
+            };
+            let mnemonic = Zeroizing::new(Mnemonic::from_entropy(&entropy)?);
+            println!("{}", *mnemonic);
+        }
+        Commands::MnemonicId { mnemonic_args } => {
+            let mnemonic = prompt_input_mnemonic(mnemonic_args)?;
+            println!("{}", mnemonic_id(&mnemonic));
+        }
+        Commands::Verify {
+            checksum_sheet,
+            mnemonic,
+        } => {
+            let sheet = std::fs::read_to_string(&checksum_sheet)
+                .with_context(|| format!("failed to read {}", checksum_sheet.display()))?;
+            let mnemonic = match mnemonic {
+                Some(mnemonic) => mnemonic,
+                None => SecretString::new(
+                    inquire::Text::new(
+                        "Enter the transcribed mnemonic (separate words with spaces)",
+                    )
+                    .prompt()?,
+                ),
+            };
+            let words: Vec<&str> = mnemonic.expose_secret().split_whitespace().collect();
+            checksum_sheet::verify(&words, &sheet)?;
+            mnemonic::parse(mnemonic.expose_secret(), None)?;
+            println!("The mnemonic matches the checksum sheet");
+        }
+        Commands::Wordlist { language } => {
+            print!("{}", wordlist::numbered(language.into()));
         }
         Commands::SSH {
             key_type,
-            no_passphrase,
-            passphrase,
             output_path,
-            mnemonic: mnemonic_opt,
+            prompt_default_path,
+            canonicalize_output_path,
+            allow_system_path,
+            format,
+            raw_secret_key,
+            seed_args,
             comment,
+            pub_comment,
+            deterministic_comment,
+            salt_with_comment,
+            passphrase_use,
+            fail_on_weak_passphrase_for_encryption,
+            application,
+            preset,
+            api_payload,
+            emit_shell_env,
+            comment_max_len,
+            on_long_comment,
+            confirm_fingerprint,
+            metadata_sidecar,
+            write_fingerprint,
+            append_to_authorized_keys,
+            from_cidr,
+            git_signing,
+            git_config_path,
+            print_derivation,
+            print_next_steps,
+            verify_after_write,
+            ssh_keygen_compat,
+            test_host,
+            test_timeout,
+            #[cfg(target_os = "linux")]
+            restorecon,
+            #[cfg(target_os = "linux")]
+            allow_volatile_fs,
+            #[cfg(unix)]
+            lockfile,
+            #[cfg(unix)]
+            lock_timeout,
+            #[cfg(unix)]
+            output_permissions,
+            #[cfg(unix)]
+            output_owner,
+            stage_dir,
+            encrypt_to_stdout,
+            combined,
+            dry_run,
+            require_confirmation_file,
+            yes,
+            overwrite_args,
+            tx_args,
         } => {
-            let mnemonic = prompt_generate_mnemonic(mnemonic_opt)?;
-            let passphrase = prompt_passphrase(if no_passphrase {
-                Some(SecretString::new(String::new()))
-            } else if passphrase.expose_secret().is_empty() {
+            let all_key_types = key_type == KeyTypeArg::All;
+            if all_key_types && output_path.is_some() {
+                bail!(
+                    "--key-type all saves each key to its conventional file name, not --output-path"
+                );
+            }
+            let key_types = key_type.key_types();
+            let [key_type] = key_types[..] else {
+                bail!(
+                    "--key-type all would write several key types in one run, which is not supported yet"
+                );
+            };
+            // Truncating prints a warning, which fails in --strict mode.
+            let on_long_comment = match on_long_comment {
+                LongCommentPolicy::Truncate if cli.strict => LongCommentPolicy::Error,
+                policy => policy,
+            };
+            let comment = if deterministic_comment {
                 None
             } else {
-                Some(passphrase)
-            })?;
+                let comment = comment.unwrap_or_else(|| {
+                    preset.map_or_else(ssh_default_comment, |preset| preset.comment())
+                });
+                Some(check_comment_len(
+                    with_application(comment, application.as_ref()),
+                    comment_max_len,
+                    on_long_comment,
+                )?)
+            };
+            let pub_comment = pub_comment
+                .map(|comment| check_comment_len(comment, comment_max_len, on_long_comment))
+                .transpose()?;
+            if git_signing && format == KeyFormat::Raw {
+                bail!("--git-signing needs an OpenSSH key, not --format raw");
+            }
+            if append_to_authorized_keys.is_some() && format == KeyFormat::Raw {
+                bail!("--append-to-authorized-keys needs an OpenSSH key, not --format raw");
+            }
+            if encrypt_to_stdout.is_some() && format == KeyFormat::Raw {
+                bail!("--encrypt-to-stdout prints an OpenSSH key, not --format raw");
+            }
+            if combined.is_some() && format == KeyFormat::Raw {
+                bail!("--combined writes an OpenSSH key, not --format raw");
+            }
+            let recipient = encrypt_to_stdout
+                .as_deref()
+                .map(jwe::read_recipient)
+                .transpose()?;
+            let git_config_path = match (git_signing, git_config_path) {
+                (true, Some(path)) => Some(path),
+                (true, None) => Some(gitconfig::global_config_path()?),
+                (false, _) => None,
+            };
+            if ssh_keygen_compat {
+                println!(
+                    "Generating public/private {} key pair.",
+                    key_type.as_ssh_keygen_type()
+                );
+            }
+            let mnemonic = if recipient.is_some() {
+                prompt_input_mnemonic(seed_args.mnemonic_args)?
+            } else {
+                prompt_generate_mnemonic(seed_args.mnemonic_args)?
+            };
+            let combine_passphrases =
+                (seed_args.passphrase.len() > 1).then_some(seed_args.combine_passphrases);
+            let passphrase = resolve_passphrase(
+                seed_args.no_passphrase,
+                seed_args.passphrase_stdin,
+                seed_args.no_passphrase_confirm,
+                seed_args.use_agent,
+                &seed_args.prompt_labels,
+                seed_args.passphrase,
+                seed_args.combine_passphrases,
+            )?;
+            let passphrase_use = passphrase_use.unwrap_or(
+                if combine_passphrases.is_some() || format == KeyFormat::Raw {
+                    PassphraseUse::Seed
+                } else {
+                    PassphraseUse::Both
+                },
+            );
+            let (passphrase, encryption) = split_passphrase(passphrase_use, passphrase);
+            if let Some(encryption) = &encryption {
+                if combine_passphrases.is_some() {
+                    bail!(
+                        "several passphrases cannot encrypt the private key, pass --passphrase-use seed"
+                    );
+                }
+                if format == KeyFormat::Raw {
+                    bail!(
+                        "--format raw cannot be encrypted, pass --passphrase-use seed to only derive the seed from the passphrase"
+                    );
+                }
+                if fail_on_weak_passphrase_for_encryption {
+                    passphrase::check_encryption_strength(encryption.expose_secret())?;
+                }
+            }
+            // Records whether a passphrase was given, not whether the
+            // salted one is empty.
+            let derivation_record = print_derivation.then(|| metadata::DerivationRecord {
+                salt_with_comment,
+                ..metadata::DerivationRecord::new(
+                    key_type,
+                    &mnemonic,
+                    &passphrase,
+                    combine_passphrases,
+                    seed_args.derivation,
+                    derivation::SSH_ED25519,
+                    seed_args.domain.clone(),
+                )
+            });
+            let passphrase = match &comment {
+                Some(comment) if salt_with_comment => salt_passphrase(&passphrase, comment),
+                _ => passphrase,
+            };
 
-            let seckey_path = prompt_output_path(output_path, key_type)?;
-            let pubkey_path = seckey_path.with_extension("pub");
+            let keypair = derive_ed25519_keypair(
+                &mnemonic,
+                &passphrase,
+                seed_args.derivation,
+                derivation::SSH_ED25519,
+                seed_args.domain.as_deref(),
+            );
+            let key_data = ssh_key::public::KeyData::Ed25519(keypair.public);
+            if let Some(expected) = &confirm_fingerprint {
+                check_fingerprint(&key_data, expected)?;
+            }
+            #[cfg(unix)]
+            events.emit(events::Event::Derived {
+                fingerprint: key_data.fingerprint(Default::default()).to_string(),
+            })?;
+            let comment = match comment {
+                Some(comment) => comment,
+                None => check_comment_len(
+                    with_application(key_comment(&key_data), application.as_ref()),
+                    comment_max_len,
+                    on_long_comment,
+                )?,
+            };
 
-            prompt_overwrite_path(&seckey_path)?;
-            prompt_overwrite_path(&pubkey_path)?;
+            if let Some(recipient) = &recipient {
+                let secret_key = ssh_key::PrivateKey::new(
+                    ssh_key::private::KeypairData::Ed25519(keypair),
+                    comment,
+                )?;
+                println!(
+                    "{}",
+                    jwe::encrypt(
+                        recipient,
+                        encode_openssh(&secret_key, encryption.as_ref())?.as_bytes()
+                    )?
+                );
+                return Ok(());
+            }
+            if let Some(combined_path) = &combined {
+                let combined_path = path::absolute(combined_path)?;
+                if !allow_system_path {
+                    check_not_system_path(&combined_path)?;
+                }
+                #[cfg(target_os = "linux")]
+                if !allow_volatile_fs {
+                    warn(cli.strict, mounts::check_persistent_fs(&combined_path))?;
+                }
+                #[cfg(unix)]
+                warn(cli.strict, mode::loose_dir_warning(&combined_path))?;
+                prompt_overwrite_path(&combined_path, &overwrite_args)?;
+                if !yes && std::io::stdin().is_terminal() {
+                    let review = SshReview {
+                        key_type,
+                        seckey_path: &combined_path,
+                        pubkey_path: &combined_path,
+                        comment: &comment,
+                        encrypted: encryption.is_some(),
+                        derivation: seed_args.derivation,
+                        domain: seed_args.domain.as_deref(),
+                        fingerprint: key_data.fingerprint(Default::default()),
+                    };
+                    confirm_review(&review, || {
+                        Ok(inquire::Confirm::new("Write the keys?")
+                            .with_default(true)
+                            .prompt()?)
+                    })?;
+                }
+                #[cfg(unix)]
+                warn(cli.strict, diskspace::check_free_space(&combined_path, 1))?;
+                let secret_key = ssh_key::PrivateKey::new(
+                    ssh_key::private::KeypairData::Ed25519(keypair),
+                    comment,
+                )?;
+                let mut public_key = secret_key.public_key().clone();
+                if let Some(pub_comment) = &pub_comment {
+                    public_key.set_comment(pub_comment);
+                }
+                let metadata = metadata::KeyMetadata::new(
+                    &public_key,
+                    seed_args.derivation,
+                    derivation::SSH_ED25519,
+                );
+                let private_key = encode_openssh(&secret_key, encryption.as_ref())?;
+                let contents = combined::Combined {
+                    private_key: Zeroizing::new(
+                        text_file(&private_key, cli.no_trailing_newline).to_string(),
+                    ),
+                    public_key: public_key_file(&public_key, cli.no_trailing_newline)?,
+                    metadata: text_file(&metadata.to_json()?, cli.no_trailing_newline).to_string(),
+                };
+                let mut tx = tx_args.new_transaction(creation_time)?;
+                tx.write_private_file(&combined_path, contents.encode().as_bytes())?;
+                #[cfg(unix)]
+                events.emit(events::Event::Written {
+                    path: &combined_path,
+                })?;
+                tx.commit();
+                #[cfg(unix)]
+                events.emit(events::Event::Committed)?;
+                if let Some(derivation_record) = derivation_record {
+                    print!("{}", derivation_record.to_json()?);
+                }
+                return Ok(());
+            }
 
-            let seed = mnemonic.to_seed(passphrase.expose_secret());
-            let (seed32, _) = seed.split_array_ref::<32>();
-            let keypair = ssh_key::private::KeypairData::Ed25519(
-                ssh_key::private::Ed25519Keypair::from_seed(seed32),
+            let seckey_path = if all_key_types {
+                path::absolute(ssh_default_output_path(key_type))?
+            } else {
+                expand_output_path(
+                    &prompt_output_path(
+                        output_path,
+                        prompt_default_path.unwrap_or_else(|| ssh_default_output_path(key_type)),
+                        canonicalize_output_path,
+                    )?,
+                    &key_data,
+                )
+            };
+            if canonicalize_output_path {
+                println!("Saving the key to {}", seckey_path.display());
+            }
+            if !allow_system_path {
+                check_not_system_path(&seckey_path)?;
+            }
+            let (seckey_path, promote_to) = match &stage_dir {
+                Some(stage_dir) => {
+                    let Some(file_name) = seckey_path.file_name() else {
+                        bail!("{} has no file name", seckey_path.display());
+                    };
+                    let promote_to = seckey_path.parent().map(Path::to_path_buf);
+                    (path::absolute(stage_dir)?.join(file_name), promote_to)
+                }
+                None => (seckey_path, None),
+            };
+            let (seckey_path, pubkey_path) = match format {
+                KeyFormat::Openssh => {
+                    let pubkey_path = seckey_path.with_extension("pub");
+                    (seckey_path, pubkey_path)
+                }
+                KeyFormat::Raw => (
+                    seckey_path.with_extension("sk"),
+                    seckey_path.with_extension("pk"),
+                ),
+                KeyFormat::Base64Openssh => (
+                    seckey_path.with_extension("b64"),
+                    seckey_path.with_extension("pub"),
+                ),
+            };
+            let metadata_path = metadata_sidecar.then(|| seckey_path.with_extension("meta.json"));
+            let fingerprint_path =
+                write_fingerprint.then(|| seckey_path.with_extension("fingerprint"));
+            let mut targets = vec![
+                ("the private key", seckey_path.as_path()),
+                ("the public key", pubkey_path.as_path()),
+            ];
+            targets.extend(
+                metadata_path
+                    .as_deref()
+                    .map(|path| ("--metadata-sidecar", path)),
             );
-            let public_key = ssh_key::PublicKey::new(
-                ssh_key::public::KeyData::try_from(&keypair)?,
-                comment.clone(),
+            targets.extend(
+                fingerprint_path
+                    .as_deref()
+                    .map(|path| ("--write-fingerprint", path)),
             );
-            let secret_key = ssh_key::PrivateKey::new(keypair, comment)?;
-
-            let txdir = tempfile::Builder::new().prefix("bip39-keygen").tempdir()?;
-            let mut tx = Transaction::new(txdir);
-            tx.write_file(pubkey_path, public_key.to_openssh()?)?;
-            tx.write_file(seckey_path, secret_key.to_openssh(Default::default())?)?;
-            tx.commit();
-        }
-    }
-    Ok(())
-}
-
-fn ssh_default_output_path(key_type: KeyType) -> PathBuf {
-    use std::path::MAIN_SEPARATOR;
+            targets.extend(
+                append_to_authorized_keys
+                    .as_deref()
+                    .map(|path| ("--append-to-authorized-keys", path)),
+            );
+            targets.extend(
+                git_config_path
+                    .as_deref()
+                    .map(|path| ("--git-signing", path)),
+            );
+            check_distinct_targets(&targets)?;
 
-    match home::home_dir() {
-        Some(path) if !path.as_os_str().is_empty() => path.join(format!(
-            ".ssh{MAIN_SEPARATOR}{}",
-            key_type.as_openssh_filename()
-        )),
-        _ => PathBuf::new(),
-    }
-}
+            let review = SshReview {
+                key_type,
+                seckey_path: &seckey_path,
+                pubkey_path: &pubkey_path,
+                comment: &comment,
+                encrypted: encryption.is_some(),
+                derivation: seed_args.derivation,
+                domain: seed_args.domain.as_deref(),
+                fingerprint: key_data.fingerprint(Default::default()),
+            };
+            let nonce = confirmation::nonce(&review.fingerprint, &seckey_path);
+            if dry_run {
+                println!("{review}");
+                println!("Confirmation nonce: {nonce}");
+                return Ok(());
+            }
+            if let Some(confirmation_path) = &require_confirmation_file {
+                confirmation::check(confirmation_path, &nonce)?;
+            }
 
-fn ssh_default_comment() -> String {
-    format!(
-        "{}@{}",
-        whoami::username(),
-        whoami::fallible::hostname().unwrap_or_else(|_| "localhost".to_string())
-    )
-}
+            // Staged keys are only kept there until they are promoted.
+            #[cfg(target_os = "linux")]
+            if !allow_volatile_fs && stage_dir.is_none() {
+                warn(cli.strict, mounts::check_persistent_fs(&seckey_path))?;
+            }
+            #[cfg(unix)]
+            warn(cli.strict, mode::loose_dir_warning(&seckey_path))?;
+            // Held until the keys are written, so that another run neither
+            // finds them half-written nor replaces them after the checks.
+            #[cfg(unix)]
+            let _lock = lockfile
+                .then(|| lock::DirLock::acquire(&seckey_path, Duration::from_secs(lock_timeout)))
+                .transpose()?;
+            prompt_overwrite_path(&seckey_path, &overwrite_args)?;
+            prompt_overwrite_path(&pubkey_path, &overwrite_args)?;
+            if let Some(metadata_path) = &metadata_path {
+                prompt_overwrite_path(metadata_path, &overwrite_args)?;
+            }
+            if let Some(fingerprint_path) = &fingerprint_path {
+                prompt_overwrite_path(fingerprint_path, &overwrite_args)?;
+            }
+            if !yes && std::io::stdin().is_terminal() {
+                confirm_review(&review, || {
+                    Ok(inquire::Confirm::new("Write the keys?")
+                        .with_default(true)
+                        .prompt()?)
+                })?;
+            }
 
-#[allow(dead_code)]
-fn gpg_default_output_dir() -> PathBuf {
-    match home::home_dir() {
-        Some(path) if !path.as_os_str().is_empty() => path.join(".gnupg"),
-        _ => PathBuf::new(),
-    }
-}
+            #[cfg(unix)]
+            warn(
+                cli.strict,
+                diskspace::check_free_space(
+                    &seckey_path,
+                    2 + usize::from(metadata_path.is_some())
+                        + usize::from(fingerprint_path.is_some()),
+                ),
+            )?;
+            let probe_keypair = test_host.as_ref().map(|_| keypair.clone());
+            let mut tx = tx_args.new_transaction(creation_time)?;
+            let public_key = match format {
+                KeyFormat::Openssh => write_ssh_keypair(
+                    &mut tx,
+                    &seckey_path,
+                    &pubkey_path,
+                    keypair,
+                    comment,
+                    pub_comment.as_deref(),
+                    encryption.as_ref(),
+                    cli.no_trailing_newline,
+                )?,
+                KeyFormat::Raw => write_raw_keypair(
+                    &mut tx,
+                    &seckey_path,
+                    &pubkey_path,
+                    keypair,
+                    raw_secret_key,
+                    pub_comment.unwrap_or(comment),
+                )?,
+                KeyFormat::Base64Openssh => write_base64_keypair(
+                    &mut tx,
+                    &seckey_path,
+                    &pubkey_path,
+                    keypair,
+                    comment,
+                    pub_comment.as_deref(),
+                    encryption.as_ref(),
+                    cli.no_trailing_newline,
+                )?,
+            };
+            if let Some(metadata_path) = &metadata_path {
+                let metadata = metadata::KeyMetadata::new(
+                    &public_key,
+                    seed_args.derivation,
+                    derivation::SSH_ED25519,
+                );
+                tx.write_file(
+                    metadata_path,
+                    text_file(&metadata.to_json()?, cli.no_trailing_newline),
+                )?;
+            }
+            if let Some(fingerprint_path) = &fingerprint_path {
+                write_fingerprint_file(
+                    &mut tx,
+                    fingerprint_path,
+                    &public_key,
+                    cli.no_trailing_newline,
+                )?;
+            }
+            if let Some(authorized_keys_path) = &append_to_authorized_keys {
+                authorized_keys::append(
+                    &mut tx,
+                    authorized_keys_path,
+                    &public_key,
+                    from_cidr.as_ref().map(cidr::CidrList::to_option).as_deref(),
+                )?;
+            }
+            if let Some(git_config_path) = &git_config_path {
+                gitconfig::write_signing_config(&mut tx, git_config_path, &pubkey_path)?;
+            }
+            #[cfg(unix)]
+            if let Some(mode) = &output_permissions {
+                use std::os::unix::fs::PermissionsExt;
 
-fn prompt_passphrase(passphrase_opt: Option<SecretString>) -> anyhow::Result<SecretString> {
-    match passphrase_opt {
-        Some(passphrase) => Ok(passphrase),
-        None => Ok(SecretString::new(
-            inquire::Password::new("Enter passphrase (empty for no passphrase):")
-                .with_display_mode(inquire::PasswordDisplayMode::Masked)
-                .with_custom_confirmation_message("Confirmation passphrase")
-                .prompt()?,
-        )),
-    }
-}
+                let current = std::fs::metadata(&seckey_path)?.permissions().mode();
+                tx.set_permissions(
+                    &seckey_path,
+                    std::fs::Permissions::from_mode(mode.apply(current)),
+                )?;
+            }
+            #[cfg(unix)]
+            if let Some(owner) = output_owner {
+                let mut paths = vec![seckey_path.as_path(), pubkey_path.as_path()];
+                paths.extend(metadata_path.as_deref());
+                paths.extend(fingerprint_path.as_deref());
+                for path in paths {
+                    tx.set_owner(path, owner.uid, owner.gid).with_context(|| {
+                        format!("failed to change the owner of {}", path.display())
+                    })?;
+                }
+            }
+            if verify_after_write {
+                verify_written_keys(format, &seckey_path, &pubkey_path, public_key.key_data())?;
+            }
+            // Before committing, so that with `--strict` a failure rolls the
+            // keys back instead of leaving them with the wrong context.
+            #[cfg(target_os = "linux")]
+            if restorecon {
+                let mut paths = vec![seckey_path.as_path(), pubkey_path.as_path()];
+                paths.extend(metadata_path.as_deref());
+                paths.extend(fingerprint_path.as_deref());
+                warn(cli.strict, selinux::restorecon(&paths))?;
+            }
+            #[cfg(unix)]
+            for &(_, path) in &targets {
+                events.emit(events::Event::Written { path })?;
+            }
+            tx.commit();
+            #[cfg(unix)]
+            events.emit(events::Event::Committed)?;
+            if ssh_keygen_compat {
+                print!(
+                    "{}",
+                    ssh_keygen_report(&seckey_path, &pubkey_path, &public_key)
+                );
+            }
+            if let (Some(stage_dir), Some(promote_to)) = (&stage_dir, &promote_to) {
+                println!(
+                    "Staged the keys in {}, put them in place with `bip39-keygen promote {} {}`",
+                    stage_dir.display(),
+                    stage_dir.display(),
+                    promote_to.display()
+                );
+            }
+            if let Some(preset) = preset {
+                println!("{}", preset.upload_hint(&pubkey_path));
+            }
+            if let Some(provider) = api_payload {
+                println!("{}", self::api_payload(provider, &public_key)?);
+            }
+            if let Some(shell) = emit_shell_env {
+                let fingerprint = public_key.fingerprint(Default::default()).to_string();
+                print!(
+                    "{}",
+                    shell_env(shell, &[
+                        ("SSH_KEY_PATH", &seckey_path.to_string_lossy()),
+                        ("SSH_PUBLIC_KEY_PATH", &pubkey_path.to_string_lossy()),
+                        ("SSH_KEY_FINGERPRINT", &fingerprint),
+                    ])
+                );
+            }
+            if let Some(derivation_record) = derivation_record {
+                print!("{}", derivation_record.to_json()?);
+            }
+            if print_next_steps {
+                // Staged keys are only used once promoted.
+                let pubkey_path = match (&promote_to, pubkey_path.file_name()) {
+                    (Some(promote_to), Some(file_name)) => promote_to.join(file_name),
+                    _ => pubkey_path.clone(),
+                };
+                println!("Next steps:");
+                for step in next_steps(
+                    preset,
+                    &pubkey_path,
+                    public_key.comment(),
+                    test_host.as_ref(),
+                ) {
+                    println!("  {step}");
+                }
+            }
+            if let (Some(target), Some(keypair)) = (test_host, probe_keypair) {
+                let secret_key = ssh_key::PrivateKey::new(
+                    ssh_key::private::KeypairData::Ed25519(keypair),
+                    public_key.comment(),
+                )?;
+                let accepted =
+                    probe::probe(&target, &secret_key, Duration::from_secs(test_timeout))
+                        .with_context(|| format!("failed to probe {target}"))?;
+                if !accepted {
+                    bail!("{target} did not accept the key");
+                }
+                println!("{target} accepts the key");
+            }
+        }
+        Commands::KeyringStore {
+            keyring,
+            mnemonic_args,
+        } => {
+            let mnemonic = prompt_generate_mnemonic(mnemonic_args)?;
+            keyring::store(&keyring.entry()?, &mnemonic)?;
+        }
+        Commands::Promote {
+            stage,
+            target,
+            tx_args,
+        } => {
+            let mut tx = tx_args.new_transaction(creation_time)?;
+            let promoted = promote(&mut tx, &stage, &target)?;
+            tx.commit();
+            for path in promoted {
+                println!("Promoted {}", path.display());
+            }
+        }
+        Commands::SetComment {
+            key_path,
+            comment,
+            key_passphrase_args,
+            tx_args,
+        } => {
+            let seckey_path = path::absolute(key_path)?;
+            let pubkey_path = seckey_path.with_extension("pub");
+            let mut tx = tx_args.new_transaction(creation_time)?;
+            set_comment(
+                &mut tx,
+                &seckey_path,
+                &pubkey_path,
+                comment,
+                cli.no_trailing_newline,
+                || key_passphrase_args.passphrase(&seckey_path),
+            )?;
+            tx.commit();
+            println!("Changed the comment of {}", seckey_path.display());
+        }
+        Commands::Import {
+            input,
+            output_path,
+            overwrite_args,
+            tx_args,
+        } => {
+            let encoded = Zeroizing::new(if input == Path::new("-") {
+                std::io::read_to_string(std::io::stdin())?
+            } else {
+                std::fs::read_to_string(&input)
+                    .with_context(|| format!("failed to read {}", input.display()))?
+            });
+            let secret_key = decode_base64_openssh(&encoded)?;
+            let seckey_path = path::absolute(output_path)?;
+            let pubkey_path = seckey_path.with_extension("pub");
+            prompt_overwrite_path(&seckey_path, &overwrite_args)?;
+            prompt_overwrite_path(&pubkey_path, &overwrite_args)?;
 
-fn prompt_output_path(outpath: Option<PathBuf>, key_type: KeyType) -> anyhow::Result<PathBuf> {
-    let path = match outpath {
-        Some(path) => path,
-        None => {
-            let mut text = inquire::Text::new("Enter file in which to save the key");
-            let default_path = ssh_default_output_path(key_type);
-            if let Some(default) = default_path.to_str() {
-                text = text.with_default(default);
+            let mut tx = tx_args.new_transaction(creation_time)?;
+            tx.write_file(
+                &pubkey_path,
+                public_key_file(secret_key.public_key(), cli.no_trailing_newline)?,
+            )?;
+            tx.write_private_file(
+                &seckey_path,
+                text_file(
+                    &secret_key.to_openssh(Default::default())?,
+                    cli.no_trailing_newline,
+                ),
+            )?;
+            tx.commit();
+            println!("Wrote {}", seckey_path.display());
+            println!("Wrote {}", pubkey_path.display());
+        }
+        Commands::Split {
+            combined,
+            output_path,
+            overwrite_args,
+            tx_args,
+        } => {
+            let contents = Zeroizing::new(
+                std::fs::read_to_string(&combined)
+                    .with_context(|| format!("failed to read {}", combined.display()))?,
+            );
+            let contents = combined::Combined::parse(&contents)
+                .with_context(|| format!("failed to parse {}", combined.display()))?;
+            let output_path = path::absolute(output_path)?;
+            let paths = [
+                output_path.clone(),
+                output_path.with_extension("pub"),
+                output_path.with_extension("meta.json"),
+            ];
+            for path in &paths {
+                prompt_overwrite_path(path, &overwrite_args)?;
+            }
+            let mut tx = tx_args.new_transaction(creation_time)?;
+            split_combined(&mut tx, &contents, &paths)?;
+            tx.commit();
+            for path in &paths {
+                println!("Wrote {}", path.display());
             }
-            PathBuf::from(text.prompt()?)
         }
-    };
-    Ok(path::absolute(path)?)
-}
+        Commands::Diff { a, b } => {
+            let entries = diff::diff(&a, &b)?;
+            for entry in &entries {
+                println!("{entry}");
+            }
+            let differences = entries
+                .iter()
+                .filter(|entry| !matches!(entry.change, diff::Change::Identical(_)))
+                .count();
+            if differences > 0 {
+                bail!(
+                    "{differences} of {} keys differ between {} and {}",
+                    entries.len(),
+                    a.display(),
+                    b.display()
+                );
+            }
+        }
+        Commands::CleanupRecover { restore } => {
+            let orphans = recover::find_orphans(&std::env::temp_dir())?;
+            if orphans.is_empty() {
+                println!("No backups left behind");
+            }
+            let mut restored: Vec<&recover::Orphan> = Vec::new();
+            for orphan in &orphans {
+                match (&orphan.original, restore) {
+                    // The earliest backup holds the original, the later ones
+                    // what the interrupted transaction wrote in between.
+                    (Some(original), true) if restored.iter().any(|r| orphan.supersedes(r)) => {
+                        recover::discard(orphan).with_context(|| {
+                            format!("failed to remove {}", orphan.backup.display())
+                        })?;
+                        println!(
+                            "Removed {}, a later backup of {}",
+                            orphan.backup.display(),
+                            original.display()
+                        );
+                    }
+                    (Some(original), true) => {
+                        recover::restore(orphan).with_context(|| {
+                            format!("failed to restore {}", orphan.backup.display())
+                        })?;
+                        println!("Restored {}", original.display());
+                        restored.push(orphan);
+                    }
+                    (Some(original), false) => println!(
+                        "{} is a backup of {}",
+                        orphan.backup.display(),
+                        original.display()
+                    ),
+                    (None, _) => {
+                        println!("{} is a backup of an unknown file", orphan.backup.display())
+                    }
+                }
+            }
+        }
+        Commands::Batch {
+            quiet,
+            allow_system_path,
+            max_jobs,
+            resume,
+            tx_args,
+        } => {
+            let mut stderr = std::io::stderr();
+            let progress = batch::show_progress(quiet, stderr.is_terminal())
+                .then_some(&mut stderr as &mut dyn std::io::Write);
+            let failed = batch::run(
+                std::io::stdin().lock(),
+                std::io::stdout().lock(),
+                progress,
+                &tx_args,
+                allow_system_path,
+                creation_time,
+                max_jobs,
+                resume,
+            )?;
+            if failed > 0 {
+                bail!("{failed} job(s) failed");
+            }
+        }
+        Commands::DeriveChild {
+            parent_key,
+            index,
+            output_path,
+            comment,
+            key_passphrase_args,
+            overwrite_args,
+            tx_args,
+        } => {
+            let parent = ssh_key::PrivateKey::read_openssh_file(&parent_key)?;
+            // The child is encrypted with the passphrase of the parent too.
+            let passphrase = parent
+                .is_encrypted()
+                .then(|| key_passphrase_args.passphrase(&parent_key))
+                .transpose()?;
+            let parent = match &passphrase {
+                Some(passphrase) => decrypt_private_key(&parent, &parent_key, passphrase)?,
+                None => parent,
+            };
+            let Some(parent_keypair) = parent.key_data().ed25519() else {
+                bail!("{} is not an ed25519 key", parent_key.display());
+            };
 
-fn prompt_overwrite_path(path: &Path) -> anyhow::Result<()> {
-    if !path.exists() {
-        return Ok(());
-    }
-    let ans = inquire::Confirm::new(&format!("{} already exists, overwrite?", path.display()))
-        .with_default(false)
-        .prompt();
+            let seckey_path = path::absolute(output_path.unwrap_or_else(|| {
+                let mut path = parent_key.into_os_string();
+                path.push(format!("_{index}"));
+                PathBuf::from(path)
+            }))?;
+            let pubkey_path = seckey_path.with_extension("pub");
 
-    match ans {
-        Ok(true) => Ok(()),
+            prompt_overwrite_path(&seckey_path, &overwrite_args)?;
+            prompt_overwrite_path(&pubkey_path, &overwrite_args)?;
+
+            let parent_seed = Zeroizing::new(parent_keypair.private.to_bytes());
+            let child = slip10::ExtendedKey::master(parent_seed.as_ref()).derive_hardened(index);
+            let mut tx = tx_args.new_transaction(creation_time)?;
+            write_ssh_keypair(
+                &mut tx,
+                &seckey_path,
+                &pubkey_path,
+                ssh_key::private::Ed25519Keypair::from_seed(child.key()),
+                comment.unwrap_or_else(|| parent.comment().to_string()),
+                None,
+                passphrase.as_ref(),
+                cli.no_trailing_newline,
+            )?;
+            tx.commit();
+        }
+        Commands::DeriveAllAccounts {
+            coin,
+            count,
+            seed_args,
+        } => {
+            if seed_args.derivation != Derivation::Legacy {
+                bail!(
+                    "coin accounts are always derived with SLIP-0010, --derivation does not apply"
+                );
+            }
+            if seed_args.domain.is_some() {
+                bail!("coin accounts are always derived with SLIP-0010, --domain does not apply");
+            }
+            let mnemonic = prompt_input_mnemonic(seed_args.mnemonic_args)?;
+            let passphrase = resolve_passphrase(
+                seed_args.no_passphrase,
+                seed_args.passphrase_stdin,
+                seed_args.no_passphrase_confirm,
+                seed_args.use_agent,
+                &seed_args.prompt_labels,
+                seed_args.passphrase,
+                seed_args.combine_passphrases,
+            )?;
+            let seed = Zeroizing::new(mnemonic.to_seed(passphrase.expose_secret()));
+            for account in 0..count {
+                println!(
+                    "{account}\t{}\t{}",
+                    coin.path(account),
+                    accounts::address(&seed, coin, account)?
+                );
+            }
+        }
+        Commands::Info => {
+            println!("{}", version::Info::new().to_json()?);
+        }
+        Commands::Bench {
+            key_type,
+            derivation,
+            iterations,
+            encrypt,
+        } => {
+            println!("{}", bench::run(key_type, derivation, iterations, encrypt)?);
+        }
+        Commands::Minisign {
+            output_path,
+            seed_args,
+            overwrite_args,
+            tx_args,
+        } => {
+            let mnemonic = prompt_generate_mnemonic(seed_args.mnemonic_args)?;
+            let passphrase = resolve_passphrase(
+                seed_args.no_passphrase,
+                seed_args.passphrase_stdin,
+                seed_args.no_passphrase_confirm,
+                seed_args.use_agent,
+                &seed_args.prompt_labels,
+                seed_args.passphrase,
+                seed_args.combine_passphrases,
+            )?;
+
+            let seckey_path =
+                path::absolute(output_path.unwrap_or_else(minisign_default_output_path))?;
+            let pubkey_path = seckey_path.with_extension("pub");
+
+            prompt_overwrite_path(&seckey_path, &overwrite_args)?;
+            prompt_overwrite_path(&pubkey_path, &overwrite_args)?;
+
+            let keypair = minisign::KeyPair::new(derive_ed25519_keypair(
+                &mnemonic,
+                &passphrase,
+                seed_args.derivation,
+                derivation::MINISIGN_ED25519,
+                seed_args.domain.as_deref(),
+            ));
+            let mut tx = tx_args.new_transaction(creation_time)?;
+            tx.write_file(
+                &pubkey_path,
+                text_file(&keypair.to_public_key_file(), cli.no_trailing_newline),
+            )?;
+            tx.write_private_file(
+                &seckey_path,
+                text_file(&keypair.to_secret_key_file(), cli.no_trailing_newline),
+            )?;
+            tx.commit();
+            println!("Key id: {}", keypair.key_id());
+        }
+        Commands::Wireguard {
+            output_path,
+            seed_args,
+            overwrite_args,
+            tx_args,
+        } => {
+            let mnemonic = prompt_generate_mnemonic(seed_args.mnemonic_args)?;
+            let passphrase = resolve_passphrase(
+                seed_args.no_passphrase,
+                seed_args.passphrase_stdin,
+                seed_args.no_passphrase_confirm,
+                seed_args.use_agent,
+                &seed_args.prompt_labels,
+                seed_args.passphrase,
+                seed_args.combine_passphrases,
+            )?;
+
+            let seckey_path = path::absolute(output_path)?;
+            let pubkey_path = seckey_path.with_extension("pub");
+
+            prompt_overwrite_path(&seckey_path, &overwrite_args)?;
+            prompt_overwrite_path(&pubkey_path, &overwrite_args)?;
+
+            // Always HKDF: the legacy derivation would give the same key
+            // material as the default SSH key, which a leaked `wg.key`, often
+            // copied into configs, would then reveal.
+            let keypair = wireguard::Keypair::from_key(&derive_key_material(
+                &mnemonic,
+                &passphrase,
+                Derivation::Hkdf,
+                derivation::WIREGUARD_X25519,
+                seed_args.domain.as_deref(),
+            ));
+            let mut tx = tx_args.new_transaction(creation_time)?;
+            let private_key = Zeroizing::new(format!("{}\n", *keypair.private_key()));
+            tx.write_file(
+                &pubkey_path,
+                text_file(
+                    &format!("{}\n", keypair.public_key()),
+                    cli.no_trailing_newline,
+                ),
+            )?;
+            tx.write_private_file(
+                &seckey_path,
+                text_file(&private_key, cli.no_trailing_newline),
+            )?;
+            tx.commit();
+            println!("Public key: {}", keypair.public_key());
+        }
+        Commands::Totp {
+            account,
+            issuer,
+            seed_args,
+        } => {
+            if seed_args.domain.is_some() {
+                bail!("--domain does not apply to TOTP secrets, the account label separates them");
+            }
+            if seed_args.derivation != Derivation::Legacy {
+                bail!(
+                    "TOTP secrets are always derived with their own HKDF, --derivation does not apply"
+                );
+            }
+            let mnemonic = prompt_input_mnemonic(seed_args.mnemonic_args)?;
+            let passphrase = resolve_passphrase(
+                seed_args.no_passphrase,
+                seed_args.passphrase_stdin,
+                seed_args.no_passphrase_confirm,
+                seed_args.use_agent,
+                &seed_args.prompt_labels,
+                seed_args.passphrase,
+                seed_args.combine_passphrases,
+            )?;
+            let seed = Zeroizing::new(mnemonic.to_seed(passphrase.expose_secret()));
+            let secret = totp::derive_secret(&seed, &account);
+            println!(
+                "{}",
+                *totp::otpauth_uri(&account, issuer.as_deref(), secret.as_ref())
+            );
+        }
+        Commands::SkStub {
+            application,
+            seed_args,
+            comment,
+        } => {
+            let mnemonic = prompt_input_mnemonic(seed_args.mnemonic_args)?;
+            let passphrase = resolve_passphrase(
+                seed_args.no_passphrase,
+                seed_args.passphrase_stdin,
+                seed_args.no_passphrase_confirm,
+                seed_args.use_agent,
+                &seed_args.prompt_labels,
+                seed_args.passphrase,
+                seed_args.combine_passphrases,
+            )?;
+            let keypair = derive_ed25519_keypair(
+                &mnemonic,
+                &passphrase,
+                seed_args.derivation,
+                derivation::SSH_ED25519,
+                seed_args.domain.as_deref(),
+            );
+            print!(
+                "{}",
+                sk_stub_descriptor(keypair.public, &application, comment)?
+            );
+        }
+        Commands::Sign {
+            namespace,
+            message,
+            message_file,
+            verify,
+            public_key,
+            seed_args,
+        } => {
+            let msg = match (message, message_file) {
+                (Some(message), _) => message.into_bytes(),
+                (None, Some(path)) => std::fs::read(path)?,
+                (None, None) => unreachable!("input group is required"),
+            };
+
+            if let (Some(signature_path), Some(public_key_path)) = (verify, public_key) {
+                let public_key = ssh_key::PublicKey::read_openssh_file(&public_key_path)?;
+                let signature = std::fs::read_to_string(signature_path)?;
+                sign::verify(&public_key, &namespace, &msg, &signature)?;
+                println!(
+                    "Good signature with {}",
+                    public_key.fingerprint(Default::default())
+                );
+            } else {
+                let mnemonic = prompt_input_mnemonic(seed_args.mnemonic_args)?;
+                let passphrase = resolve_passphrase(
+                    seed_args.no_passphrase,
+                    seed_args.passphrase_stdin,
+                    seed_args.no_passphrase_confirm,
+                    seed_args.use_agent,
+                    &seed_args.prompt_labels,
+                    seed_args.passphrase,
+                    seed_args.combine_passphrases,
+                )?;
+                let secret_key = ssh_key::PrivateKey::new(
+                    ssh_key::private::KeypairData::Ed25519(derive_ed25519_keypair(
+                        &mnemonic,
+                        &passphrase,
+                        seed_args.derivation,
+                        derivation::SSH_ED25519,
+                        seed_args.domain.as_deref(),
+                    )),
+                    "",
+                )?;
+                print!("{}", sign::sign(&secret_key, &namespace, &msg)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn mnemonic_id(mnemonic: &Mnemonic) -> String {
+    use sha2::Digest;
+
+    let normalized = Zeroizing::new(mnemonic.to_string());
+    let mut id = format!("{:x}", sha2::Sha256::digest(normalized.as_bytes()));
+    id.truncate(8);
+    id
+}
+
+/// Splits the passphrase into the BIP39 passphrase and the passphrase that
+/// encrypts the private key, if any, as `passphrase_use` says. Like
+/// `ssh-keygen`, an empty passphrase leaves the private key unencrypted.
+fn split_passphrase(
+    passphrase_use: PassphraseUse,
+    passphrase: SecretString,
+) -> (SecretString, Option<SecretString>) {
+    let encryption = (passphrase_use != PassphraseUse::Seed
+        && !passphrase.expose_secret().is_empty())
+    .then(|| passphrase.clone());
+    match passphrase_use {
+        PassphraseUse::Encryption => (SecretString::new(String::new()), encryption),
+        PassphraseUse::Both | PassphraseUse::Seed => (passphrase, encryption),
+    }
+}
+
+/// Returns the BIP39 passphrase of `--salt-with-comment`: `passphrase`
+/// immediately followed by `comment`, without a separator.
+fn salt_passphrase(passphrase: &SecretString, comment: &str) -> SecretString {
+    SecretString::new(format!("{}{comment}", passphrase.expose_secret()))
+}
+
+fn derive_ed25519_keypair(
+    mnemonic: &Mnemonic,
+    passphrase: &SecretString,
+    derivation: Derivation,
+    domain: derivation::Domain,
+    user_domain: Option<&str>,
+) -> ssh_key::private::Ed25519Keypair {
+    ssh_key::private::Ed25519Keypair::from_seed(&derive_key_material(
+        mnemonic,
+        passphrase,
+        derivation,
+        domain,
+        user_domain,
+    ))
+}
+
+fn derive_key_material(
+    mnemonic: &Mnemonic,
+    passphrase: &SecretString,
+    derivation: Derivation,
+    domain: derivation::Domain,
+    user_domain: Option<&str>,
+) -> Zeroizing<[u8; 32]> {
+    let seed = Zeroizing::new(mnemonic.to_seed(passphrase.expose_secret()));
+    let mut key = derivation::derive_key(&seed, derivation, domain);
+    if let Some(user_domain) = user_domain {
+        key = derivation::separate_user_domain(&key, user_domain);
+    }
+    key
+}
+
+/// Renames every file in `stage` into `target`, checking first that the
+/// stage only holds files. Returns the new paths.
+fn promote(tx: &mut Transaction, stage: &Path, target: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut staged = std::fs::read_dir(stage)
+        .with_context(|| format!("failed to read {}", stage.display()))?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    staged.sort();
+    if staged.is_empty() {
+        bail!("{} is empty", stage.display());
+    }
+    if let Some(path) = staged.iter().find(|path| !path.is_file()) {
+        bail!("{} is not a file", path.display());
+    }
+
+    let mut promoted = Vec::with_capacity(staged.len());
+    for from in staged {
+        let to = target.join(from.file_name().expect("read_dir entries have a file name"));
+        tx.rename(&from, &to)
+            .with_context(|| format!("failed to move {} to {}", from.display(), to.display()))?;
+        promoted.push(to);
+    }
+    Ok(promoted)
+}
+
+fn write_entropy(
+    tx: &mut Transaction,
+    path: &Path,
+    mnemonic: &Mnemonic,
+    no_trailing_newline: bool,
+) -> anyhow::Result<()> {
+    use std::fmt::Write;
+
+    let entropy = Zeroizing::new(mnemonic.to_entropy());
+    let mut hex = Zeroizing::new(String::with_capacity(entropy.len() * 2 + 1));
+    for b in entropy.iter() {
+        write!(hex, "{b:02x}").expect("writing to a String cannot fail");
+    }
+    hex.push('\n');
+    tx.write_private_file(path, text_file(&hex, no_trailing_newline))?;
+    Ok(())
+}
+
+/// Writes the SHA256 fingerprint of `public_key`, as `SHA256:...`.
+fn write_fingerprint_file(
+    tx: &mut Transaction,
+    path: &Path,
+    public_key: &ssh_key::PublicKey,
+    no_trailing_newline: bool,
+) -> anyhow::Result<()> {
+    let fingerprint = format!("{}\n", public_key.fingerprint(ssh_key::HashAlg::Sha256));
+    tx.write_file(path, text_file(&fingerprint, no_trailing_newline))?;
+    Ok(())
+}
+
+/// Writes the keypair as OpenSSH keys, the public key with `pub_comment`
+/// instead of `comment` if given and the private key, only readable by its
+/// owner, encrypted with `encryption` if given.
+#[allow(clippy::too_many_arguments)]
+fn write_ssh_keypair(
+    tx: &mut Transaction,
+    seckey_path: &Path,
+    pubkey_path: &Path,
+    keypair: ssh_key::private::Ed25519Keypair,
+    comment: String,
+    pub_comment: Option<&str>,
+    encryption: Option<&SecretString>,
+    no_trailing_newline: bool,
+) -> anyhow::Result<ssh_key::PublicKey> {
+    let keypair = ssh_key::private::KeypairData::Ed25519(keypair);
+    let public_key = ssh_key::PublicKey::new(
+        ssh_key::public::KeyData::try_from(&keypair)?,
+        pub_comment.unwrap_or(&comment),
+    );
+    let secret_key = ssh_key::PrivateKey::new(keypair, comment)?;
+
+    tx.write_file(
+        pubkey_path,
+        public_key_file(&public_key, no_trailing_newline)?,
+    )?;
+    tx.write_private_file(
+        seckey_path,
+        text_file(
+            &encode_openssh(&secret_key, encryption)?,
+            no_trailing_newline,
+        ),
+    )?;
+    Ok(public_key)
+}
+
+/// Encodes the OpenSSH private key, encrypted with aes256-ctr and a bcrypt
+/// derived key from `encryption` if given, so that `ssh-add` asks for it.
+fn encode_openssh(
+    secret_key: &ssh_key::PrivateKey,
+    encryption: Option<&SecretString>,
+) -> anyhow::Result<Zeroizing<String>> {
+    let openssh = match encryption {
+        Some(passphrase) => secret_key
+            .encrypt(&mut OsRng, passphrase.expose_secret())?
+            .to_openssh(Default::default())?,
+        None => secret_key.to_openssh(Default::default())?,
+    };
+    Ok(openssh)
+}
+
+/// Writes the private key, public key and metadata of `combined` to `paths`,
+/// in this order, the private key only readable by its owner.
+fn split_combined(
+    tx: &mut Transaction,
+    combined: &combined::Combined,
+    [seckey_path, pubkey_path, metadata_path]: &[PathBuf; 3],
+) -> std::io::Result<()> {
+    tx.write_private_file(seckey_path, combined.private_key.as_bytes())?;
+    tx.write_file(pubkey_path, &combined.public_key)?;
+    tx.write_file(metadata_path, &combined.metadata)
+}
+
+/// Rewrites the OpenSSH key pair at `seckey_path` and `pubkey_path` with
+/// `comment`, restoring the permissions the files had.
+fn set_comment(
+    tx: &mut Transaction,
+    seckey_path: &Path,
+    pubkey_path: &Path,
+    comment: String,
+    no_trailing_newline: bool,
+    passphrase: impl FnOnce() -> anyhow::Result<SecretString>,
+) -> anyhow::Result<()> {
+    let read_key = ssh_key::PrivateKey::read_openssh_file(seckey_path)
+        .with_context(|| format!("failed to read {}", seckey_path.display()))?;
+    let passphrase = read_key.is_encrypted().then(passphrase).transpose()?;
+    let mut secret_key = match &passphrase {
+        Some(passphrase) => decrypt_private_key(&read_key, seckey_path, passphrase)?,
+        None => read_key.clone(),
+    };
+    let public_key = ssh_key::PublicKey::read_openssh_file(pubkey_path)
+        .with_context(|| format!("failed to read {}", pubkey_path.display()))?;
+    if public_key.key_data() != secret_key.public_key().key_data() {
+        bail!(
+            "{} is not the public key of {}",
+            pubkey_path.display(),
+            seckey_path.display()
+        );
+    }
+    secret_key.set_comment(comment);
+    if let Some(passphrase) = &passphrase {
+        secret_key = encrypt_like(&secret_key, &read_key, passphrase)?;
+    }
+
+    let seckey_permissions = std::fs::metadata(seckey_path)?.permissions();
+    let pubkey_permissions = std::fs::metadata(pubkey_path)?.permissions();
+    tx.write_file(
+        pubkey_path,
+        public_key_file(secret_key.public_key(), no_trailing_newline)?,
+    )?;
+    tx.set_permissions(pubkey_path, pubkey_permissions)?;
+    tx.write_private_file(
+        seckey_path,
+        text_file(
+            &secret_key.to_openssh(Default::default())?,
+            no_trailing_newline,
+        ),
+    )?;
+    tx.set_permissions(seckey_path, seckey_permissions)?;
+    Ok(())
+}
+
+/// Decrypts `secret_key`, read from `path`, with `passphrase`.
+fn decrypt_private_key(
+    secret_key: &ssh_key::PrivateKey,
+    path: &Path,
+    passphrase: &SecretString,
+) -> anyhow::Result<ssh_key::PrivateKey> {
+    secret_key
+        .decrypt(passphrase.expose_secret())
+        .with_context(|| {
+            format!(
+                "failed to decrypt {}, is the passphrase right?",
+                path.display()
+            )
+        })
+}
+
+/// Encrypts `secret_key` with `passphrase` the way `like` is encrypted: with
+/// the same cipher and KDF rounds, but a new salt.
+fn encrypt_like(
+    secret_key: &ssh_key::PrivateKey,
+    like: &ssh_key::PrivateKey,
+    passphrase: &SecretString,
+) -> anyhow::Result<ssh_key::PrivateKey> {
+    use ssh_key::rand_core::RngCore;
+
+    let kdf = match like.kdf() {
+        ssh_key::Kdf::Bcrypt { salt, rounds } => {
+            let mut salt = vec![0; salt.len()];
+            OsRng.fill_bytes(&mut salt);
+            ssh_key::Kdf::Bcrypt {
+                salt,
+                rounds: *rounds,
+            }
+        }
+        kdf => kdf.clone(),
+    };
+    Ok(secret_key.encrypt_with(
+        like.cipher(),
+        kdf,
+        OsRng.next_u32(),
+        passphrase.expose_secret(),
+    )?)
+}
+
+/// Writes the keypair like [`write_ssh_keypair`], but the OpenSSH private key
+/// base64-encoded into one line, only readable by its owner.
+#[allow(clippy::too_many_arguments)]
+fn write_base64_keypair(
+    tx: &mut Transaction,
+    seckey_path: &Path,
+    pubkey_path: &Path,
+    keypair: ssh_key::private::Ed25519Keypair,
+    comment: String,
+    pub_comment: Option<&str>,
+    encryption: Option<&SecretString>,
+    no_trailing_newline: bool,
+) -> anyhow::Result<ssh_key::PublicKey> {
+    let secret_key =
+        ssh_key::PrivateKey::new(ssh_key::private::KeypairData::Ed25519(keypair), comment)?;
+    let mut public_key = secret_key.public_key().clone();
+    if let Some(pub_comment) = pub_comment {
+        public_key.set_comment(pub_comment);
+    }
+
+    tx.write_file(
+        pubkey_path,
+        public_key_file(&public_key, no_trailing_newline)?,
+    )?;
+    let mut encoded = Zeroizing::new(encode_base64_openssh(&secret_key, encryption)?);
+    if !no_trailing_newline {
+        encoded.push('\n');
+    }
+    tx.write_private_file(seckey_path, encoded.as_bytes())?;
+    Ok(public_key)
+}
+
+/// Encodes the OpenSSH private key as one line of standard base64.
+fn encode_base64_openssh(
+    secret_key: &ssh_key::PrivateKey,
+    encryption: Option<&SecretString>,
+) -> anyhow::Result<String> {
+    use base64ct::Encoding;
+
+    let openssh = encode_openssh(secret_key, encryption)?;
+    Ok(base64ct::Base64::encode_string(openssh.as_bytes()))
+}
+
+/// Decodes a private key written by `--format base64-openssh`.
+fn decode_base64_openssh(encoded: &str) -> anyhow::Result<ssh_key::PrivateKey> {
+    use base64ct::Encoding;
+
+    let openssh = Zeroizing::new(
+        base64ct::Base64::decode_vec(encoded.trim()).context("the key is not valid base64")?,
+    );
+    let openssh = std::str::from_utf8(&openssh).context("the key is not an OpenSSH key")?;
+    Ok(ssh_key::PrivateKey::from_openssh(openssh)?)
+}
+
+/// Returns the contents of the `.pub` file of `public_key`, a line as
+/// `ssh-keygen` writes it.
+fn public_key_file(
+    public_key: &ssh_key::PublicKey,
+    no_trailing_newline: bool,
+) -> anyhow::Result<String> {
+    let mut line = public_key.to_openssh()?;
+    if !no_trailing_newline {
+        line.push('\n');
+    }
+    Ok(line)
+}
+
+/// Returns the contents of a text file, without its trailing newline with
+/// `--no-trailing-newline`.
+fn text_file(text: &str, no_trailing_newline: bool) -> &str {
+    if no_trailing_newline {
+        text.strip_suffix('\n').unwrap_or(text)
+    } else {
+        text
+    }
+}
+
+/// Writes the keypair as raw libsodium keys, the secret key only readable
+/// by its owner.
+fn write_raw_keypair(
+    tx: &mut Transaction,
+    seckey_path: &Path,
+    pubkey_path: &Path,
+    keypair: ssh_key::private::Ed25519Keypair,
+    raw_secret_key: RawSecretKey,
+    comment: String,
+) -> anyhow::Result<ssh_key::PublicKey> {
+    tx.write_file(pubkey_path, keypair.public.as_ref())?;
+    match raw_secret_key {
+        RawSecretKey::Keypair => {
+            tx.write_private_file(seckey_path, Zeroizing::new(keypair.to_bytes()).as_ref())?
+        }
+        RawSecretKey::Seed => tx.write_private_file(
+            seckey_path,
+            Zeroizing::new(keypair.private.to_bytes()).as_ref(),
+        )?,
+    }
+    Ok(ssh_key::PublicKey::new(
+        ssh_key::public::KeyData::Ed25519(keypair.public),
+        comment,
+    ))
+}
+
+/// Reads the key files written in `format` back, and fails unless both the
+/// private key and the public key are `expected`.
+fn verify_written_keys(
+    format: KeyFormat,
+    seckey_path: &Path,
+    pubkey_path: &Path,
+    expected: &ssh_key::public::KeyData,
+) -> anyhow::Result<()> {
+    let read = |path: &Path| {
+        std::fs::read(path).with_context(|| format!("failed to read back {}", path.display()))
+    };
+    let (private, public) = match format {
+        KeyFormat::Openssh | KeyFormat::Base64Openssh => {
+            let secret_key = if format == KeyFormat::Openssh {
+                ssh_key::PrivateKey::read_openssh_file(seckey_path).map_err(anyhow::Error::from)
+            } else {
+                let encoded = Zeroizing::new(read(seckey_path)?);
+                std::str::from_utf8(&encoded)
+                    .map_err(anyhow::Error::from)
+                    .and_then(decode_base64_openssh)
+            }
+            .with_context(|| format!("{} does not hold a valid key", seckey_path.display()))?;
+            let public_key = ssh_key::PublicKey::read_openssh_file(pubkey_path)
+                .with_context(|| format!("{} does not hold a valid key", pubkey_path.display()))?;
+            (
+                secret_key.public_key().key_data().clone(),
+                public_key.key_data().clone(),
+            )
+        }
+        KeyFormat::Raw => {
+            let secret = Zeroizing::new(read(seckey_path)?);
+            let keypair = match secret.len() {
+                64 => ssh_key::private::Ed25519Keypair::from_bytes(
+                    secret.as_slice().try_into().expect("checked the length"),
+                )
+                .ok(),
+                32 => Some(ssh_key::private::Ed25519Keypair::from_seed(
+                    secret.as_slice().try_into().expect("checked the length"),
+                )),
+                _ => None,
+            };
+            let Some(keypair) = keypair else {
+                bail!("{} does not hold a valid key", seckey_path.display());
+            };
+            let public = ssh_key::public::Ed25519PublicKey::try_from(read(pubkey_path)?.as_slice())
+                .with_context(|| format!("{} does not hold a valid key", pubkey_path.display()))?;
+            (
+                ssh_key::public::KeyData::Ed25519(keypair.public),
+                ssh_key::public::KeyData::Ed25519(public),
+            )
+        }
+    };
+    for (path, key_data) in [(seckey_path, private), (pubkey_path, public)] {
+        if key_data != *expected {
+            bail!(
+                "{} holds the key {}, not the written {}",
+                path.display(),
+                key_data.fingerprint(Default::default()),
+                expected.fingerprint(Default::default())
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_fingerprint(
+    key_data: &ssh_key::public::KeyData,
+    expected: &ssh_key::Fingerprint,
+) -> anyhow::Result<()> {
+    let actual = key_data.fingerprint(expected.algorithm());
+    if actual != *expected {
+        bail!(
+            "the derived key has the fingerprint {actual}, not {expected}, check the mnemonic and the passphrase"
+        );
+    }
+    Ok(())
+}
+
+/// Expands the `{fp}` and `{id}` placeholders of an output path with the
+/// SHA256 fingerprint of `key_data` in hex, or its first 16 hex digits.
+fn expand_output_path(path: &Path, key_data: &ssh_key::public::KeyData) -> PathBuf {
+    let Some(template) = path.to_str() else {
+        return path.to_path_buf();
+    };
+    let fp = fingerprint_hex(key_data);
+    PathBuf::from(template.replace("{fp}", &fp).replace("{id}", &fp[..16]))
+}
+
+/// Where a key is used, as a URI such as `ssh:github.com`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Application(String);
+
+impl std::str::FromStr for Application {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((scheme, rest)) = s.split_once(':') else {
+            bail!("expected a URI such as ssh:github.com");
+        };
+        let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+        if !valid_scheme {
+            bail!("invalid URI scheme \"{scheme}\"");
+        }
+        // The comment is split on spaces, so the URI must not have any.
+        if rest.is_empty() || !rest.chars().all(|c| c.is_ascii_graphic()) {
+            bail!("expected a URI without spaces after \"{scheme}:\"");
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+/// Appends `application` to `comment` as an ` app=<uri>` field.
+fn with_application(comment: String, application: Option<&Application>) -> String {
+    match application {
+        Some(Application(uri)) => format!("{comment} app={uri}"),
+        None => comment,
+    }
+}
+
+/// Returns a comment made from the first 8 hex digits of the SHA256
+/// fingerprint of `key_data`, such as `key-ab12cd34`.
+fn key_comment(key_data: &ssh_key::public::KeyData) -> String {
+    format!("key-{}", &fingerprint_hex(key_data)[..8])
+}
+
+fn fingerprint_hex(key_data: &ssh_key::public::KeyData) -> String {
+    use std::fmt::Write;
+
+    let mut fp = String::with_capacity(64);
+    for b in key_data.fingerprint(Default::default()).as_bytes() {
+        write!(fp, "{b:02x}").expect("writing to a String cannot fail");
+    }
+    fp
+}
+
+/// Formats the messages `ssh-keygen` prints after saving a key.
+fn ssh_keygen_report(
+    seckey_path: &Path,
+    pubkey_path: &Path,
+    public_key: &ssh_key::PublicKey,
+) -> String {
+    let fingerprint = public_key.fingerprint(Default::default());
+    let header = match public_key.algorithm() {
+        ssh_key::Algorithm::Ed25519 => "[ED25519 256]",
+        _ => "",
+    };
+    format!(
+        "Your identification has been saved in {}\n\
+         Your public key has been saved in {}\n\
+         The key fingerprint is:\n\
+         {fingerprint} {}\n\
+         The key's randomart image is:\n\
+         {}\n",
+        seckey_path.display(),
+        pubkey_path.display(),
+        public_key.comment(),
+        fingerprint.to_randomart(header),
+    )
+}
+
+fn sk_stub_descriptor(
+    public: ssh_key::public::Ed25519PublicKey,
+    application: &str,
+    comment: String,
+) -> anyhow::Result<String> {
+    if !application.starts_with("ssh:") {
+        bail!("FIDO application must start with \"ssh:\"");
+    }
+    let public_key = ssh_key::PublicKey::new(
+        ssh_key::public::KeyData::SkEd25519(ssh_key::public::SkEd25519::new(public, application)),
+        comment,
+    );
+    Ok(format!(
+        "# PLACEHOLDER ONLY: this key is NOT backed by a security key and cannot authenticate.\n\
+         # Replace it with the output of `ssh-keygen -t ed25519-sk` once the device is enrolled.\n\
+         {}\n",
+        public_key.to_openssh()?
+    ))
+}
+
+/// Prints `warning`, or fails with it in `--strict` mode.
+fn warn(strict: bool, warning: Option<String>) -> anyhow::Result<()> {
+    match warning {
+        Some(warning) if strict => bail!("{warning}, failing in --strict mode"),
+        Some(warning) => {
+            eprintln!("Warning: {warning}");
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+fn check_comment_len(
+    mut comment: String,
+    max_len: usize,
+    policy: LongCommentPolicy,
+) -> anyhow::Result<String> {
+    if comment.len() <= max_len {
+        return Ok(comment);
+    }
+    match policy {
+        LongCommentPolicy::Error => bail!(
+            "comment is {} bytes long, exceeding the maximum of {max_len}",
+            comment.len()
+        ),
+        LongCommentPolicy::Truncate => {
+            let mut len = max_len;
+            while !comment.is_char_boundary(len) {
+                len -= 1;
+            }
+            comment.truncate(len);
+            eprintln!("Warning: comment truncated to {len} bytes");
+            Ok(comment)
+        }
+    }
+}
+
+fn ssh_default_output_path(key_type: KeyType) -> PathBuf {
+    use std::path::MAIN_SEPARATOR;
+
+    match home::home_dir() {
+        Some(path) if !path.as_os_str().is_empty() => path.join(format!(
+            ".ssh{MAIN_SEPARATOR}{}",
+            key_type.as_openssh_filename()
+        )),
+        _ => PathBuf::new(),
+    }
+}
+
+fn minisign_default_output_path() -> PathBuf {
+    match home::home_dir() {
+        Some(path) if !path.as_os_str().is_empty() => path.join(".minisign").join("minisign.key"),
+        _ => PathBuf::from("minisign.key"),
+    }
+}
+
+fn ssh_default_comment() -> String {
+    format!(
+        "{}@{}",
+        whoami::username(),
+        whoami::fallible::hostname().unwrap_or_else(|_| "localhost".to_string())
+    )
+}
+
+#[allow(dead_code)]
+fn gpg_default_output_dir() -> PathBuf {
+    match home::home_dir() {
+        Some(path) if !path.as_os_str().is_empty() => path.join(".gnupg"),
+        _ => PathBuf::new(),
+    }
+}
+
+fn render_config(theme: Theme, no_color: bool) -> inquire::ui::RenderConfig<'static> {
+    if no_color || theme == Theme::Plain {
+        inquire::ui::RenderConfig::empty()
+    } else {
+        inquire::ui::RenderConfig::default_colored()
+    }
+}
+
+fn resolve_passphrase(
+    no_passphrase: bool,
+    passphrase_stdin: bool,
+    no_passphrase_confirm: bool,
+    use_agent: bool,
+    prompt_labels: &PromptLabels,
+    passphrases: Vec<SecretString>,
+    combine: passphrase::Combine,
+) -> anyhow::Result<SecretString> {
+    if passphrase_stdin {
+        return read_passphrase_line(std::io::stdin().lock());
+    }
+    if no_passphrase {
+        Ok(SecretString::new(String::new()))
+    } else if passphrases.iter().all(|p| p.expose_secret().is_empty()) && use_agent {
+        agent::get_passphrase(
+            &prompt_labels.seed_passphrase_prompt_label,
+            !no_passphrase_confirm,
+        )
+    } else if passphrases.iter().all(|p| p.expose_secret().is_empty()) {
+        prompt_passphrase(
+            |message| {
+                Ok(inquire::Password::new(message)
+                    .with_display_mode(inquire::PasswordDisplayMode::Masked)
+                    .without_confirmation()
+                    .prompt()?)
+            },
+            prompt_labels,
+            !no_passphrase_confirm,
+        )
+    } else {
+        Ok(passphrase::combine(&passphrases, combine))
+    }
+}
+
+/// Reads a passphrase from the first line of `reader`, without the line
+/// ending.
+fn read_passphrase_line(mut reader: impl std::io::BufRead) -> anyhow::Result<SecretString> {
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .context("failed to read the passphrase from stdin")?;
+    let line = SecretString::new(line);
+    let passphrase = line.expose_secret();
+    let passphrase = passphrase.strip_suffix('\n').unwrap_or(passphrase);
+    let passphrase = passphrase.strip_suffix('\r').unwrap_or(passphrase);
+    Ok(SecretString::new(passphrase.to_string()))
+}
+
+/// Prompts for the passphrase, and with `confirm` for the same passphrase
+/// again, until both entries match. A mistyped passphrase would silently
+/// derive a different key.
+fn prompt_passphrase(
+    mut prompt: impl FnMut(&str) -> anyhow::Result<String>,
+    labels: &PromptLabels,
+    confirm: bool,
+) -> anyhow::Result<SecretString> {
+    loop {
+        let passphrase = SecretString::new(prompt(&labels.seed_passphrase_prompt_label)?);
+        if !confirm {
+            break Ok(passphrase);
+        }
+        let confirmation = SecretString::new(prompt(&labels.seed_passphrase_confirm_label)?);
+        if passphrase.expose_secret() == confirmation.expose_secret() {
+            break Ok(passphrase);
+        }
+        eprintln!("Passphrases do not match, try again");
+    }
+}
+
+/// Asks for the output path unless `outpath` is given, and with
+/// `canonicalize` resolves `.` and `..` in it, see [`normalize_lexically`].
+fn prompt_output_path(
+    outpath: Option<PathBuf>,
+    default_path: PathBuf,
+    canonicalize: bool,
+) -> anyhow::Result<PathBuf> {
+    prompt_output_path_with(outpath, default_path, canonicalize, |default| {
+        let mut text = inquire::Text::new("Enter file in which to save the key");
+        if let Some(default) = default {
+            text = text.with_default(default);
+        }
+        Ok(text.prompt()?)
+    })
+}
+
+/// Like [`prompt_output_path`], asking with `prompt`, which is given the
+/// default path to offer.
+fn prompt_output_path_with(
+    outpath: Option<PathBuf>,
+    default_path: PathBuf,
+    canonicalize: bool,
+    prompt: impl FnOnce(Option<&str>) -> anyhow::Result<String>,
+) -> anyhow::Result<PathBuf> {
+    let path = match outpath {
+        Some(path) => path,
+        None => PathBuf::from(prompt(default_path.to_str())?),
+    };
+    let path = path::absolute(path)?;
+    Ok(if canonicalize {
+        normalize_lexically(&path)
+    } else {
+        path
+    })
+}
+
+/// Removes the `.` and `..` components of the absolute `path`, each `..`
+/// dropping the component before it, without touching the file system, so
+/// the path need not exist. A `..` after a symlink therefore goes back to
+/// the directory of the symlink, not to the parent of its target.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            path::Component::CurDir => {}
+            path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Returns the file `path` names, with the symlinks and `..` of the
+/// directories leading to it resolved as far as they exist, and the rest,
+/// which the transaction is yet to create, normalized lexically.
+fn resolve_parent(path: &Path) -> std::io::Result<PathBuf> {
+    let path = path::absolute(path)?;
+    let (Some(parent), Some(file_name)) = (path.parent(), path.file_name()) else {
+        return Ok(normalize_lexically(&path));
+    };
+    for dir in parent.ancestors() {
+        if let Ok(resolved) = dir.canonicalize() {
+            let missing = parent.strip_prefix(dir).expect("dir is an ancestor");
+            return Ok(normalize_lexically(&resolved.join(missing)).join(file_name));
+        }
+    }
+    Ok(normalize_lexically(&path))
+}
+
+/// Fails if two of the files written in one run, each named by what it is,
+/// are the same file, since the later one would silently replace the other.
+fn check_distinct_targets(targets: &[(&str, &Path)]) -> anyhow::Result<()> {
+    let mut seen: Vec<(&str, PathBuf)> = Vec::with_capacity(targets.len());
+    for &(name, target) in targets {
+        let target = resolve_parent(target)?;
+        if let Some((other, _)) = seen.iter().find(|(_, path)| *path == target) {
+            bail!(
+                "{other} and {name} would both be written to {}",
+                target.display()
+            );
+        }
+        seen.push((name, target));
+    }
+    Ok(())
+}
+
+/// Rejects paths directly inside well-known system directories, or anywhere
+/// under a virtual filesystem.
+fn check_not_system_path(path: &Path) -> anyhow::Result<()> {
+    const SYSTEM_DIRS: &[&str] = &[
+        "/",
+        "/bin",
+        "/boot",
+        "/etc",
+        "/lib",
+        "/lib64",
+        "/sbin",
+        "/usr",
+        "/usr/bin",
+        "/usr/lib",
+        "/usr/sbin",
+        "/var",
+    ];
+    const VIRTUAL_DIRS: &[&str] = &["/dev", "/proc", "/sys"];
+
+    let in_system_dir = path.parent().map_or(true, |parent| {
+        SYSTEM_DIRS.iter().any(|dir| parent == Path::new(dir))
+    });
+    let in_virtual_dir = VIRTUAL_DIRS.iter().any(|dir| path.starts_with(dir));
+    if in_system_dir || in_virtual_dir {
+        bail!(
+            "refusing to write into system path {}, pass --allow-system-path to override",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// The choices of the `ssh` command, summarized before anything is written.
+struct SshReview<'a> {
+    key_type: KeyType,
+    seckey_path: &'a Path,
+    pubkey_path: &'a Path,
+    comment: &'a str,
+    encrypted: bool,
+    derivation: Derivation,
+    domain: Option<&'a str>,
+    fingerprint: ssh_key::Fingerprint,
+}
+
+impl std::fmt::Display for SshReview<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Key type:    {}", self.key_type.as_ssh_keygen_type())?;
+        writeln!(f, "Private key: {}", self.seckey_path.display())?;
+        writeln!(f, "Public key:  {}", self.pubkey_path.display())?;
+        writeln!(f, "Comment:     {}", self.comment)?;
+        let encrypted = if self.encrypted {
+            "yes, with the passphrase"
+        } else {
+            "no"
+        };
+        writeln!(f, "Encrypted:   {encrypted}")?;
+        match self.derivation {
+            Derivation::Legacy => {
+                writeln!(f, "Derivation:  legacy, the first 32 bytes of the seed")?
+            }
+            Derivation::Hkdf => {
+                writeln!(f, "Derivation:  hkdf, {}", derivation::SSH_ED25519.label())?
+            }
+        }
+        if let Some(domain) = self.domain {
+            writeln!(f, "Domain:      {domain}")?;
+        }
+        write!(f, "Fingerprint: {}", self.fingerprint)
+    }
+}
+
+/// Prints `review` to stderr, next to the prompt, and aborts unless
+/// `confirm` returns true. Stdout is left to what the options print, such as
+/// `--emit-shell-env`.
+fn confirm_review(
+    review: &impl std::fmt::Display,
+    confirm: impl FnOnce() -> anyhow::Result<bool>,
+) -> anyhow::Result<()> {
+    eprintln!("{review}");
+    match confirm() {
+        Ok(true) => Ok(()),
+        _ => bail!("Aborted"),
+    }
+}
+
+fn prompt_write_paper(path: &Path) -> anyhow::Result<()> {
+    eprintln!(
+        "WARNING: {} will hold the mnemonic in plain text. Anyone who reads the file or the \
+         printout can derive your keys. Print it on a trusted printer and delete the file.",
+        path.display()
+    );
+    let ans = inquire::Confirm::new("Write the paper backup?")
+        .with_default(false)
+        .prompt();
+
+    match ans {
+        Ok(true) => Ok(()),
+        _ => bail!("Aborted"),
+    }
+}
+
+fn prompt_write_checksum_sheet(path: &Path) -> anyhow::Result<()> {
+    eprintln!(
+        "WARNING: {} will hold a code for each word of the mnemonic. Together they narrow the \
+         mnemonic down enough to recover it, so keep the sheet as safe as the mnemonic itself.",
+        path.display()
+    );
+    let ans = inquire::Confirm::new("Write the checksum sheet?")
+        .with_default(false)
+        .prompt();
+
+    match ans {
+        Ok(true) => Ok(()),
+        _ => bail!("Aborted"),
+    }
+}
+
+fn prompt_overwrite_path(path: &Path, overwrite_args: &OverwriteArgs) -> anyhow::Result<()> {
+    prompt_overwrite_path_with(path, overwrite_args, |message| {
+        Ok(inquire::Confirm::new(message)
+            .with_default(false)
+            .prompt()?)
+    })
+}
+
+/// Like [`prompt_overwrite_path`], asking with `confirm`.
+fn prompt_overwrite_path_with(
+    path: &Path,
+    overwrite_args: &OverwriteArgs,
+    confirm: impl FnOnce(&str) -> anyhow::Result<bool>,
+) -> anyhow::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    // A symlink is never taken for an empty file, whatever it points to.
+    if overwrite_args.allow_existing_empty_file
+        && path
+            .symlink_metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.len() == 0)
+    {
+        return Ok(());
+    }
+    if overwrite_args.fail_if_exists {
+        bail!("{} already exists", path.display());
+    }
+    match confirm(&format!("{} already exists, overwrite?", path.display())) {
+        Ok(true) => Ok(()),
         _ => bail!("Aborted"),
     }
 }
 
-fn prompt_generate_mnemonic(
-    mnemonic_opt: Option<SecretString>,
+fn prompt_input_mnemonic(mnemonic_args: MnemonicArgs) -> anyhow::Result<Zeroizing<Mnemonic>> {
+    let language = mnemonic_args.language.map(Language::from);
+    let mnemonic = match (
+        mnemonic_args.mnemonic,
+        mnemonic_args.mnemonic_keyring,
+        mnemonic_args.mnemonic_file,
+    ) {
+        (Some(mnemonic), _, _) => mnemonic,
+        (None, Some(keyring), _) => keyring::load(&keyring.entry()?)?,
+        (None, None, Some(path)) => read_mnemonic_file(&path)?,
+        (None, None, None) => {
+            return input_mnemonic(
+                || {
+                    Ok(SecretString::new(
+                        inquire::Text::new(
+                            "Enter your 12-word mnemonic (separate words with spaces)",
+                        )
+                        .prompt()?,
+                    ))
+                },
+                language,
+                mnemonic_args.max_mnemonic_attempts,
+            );
+        }
+    };
+    Ok(Zeroizing::new(mnemonic::parse(
+        mnemonic.expose_secret(),
+        language,
+    )?))
+}
+
+/// Reads the mnemonic of `--mnemonic-file` up to the end of the file. Opening
+/// a named pipe waits for a writer, and its end is when the writer closes it.
+fn read_mnemonic_file(path: &Path) -> anyhow::Result<SecretString> {
+    let contents = Zeroizing::new(
+        std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read the mnemonic from {}", path.display()))?,
+    );
+    Ok(SecretString::new(contents.trim().to_string()))
+}
+
+/// Asks for the mnemonic with `prompt` until it parses, at most
+/// `max_attempts` times, printing why each mistyped one was rejected.
+fn input_mnemonic(
+    mut prompt: impl FnMut() -> anyhow::Result<SecretString>,
+    language: Option<Language>,
+    max_attempts: u32,
 ) -> anyhow::Result<Zeroizing<Mnemonic>> {
-    if let Some(mnemonic) = mnemonic_opt {
-        return Ok(Zeroizing::new(Mnemonic::from_str(
-            mnemonic.expose_secret(),
-        )?));
+    let mut attempt = 1;
+    loop {
+        let mnemonic = prompt()?;
+        match mnemonic::parse(mnemonic.expose_secret(), language) {
+            Ok(mnemonic) => break Ok(Zeroizing::new(mnemonic)),
+            Err(e) if attempt < max_attempts => {
+                eprintln!("{e}, try again");
+                attempt += 1;
+            }
+            Err(e) => break Err(e.into()),
+        }
+    }
+}
+
+fn prompt_generate_mnemonic(mnemonic_args: MnemonicArgs) -> anyhow::Result<Zeroizing<Mnemonic>> {
+    if mnemonic_args.mnemonic.is_some()
+        || mnemonic_args.mnemonic_keyring.is_some()
+        || mnemonic_args.mnemonic_file.is_some()
+    {
+        return prompt_input_mnemonic(mnemonic_args);
     }
 
     match inquire::Select::new("Choose mnemonic option", vec![
         "Input mnemonic",
+        "Input mnemonic word by word",
         "Generate new mnemonic",
     ])
     .prompt()?
     {
-        "Input mnemonic" => {
-            let mnemonic = SecretString::new(
-                inquire::Text::new("Enter your 12-word mnemonic (separate words with spaces)")
-                    .prompt()?,
+        "Input mnemonic" => prompt_input_mnemonic(mnemonic_args),
+        "Input mnemonic word by word" => {
+            let language = mnemonic_args
+                .language
+                .map_or(Language::English, Language::from);
+            let word_count =
+                inquire::Select::new("How many words?", vec![12, 15, 18, 21, 24]).prompt()?;
+            input_words(
+                |i| {
+                    Ok(inquire::Text::new(&format!("Word {i}:"))
+                        .with_autocomplete(wordlist::Completer { language })
+                        .prompt()?)
+                },
+                language,
+                word_count,
+            )
+        }
+        "Generate new mnemonic" => generate_mnemonic(
+            || Ok(Mnemonic::generate_in(Language::English, 12)?),
+            || {
+                Ok(
+                    inquire::Confirm::new("Do you want to regenerate a new mnemonic?")
+                        .with_default(false)
+                        .prompt()?,
+                )
+            },
+            mnemonic_args.assume_yes_for_generation,
+        ),
+        _ => unreachable!(),
+    }
+}
+
+/// Asks for the `word_count` words of a mnemonic one by one with `prompt`,
+/// which gets the number of the word. A word may be cut to a prefix only it
+/// starts with, and is asked for again if it is not in the wordlist.
+fn input_words(
+    mut prompt: impl FnMut(usize) -> anyhow::Result<String>,
+    language: Language,
+    word_count: usize,
+) -> anyhow::Result<Zeroizing<Mnemonic>> {
+    let mut words = Vec::with_capacity(word_count);
+    for i in 1..=word_count {
+        loop {
+            let input = Zeroizing::new(prompt(i)?);
+            match wordlist::resolve(language, &input) {
+                Some(word) => {
+                    words.push(word);
+                    break;
+                }
+                None => eprintln!(
+                    "\"{}\" is not a word of the {} wordlist, nor the start of only one, try again",
+                    input.trim(),
+                    WordlistLanguage::from(language).name()
+                ),
+            }
+        }
+    }
+    let phrase = Zeroizing::new(words.join(" "));
+    Ok(Zeroizing::new(Mnemonic::parse_in(
+        language,
+        phrase.as_str(),
+    )?))
+}
+
+/// Shows generated mnemonics until `regenerate` declines, or only the first
+/// one with `assume_yes`.
+fn generate_mnemonic(
+    mut generate: impl FnMut() -> anyhow::Result<Mnemonic>,
+    mut regenerate: impl FnMut() -> anyhow::Result<bool>,
+    assume_yes: bool,
+) -> anyhow::Result<Zeroizing<Mnemonic>> {
+    loop {
+        let mnemonic = Zeroizing::new(generate()?);
+
+        println!("Your new 12-word mnemonic is:");
+        println!("  {}", *mnemonic);
+        println!("Please write it down and store it in a safe place");
+
+        if assume_yes || !regenerate()? {
+            break Ok(mnemonic);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn mnemonic_id_is_deterministic() {
+        let mnemonic = Mnemonic::parse(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+             abandon about",
+        )
+        .unwrap();
+        let other = Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+
+        let id = mnemonic_id(&mnemonic);
+        assert_eq!(id.len(), 8);
+        assert_eq!(id, mnemonic_id(&mnemonic));
+        assert_ne!(id, mnemonic_id(&other));
+    }
+
+    #[test]
+    fn sk_stub_algorithm() {
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+        let descriptor = sk_stub_descriptor(keypair.public, "ssh:", "me".to_string()).unwrap();
+
+        let line = descriptor.lines().find(|l| !l.starts_with('#')).unwrap();
+        assert_eq!(line.split(' ').next(), Some("sk-ssh-ed25519@openssh.com"));
+        assert!(sk_stub_descriptor(keypair.public, "web:", "me".to_string()).is_err());
+    }
+
+    #[test]
+    fn system_path() {
+        assert!(check_not_system_path(Path::new("/etc/id_ed25519")).is_err());
+        assert!(check_not_system_path(Path::new("/id_ed25519")).is_err());
+        assert!(check_not_system_path(Path::new("/proc/self/id_ed25519")).is_err());
+
+        assert!(check_not_system_path(Path::new("/etc/ssh/ssh_host_ed25519_key")).is_ok());
+        assert!(check_not_system_path(Path::new("/home/user/.ssh/id_ed25519")).is_ok());
+    }
+
+    #[test]
+    fn no_color_render_config() {
+        use inquire::ui::StyleSheet;
+
+        assert_ne!(
+            render_config(Theme::Fancy, false).answer,
+            StyleSheet::empty()
+        );
+        assert_eq!(
+            render_config(Theme::Fancy, true).answer,
+            StyleSheet::empty()
+        );
+        assert_eq!(
+            render_config(Theme::Plain, false).answer,
+            StyleSheet::empty()
+        );
+    }
+
+    #[test]
+    fn allow_existing_empty_file() {
+        let testdir = tempfile::tempdir().unwrap();
+        let empty = testdir.path().join("id_ed25519");
+        let non_empty = testdir.path().join("id_ed25519.pub");
+        std::fs::write(&empty, "").unwrap();
+        std::fs::write(&non_empty, "ssh-ed25519 AAAA").unwrap();
+        let overwrite_args = OverwriteArgs {
+            fail_if_exists: false,
+            allow_existing_empty_file: true,
+        };
+
+        prompt_overwrite_path_with(&empty, &overwrite_args, |_| {
+            panic!("an empty file is overwritten without prompting")
+        })
+        .unwrap();
+
+        let mut prompted = None;
+        let err = prompt_overwrite_path_with(&non_empty, &overwrite_args, |message| {
+            prompted = Some(message.to_string());
+            Ok(false)
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Aborted");
+        assert_eq!(
+            prompted,
+            Some(format!(
+                "{} already exists, overwrite?",
+                non_empty.display()
+            ))
+        );
+    }
+
+    #[test]
+    fn fail_if_exists() {
+        let testdir = tempfile::tempdir().unwrap();
+        let path = testdir.path().join("id_ed25519");
+        let overwrite_args = OverwriteArgs {
+            fail_if_exists: true,
+            allow_existing_empty_file: false,
+        };
+
+        prompt_overwrite_path(&path, &overwrite_args).unwrap();
+
+        std::fs::write(&path, "").unwrap();
+        let err = prompt_overwrite_path(&path, &overwrite_args).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("{} already exists", path.display())
+        );
+    }
+
+    #[test]
+    fn long_comment_error() {
+        let res = check_comment_len("a".repeat(256), 255, LongCommentPolicy::Error);
+        assert!(res.is_err());
+
+        let comment = check_comment_len("a".repeat(255), 255, LongCommentPolicy::Error).unwrap();
+        assert_eq!(comment.len(), 255);
+    }
+
+    #[test]
+    fn long_comment_truncate() {
+        let comment = check_comment_len("a".repeat(256), 255, LongCommentPolicy::Truncate).unwrap();
+        assert_eq!(comment, "a".repeat(255));
+
+        // Never splits a multi-byte character.
+        let comment = check_comment_len("ééé".to_string(), 5, LongCommentPolicy::Truncate).unwrap();
+        assert_eq!(comment, "éé");
+    }
+
+    #[test]
+    fn output_path_template() {
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+        let key_data = ssh_key::public::KeyData::Ed25519(keypair.public);
+        let fp = hex::encode(key_data.fingerprint(Default::default()).as_bytes());
+
+        let path = expand_output_path(Path::new("/keys/{fp}/id_ed25519"), &key_data);
+        assert_eq!(path, PathBuf::from(format!("/keys/{fp}/id_ed25519")));
+
+        let path = expand_output_path(Path::new("/keys/id_ed25519_{id}"), &key_data);
+        assert_eq!(
+            path,
+            PathBuf::from(format!("/keys/id_ed25519_{}", &fp[..16]))
+        );
+
+        let path = expand_output_path(Path::new("/keys/id_ed25519"), &key_data);
+        assert_eq!(path, PathBuf::from("/keys/id_ed25519"));
+    }
+
+    #[test]
+    fn no_trailing_newline() {
+        let testdir = tempfile::tempdir().unwrap();
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+
+        let mut lens = Vec::new();
+        for (name, no_trailing_newline) in [("a", false), ("b", true)] {
+            let seckey_path = testdir.path().join(name);
+            let mut tx = Transaction::without_temp_dir();
+            write_ssh_keypair(
+                &mut tx,
+                &seckey_path,
+                &seckey_path.with_extension("pub"),
+                keypair.clone(),
+                "me".to_string(),
+                None,
+                None,
+                no_trailing_newline,
+            )
+            .unwrap();
+            tx.commit();
+            lens.push(std::fs::read(&seckey_path).unwrap().len());
+        }
+
+        assert_eq!(lens[0], lens[1] + 1);
+        assert_eq!(text_file("hi\n", false), "hi\n");
+        assert_eq!(text_file("hi\n", true), "hi");
+        assert_eq!(text_file("hi", true), "hi");
+    }
+
+    #[test]
+    fn confirm_fingerprint() {
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+        let key_data = ssh_key::public::KeyData::Ed25519(keypair.public);
+        let fingerprint = key_data.fingerprint(Default::default());
+
+        check_fingerprint(&key_data, &fingerprint).unwrap();
+        check_fingerprint(&key_data, &fingerprint.to_string().parse().unwrap()).unwrap();
+
+        let other = ssh_key::private::Ed25519Keypair::from_seed(&[8; 32]);
+        let other = ssh_key::public::KeyData::Ed25519(other.public).fingerprint(Default::default());
+        let err = check_fingerprint(&key_data, &other).unwrap_err();
+        assert!(err.to_string().contains(&other.to_string()));
+    }
+
+    #[test]
+    fn passphrase_stdin() {
+        let mnemonic = Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+        let derive = |passphrase: &SecretString| {
+            derive_ed25519_keypair(
+                &mnemonic,
+                passphrase,
+                Derivation::Legacy,
+                derivation::SSH_ED25519,
+                None,
+            )
+            .public
+        };
+        let expected = derive(&SecretString::new("hunter2".to_string()));
+
+        for input in ["hunter2\n", "hunter2\r\n", "hunter2", "hunter2\nignored\n"] {
+            let passphrase = read_passphrase_line(input.as_bytes()).unwrap();
+            assert_eq!(derive(&passphrase), expected);
+        }
+        let empty = read_passphrase_line("\n".as_bytes()).unwrap();
+        assert_eq!(empty.expose_secret(), "");
+    }
+
+    #[test]
+    fn passphrase_salted_with_comment() {
+        let mnemonic = Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+        let passphrase = SecretString::new("hunter2".to_string());
+        let derive = |passphrase: &SecretString| {
+            derive_ed25519_keypair(
+                &mnemonic,
+                passphrase,
+                Derivation::Legacy,
+                derivation::SSH_ED25519,
+                None,
+            )
+            .public
+        };
+
+        let salted = salt_passphrase(&passphrase, "me@work");
+        assert_eq!(salted.expose_secret(), "hunter2me@work");
+        assert_ne!(
+            derive(&salted),
+            derive(&salt_passphrase(&passphrase, "me@home"))
+        );
+        assert_ne!(derive(&salted), derive(&passphrase));
+        // Without the flag, the comment is not part of the derivation.
+        assert_eq!(
+            derive(&SecretString::new("hunter2".to_string())),
+            derive(&passphrase)
+        );
+    }
+
+    #[test]
+    fn salt_with_comment_requires_comment() {
+        let argv = [
+            "bip39-keygen",
+            "ssh",
+            "-t",
+            "ed25519",
+            "--salt-with-comment",
+        ];
+        let Err(err) = Cli::try_parse_from(argv) else {
+            panic!("--salt-with-comment is accepted without a comment");
+        };
+        assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+        Cli::try_parse_from(argv.into_iter().chain(["-C", "me@work"])).unwrap();
+    }
+
+    #[test]
+    fn wireguard_keys_are_reproducible() {
+        let mnemonic = Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+        let derive = || {
+            wireguard::Keypair::from_key(&derive_key_material(
+                &mnemonic,
+                &SecretString::new(String::new()),
+                Derivation::Hkdf,
+                derivation::WIREGUARD_X25519,
+                None,
+            ))
+        };
+        let keypair = derive();
+        assert_eq!(derive().private_key(), keypair.private_key());
+        assert_eq!(derive().public_key(), keypair.public_key());
+        assert_eq!(keypair.public_key().len(), 44);
+    }
+
+    #[test]
+    fn wireguard_key_is_not_the_ssh_key() {
+        let testdir = tempfile::tempdir().unwrap();
+        let wg_path = testdir.path().join("wg.key");
+        run(Cli::try_parse_from([
+            "bip39-keygen",
+            "wireguard",
+            "-m",
+            TEST_MNEMONIC,
+            "-N",
+            "-f",
+            wg_path.to_str().unwrap(),
+        ])
+        .unwrap())
+        .unwrap();
+        let wg_key = std::fs::read_to_string(&wg_path).unwrap();
+
+        let mnemonic = Mnemonic::parse(TEST_MNEMONIC).unwrap();
+        let ssh_seed = derive_key_material(
+            &mnemonic,
+            &SecretString::new(String::new()),
+            Derivation::Legacy,
+            derivation::SSH_ED25519,
+            None,
+        );
+        let legacy = wireguard::Keypair::from_key(&ssh_seed);
+        assert_ne!(wg_key.trim_end(), legacy.private_key().as_str());
+    }
+
+    #[test]
+    fn passphrase_confirm_reprompts() {
+        let labels = PromptLabels {
+            seed_passphrase_prompt_label: "Enter passphrase (empty for no passphrase):".to_string(),
+            seed_passphrase_confirm_label: "Confirmation passphrase:".to_string(),
+        };
+        let mut entries = ["hunter2", "hunter3", "hunter2", "hunter2"].into_iter();
+        let mut prompts = 0;
+        let passphrase = prompt_passphrase(
+            |_| {
+                prompts += 1;
+                Ok(entries.next().unwrap().to_string())
+            },
+            &labels,
+            true,
+        )
+        .unwrap();
+        assert_eq!(passphrase.expose_secret(), "hunter2");
+        assert_eq!(prompts, 4);
+
+        let passphrase = prompt_passphrase(|_| Ok("hunter3".to_string()), &labels, false).unwrap();
+        assert_eq!(passphrase.expose_secret(), "hunter3");
+    }
+
+    #[test]
+    fn custom_prompt_labels() {
+        let cli = Cli::try_parse_from([
+            "bip39-keygen",
+            "ssh",
+            "-t",
+            "ed25519",
+            "--seed-passphrase-prompt-label",
+            "Enter ACME seed passphrase:",
+        ])
+        .unwrap();
+        let Commands::SSH { seed_args, .. } = cli.commands else {
+            panic!("expected the ssh command");
+        };
+
+        let mut labels = Vec::new();
+        prompt_passphrase(
+            |label| {
+                labels.push(label.to_string());
+                Ok("hunter2".to_string())
+            },
+            &seed_args.prompt_labels,
+            true,
+        )
+        .unwrap();
+        assert_eq!(labels, [
+            "Enter ACME seed passphrase:",
+            "Confirmation passphrase:"
+        ]);
+    }
+
+    #[test]
+    fn review_before_writing() {
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+        let fingerprint =
+            ssh_key::public::KeyData::Ed25519(keypair.public).fingerprint(Default::default());
+        let seckey_path = path::absolute("keys/id_ed25519").unwrap();
+        let review = SshReview {
+            key_type: KeyType::Ed25519,
+            seckey_path: &seckey_path,
+            pubkey_path: &seckey_path.with_extension("pub"),
+            comment: "me",
+            encrypted: true,
+            derivation: Derivation::Hkdf,
+            domain: None,
+            fingerprint,
+        };
+
+        let summary = review.to_string();
+        assert!(summary.contains(&format!("Private key: {}", seckey_path.display())));
+        assert!(summary.contains(&fingerprint.to_string()));
+        assert!(summary.contains("bip39-keygen/v1/ssh/ed25519"));
+        assert!(summary.contains("Encrypted:   yes"));
+
+        let mut asked = false;
+        confirm_review(&review, || {
+            asked = true;
+            Ok(true)
+        })
+        .unwrap();
+        assert!(asked);
+        assert!(confirm_review(&review, || Ok(false)).is_err());
+    }
+
+    #[test]
+    fn deterministic_comment() {
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+        let key_data = ssh_key::public::KeyData::Ed25519(keypair.public);
+
+        let comment = key_comment(&key_data);
+        assert_eq!(comment, format!("key-{}", &fingerprint_hex(&key_data)[..8]));
+        assert_eq!(comment.len(), 12);
+        assert!(!comment.contains(&whoami::username()));
+
+        let other = ssh_key::private::Ed25519Keypair::from_seed(&[8; 32]);
+        assert_ne!(
+            comment,
+            key_comment(&ssh_key::public::KeyData::Ed25519(other.public))
+        );
+    }
+
+    #[test]
+    fn stage_then_promote() {
+        let testdir = tempfile::tempdir().unwrap();
+        let stage = testdir.path().join("stage");
+        let target = testdir.path().join("target");
+        std::fs::create_dir_all(&stage).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        for name in ["id_ed25519", "id_ed25519.pub"] {
+            std::fs::write(stage.join(name), format!("new {name}")).unwrap();
+            std::fs::write(target.join(name), format!("old {name}")).unwrap();
+        }
+
+        let mut tx = Transaction::new(tempfile::tempdir().unwrap());
+        let promoted = promote(&mut tx, &stage, &target).unwrap();
+        tx.commit();
+
+        assert_eq!(promoted, [
+            target.join("id_ed25519"),
+            target.join("id_ed25519.pub")
+        ]);
+        for name in ["id_ed25519", "id_ed25519.pub"] {
+            assert_eq!(
+                std::fs::read_to_string(target.join(name)).unwrap(),
+                format!("new {name}")
             );
-            Ok(Zeroizing::new(Mnemonic::from_str(
-                mnemonic.expose_secret(),
-            )?))
+            assert!(!stage.join(name).exists());
+        }
+    }
+
+    #[test]
+    fn failed_promote_restores_target() {
+        let testdir = tempfile::tempdir().unwrap();
+        let stage = testdir.path().join("stage");
+        let target = testdir.path().join("target");
+        std::fs::create_dir_all(&stage).unwrap();
+        // The second file cannot replace a directory.
+        std::fs::create_dir_all(target.join("id_ed25519.pub")).unwrap();
+        std::fs::write(target.join("id_ed25519"), "old").unwrap();
+        std::fs::write(stage.join("id_ed25519"), "new").unwrap();
+        std::fs::write(stage.join("id_ed25519.pub"), "new").unwrap();
+
+        let mut tx = Transaction::new(tempfile::tempdir().unwrap());
+        assert!(promote(&mut tx, &stage, &target).is_err());
+        drop(tx);
+
+        assert_eq!(
+            std::fs::read_to_string(target.join("id_ed25519")).unwrap(),
+            "old"
+        );
+        assert_eq!(
+            std::fs::read_to_string(stage.join("id_ed25519")).unwrap(),
+            "new"
+        );
+        assert!(target.join("id_ed25519.pub").is_dir());
+    }
+
+    #[test]
+    fn entropy_out() {
+        let testdir = tempfile::tempdir().unwrap();
+        let entropy_path = testdir.path().join("entropy");
+        let mnemonic = Mnemonic::generate_in(Language::English, 24).unwrap();
+
+        let mut tx = Transaction::without_temp_dir();
+        write_entropy(&mut tx, &entropy_path, &mnemonic, false).unwrap();
+        tx.commit();
+
+        let hex = std::fs::read_to_string(&entropy_path).unwrap();
+        let entropy = entropy::from_hex(&hex).unwrap();
+        assert_eq!(Mnemonic::from_entropy(&entropy).unwrap(), mnemonic);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = std::fs::metadata(&entropy_path).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn distinct_targets() {
+        let seckey_path = Path::new("/home/me/.ssh/id.pub");
+        let pubkey_path = seckey_path.with_extension("pub");
+        let err = check_distinct_targets(&[
+            ("the private key", seckey_path),
+            ("the public key", &pubkey_path),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the private key and the public key would both be written to /home/me/.ssh/id.pub"
+        );
+
+        check_distinct_targets(&[
+            ("the private key", Path::new("/home/me/.ssh/id")),
+            ("the public key", Path::new("/home/me/.ssh/id.pub")),
+            (
+                "--write-fingerprint",
+                Path::new("/home/me/.ssh/id.fingerprint"),
+            ),
+        ])
+        .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn distinct_targets_through_symlinks() {
+        let testdir = tempfile::tempdir().unwrap();
+        let ssh_dir = testdir.path().join("ssh");
+        std::fs::create_dir(&ssh_dir).unwrap();
+        std::os::unix::fs::symlink(&ssh_dir, testdir.path().join("link")).unwrap();
+
+        let err = check_distinct_targets(&[
+            ("the public key", &ssh_dir.join("id.pub")),
+            ("--metadata-sidecar", &testdir.path().join("link/id.pub")),
+        ])
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("the public key and --metadata-sidecar would both be written to")
+        );
+
+        // `..` is resolved in the missing directories as well.
+        let err = check_distinct_targets(&[
+            ("the private key", &ssh_dir.join("new/id")),
+            ("--write-fingerprint", &ssh_dir.join("new/other/../id")),
+        ])
+        .unwrap_err();
+        let resolved = ssh_dir.canonicalize().unwrap().join("new/id");
+        assert!(err.to_string().ends_with(&resolved.display().to_string()));
+    }
+
+    #[test]
+    fn fingerprint_file() {
+        let testdir = tempfile::tempdir().unwrap();
+        let fingerprint_path = testdir.path().join("id_ed25519.fingerprint");
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+        let public_key =
+            ssh_key::PublicKey::new(ssh_key::public::KeyData::Ed25519(keypair.public), "");
+
+        let mut tx = Transaction::without_temp_dir();
+        write_fingerprint_file(&mut tx, &fingerprint_path, &public_key, false).unwrap();
+        tx.commit();
+
+        let fingerprint = std::fs::read_to_string(&fingerprint_path).unwrap();
+        assert_eq!(
+            fingerprint,
+            format!("{}\n", public_key.fingerprint(ssh_key::HashAlg::Sha256))
+        );
+        assert!(fingerprint.starts_with("SHA256:"));
+    }
+
+    #[test]
+    fn all_key_types() {
+        let cli = Cli::try_parse_from(["bip39-keygen", "ssh", "-t", "all"]).unwrap();
+        let Commands::SSH { key_type, .. } = cli.commands else {
+            panic!("expected the ssh command");
+        };
+        let key_types = key_type.key_types();
+        assert_eq!(key_types, KeyType::value_variants());
+
+        let mut paths: Vec<PathBuf> = key_types
+            .iter()
+            .flat_map(|&key_type| {
+                let seckey_path = ssh_default_output_path(key_type);
+                [seckey_path.with_extension("pub"), seckey_path]
+            })
+            .collect();
+        assert!(paths.contains(&ssh_default_output_path(KeyType::Ed25519)));
+        let count = paths.len();
+        paths.sort();
+        paths.dedup();
+        assert_eq!(paths.len(), count);
+    }
+
+    #[test]
+    fn configured_prompt_default_path() {
+        let cli = Cli::try_parse_from([
+            "bip39-keygen",
+            "ssh",
+            "-t",
+            "ed25519",
+            "--prompt-default-path",
+            "/vault/ssh/id_ed25519",
+        ])
+        .unwrap();
+        let Commands::SSH {
+            output_path,
+            prompt_default_path,
+            ..
+        } = cli.commands
+        else {
+            panic!("expected the ssh command");
+        };
+        assert_eq!(output_path, None);
+
+        let path = prompt_output_path_with(
+            output_path,
+            prompt_default_path.unwrap(),
+            false,
+            |default| {
+                // Accepts the offered default, as pressing enter does.
+                Ok(default.unwrap().to_string())
+            },
+        )
+        .unwrap();
+        assert_eq!(path, Path::new("/vault/ssh/id_ed25519"));
+
+        let path = prompt_output_path_with(
+            Some(PathBuf::from("/tmp/id_ed25519")),
+            PathBuf::from("/vault/ssh/id_ed25519"),
+            false,
+            |_| panic!("the output path was given"),
+        )
+        .unwrap();
+        assert_eq!(path, Path::new("/tmp/id_ed25519"));
+    }
+
+    const TEST_MNEMONIC: &str =
+        "legal winner thank year wave sausage worth useful legal winner thank yellow";
+
+    /// Runs `ssh` with the test mnemonic and `args`, saving the key to `path`.
+    pub(crate) fn run_ssh(path: &Path, args: &[&str]) -> anyhow::Result<()> {
+        let mut argv = vec!["bip39-keygen", "ssh", "-t", "ed25519", "-m", TEST_MNEMONIC];
+        argv.extend(["-f", path.to_str().unwrap(), "--yes"]);
+        argv.extend(args);
+        run(Cli::try_parse_from(argv)?)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn minisign_secret_key_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("minisign.key");
+        run(Cli::try_parse_from([
+            "bip39-keygen",
+            "minisign",
+            "-m",
+            TEST_MNEMONIC,
+            "--no-passphrase",
+            "-f",
+            seckey_path.to_str().unwrap(),
+        ])
+        .unwrap())
+        .unwrap();
+        let mode = std::fs::metadata(&seckey_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unencrypted_private_key_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519");
+        run_ssh(&seckey_path, &["-p", "x", "--passphrase-use", "seed"]).unwrap();
+        let mode = std::fs::metadata(&seckey_path)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn weak_passphrase_for_encryption() {
+        let testdir = tempfile::tempdir().unwrap();
+        let fail_on_weak = "--fail-on-weak-passphrase-for-encryption";
+        let err = run_ssh(&testdir.path().join("id_weak"), &[
+            "-p",
+            "hunter2",
+            "--passphrase-use",
+            "encryption",
+            fail_on_weak,
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the encryption passphrase is weak: it has 7 characters, at least 12 are needed"
+        );
+        assert!(!testdir.path().join("id_weak").exists());
+
+        // The empty seed passphrase is not subject to the policy.
+        let plain_path = testdir.path().join("id_plain");
+        run_ssh(&plain_path, &["-N", fail_on_weak]).unwrap();
+        let encrypted_path = testdir.path().join("id_encrypted");
+        let passphrase = "correct horse battery staple";
+        run_ssh(&encrypted_path, &[
+            "-p",
+            passphrase,
+            "--passphrase-use",
+            "encryption",
+            fail_on_weak,
+        ])
+        .unwrap();
+
+        let plain = ssh_key::PrivateKey::read_openssh_file(&plain_path).unwrap();
+        let encrypted = ssh_key::PrivateKey::read_openssh_file(&encrypted_path).unwrap();
+        assert!(!plain.is_encrypted());
+        assert_eq!(
+            encrypted.decrypt(passphrase).unwrap().key_data(),
+            plain.key_data()
+        );
+    }
+
+    #[test]
+    fn totp_rejects_derivation() {
+        let err = run(Cli::try_parse_from([
+            "bip39-keygen",
+            "totp",
+            "me@example.com",
+            "-m",
+            TEST_MNEMONIC,
+            "-N",
+            "--derivation",
+            "hkdf",
+        ])
+        .unwrap())
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "TOTP secrets are always derived with their own HKDF, --derivation does not apply"
+        );
+    }
+
+    #[test]
+    fn several_passphrases_only_derive_the_seed() {
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519");
+
+        run_ssh(&seckey_path, &["-p", "x", "-p", "y"]).unwrap();
+        let secret_key = ssh_key::PrivateKey::read_openssh_file(&seckey_path).unwrap();
+        assert!(!secret_key.is_encrypted());
+
+        let err = run_ssh(&testdir.path().join("id_other"), &[
+            "-p",
+            "x",
+            "-p",
+            "y",
+            "--passphrase-use",
+            "both",
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "several passphrases cannot encrypt the private key, pass --passphrase-use seed"
+        );
+    }
+
+    #[test]
+    fn encrypted_ssh_key_for_other_commands() {
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519");
+        let child_path = testdir.path().join("id_child");
+        let passphrase = "correct horse battery staple";
+        run_ssh(&seckey_path, &["-p", passphrase]).unwrap();
+        let key_path = seckey_path.to_str().unwrap();
+
+        run(Cli::try_parse_from([
+            "bip39-keygen",
+            "set-comment",
+            key_path,
+            "new",
+            "--key-passphrase",
+            passphrase,
+        ])
+        .unwrap())
+        .unwrap();
+        let secret_key = ssh_key::PrivateKey::read_openssh_file(&seckey_path).unwrap();
+        assert!(secret_key.is_encrypted());
+        assert_eq!(secret_key.decrypt(passphrase).unwrap().comment(), "new");
+
+        run(Cli::try_parse_from([
+            "bip39-keygen",
+            "derive-child",
+            "-k",
+            key_path,
+            "-i",
+            "0",
+            "-f",
+            child_path.to_str().unwrap(),
+            "--key-passphrase",
+            passphrase,
+        ])
+        .unwrap())
+        .unwrap();
+        let child = ssh_key::PrivateKey::read_openssh_file(&child_path).unwrap();
+        assert!(child.is_encrypted());
+        assert_eq!(child.decrypt(passphrase).unwrap().comment(), "new");
+    }
+
+    #[test]
+    fn canonicalized_output_path() {
+        let path = prompt_output_path_with(
+            Some(PathBuf::from("a/../b")),
+            PathBuf::from("id_ed25519"),
+            true,
+            |_| panic!("the output path was given"),
+        )
+        .unwrap();
+        assert_eq!(path, path::absolute("b").unwrap());
+
+        assert_eq!(
+            normalize_lexically(Path::new("/keys/./a/../b/id_ed25519")),
+            Path::new("/keys/b/id_ed25519")
+        );
+        // The root has no parent.
+        assert_eq!(
+            normalize_lexically(Path::new("/../id_ed25519")),
+            Path::new("/id_ed25519")
+        );
+    }
+
+    #[test]
+    fn encrypted_private_key() {
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519");
+        let pubkey_path = testdir.path().join("id_ed25519.pub");
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+
+        let (seed, encryption) = split_passphrase(
+            PassphraseUse::Encryption,
+            SecretString::new("correct horse battery staple".to_string()),
+        );
+        assert_eq!(seed.expose_secret(), "");
+        let mut tx = Transaction::without_temp_dir();
+        let public_key = write_ssh_keypair(
+            &mut tx,
+            &seckey_path,
+            &pubkey_path,
+            keypair.clone(),
+            "me".to_string(),
+            None,
+            encryption.as_ref(),
+            false,
+        )
+        .unwrap();
+        tx.commit();
+
+        let secret_key = ssh_key::PrivateKey::read_openssh_file(&seckey_path).unwrap();
+        assert!(secret_key.is_encrypted());
+        assert_eq!(secret_key.public_key().key_data(), public_key.key_data());
+        assert!(secret_key.decrypt("wrong").is_err());
+        let decrypted = secret_key.decrypt("correct horse battery staple").unwrap();
+        assert_eq!(decrypted.key_data().ed25519(), Some(&keypair));
+
+        // Like `ssh-keygen -N ""`, an empty passphrase does not encrypt.
+        let (_, encryption) =
+            split_passphrase(PassphraseUse::Both, SecretString::new(String::new()));
+        assert!(encryption.is_none());
+        let (seed, encryption) = split_passphrase(
+            PassphraseUse::Seed,
+            SecretString::new("hunter2".to_string()),
+        );
+        assert_eq!(seed.expose_secret(), "hunter2");
+        assert!(encryption.is_none());
+    }
+
+    #[test]
+    fn separate_pub_comment() {
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519");
+        let pubkey_path = testdir.path().join("id_ed25519.pub");
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+
+        let mut tx = Transaction::without_temp_dir();
+        let public_key = write_ssh_keypair(
+            &mut tx,
+            &seckey_path,
+            &pubkey_path,
+            keypair,
+            "me@laptop".to_string(),
+            Some(""),
+            None,
+            false,
+        )
+        .unwrap();
+        tx.commit();
+        assert_eq!(public_key.comment(), "");
+
+        let secret_key = ssh_key::PrivateKey::read_openssh_file(&seckey_path).unwrap();
+        assert_eq!(secret_key.comment(), "me@laptop");
+        let public_key = ssh_key::PublicKey::read_openssh_file(&pubkey_path).unwrap();
+        assert_eq!(public_key.comment(), "");
+        assert_eq!(public_key.key_data(), secret_key.public_key().key_data());
+    }
+
+    #[test]
+    fn split_combined_file() {
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519");
+        let pubkey_path = testdir.path().join("id_ed25519.pub");
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+
+        let mut tx = Transaction::without_temp_dir();
+        write_ssh_keypair(
+            &mut tx,
+            &seckey_path,
+            &pubkey_path,
+            keypair,
+            "me".to_string(),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        tx.commit();
+        let read = |path: &Path| std::fs::read_to_string(path).unwrap();
+        let combined = combined::Combined {
+            private_key: Zeroizing::new(read(&seckey_path)),
+            public_key: read(&pubkey_path),
+            metadata: "{}\n".to_string(),
+        };
+
+        let combined = combined::Combined::parse(&combined.encode()).unwrap();
+        let split_path = testdir.path().join("split");
+        let paths = [
+            split_path.clone(),
+            split_path.with_extension("pub"),
+            split_path.with_extension("meta.json"),
+        ];
+        let mut tx = Transaction::without_temp_dir();
+        split_combined(&mut tx, &combined, &paths).unwrap();
+        tx.commit();
+
+        assert_eq!(read(&paths[0]), read(&seckey_path));
+        assert_eq!(read(&paths[1]), read(&pubkey_path));
+        assert_eq!(read(&paths[2]), "{}\n");
+    }
+
+    #[test]
+    fn combined_public_key_is_the_pub_file() {
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519");
+        let combined_path = testdir.path().join("id_ed25519.combined");
+        run_ssh(&seckey_path, &["-N"]).unwrap();
+        run(Cli::try_parse_from([
+            "bip39-keygen",
+            "ssh",
+            "-t",
+            "ed25519",
+            "-m",
+            TEST_MNEMONIC,
+            "--yes",
+            "-N",
+            "--combined",
+            combined_path.to_str().unwrap(),
+        ])
+        .unwrap())
+        .unwrap();
+
+        let combined =
+            combined::Combined::parse(&std::fs::read_to_string(&combined_path).unwrap()).unwrap();
+        let pub_file = std::fs::read_to_string(seckey_path.with_extension("pub")).unwrap();
+        assert!(pub_file.ends_with('\n'));
+        assert_eq!(combined.public_key, pub_file);
+    }
+
+    #[test]
+    fn base64_openssh_round_trip() {
+        let secret_key = ssh_key::PrivateKey::new(
+            ssh_key::private::KeypairData::Ed25519(ssh_key::private::Ed25519Keypair::from_seed(
+                &[7; 32],
+            )),
+            "me",
+        )
+        .unwrap();
+
+        let encoded = encode_base64_openssh(&secret_key, None).unwrap();
+        assert_eq!(encoded.lines().count(), 1);
+        assert_eq!(
+            decode_base64_openssh(&format!("{encoded}\n")).unwrap(),
+            secret_key
+        );
+        assert!(decode_base64_openssh("not base64!").is_err());
+    }
+
+    #[test]
+    fn set_comment_keeps_key() {
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519");
+        let pubkey_path = testdir.path().join("id_ed25519.pub");
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+
+        let mut tx = Transaction::without_temp_dir();
+        write_ssh_keypair(
+            &mut tx,
+            &seckey_path,
+            &pubkey_path,
+            keypair.clone(),
+            "old".to_string(),
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+        tx.commit();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(&seckey_path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        }
+
+        let mut tx = Transaction::new(tempfile::tempdir().unwrap());
+        set_comment(
+            &mut tx,
+            &seckey_path,
+            &pubkey_path,
+            "new".to_string(),
+            false,
+            || panic!("the key is not encrypted"),
+        )
+        .unwrap();
+        tx.commit();
+
+        let secret_key = ssh_key::PrivateKey::read_openssh_file(&seckey_path).unwrap();
+        let public_key = ssh_key::PublicKey::read_openssh_file(&pubkey_path).unwrap();
+        assert_eq!(secret_key.comment(), "new");
+        assert_eq!(public_key.comment(), "new");
+        assert_eq!(
+            secret_key.key_data(),
+            &ssh_key::private::KeypairData::Ed25519(keypair.clone())
+        );
+        assert_eq!(
+            public_key.key_data(),
+            &ssh_key::public::KeyData::Ed25519(keypair.public)
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = std::fs::metadata(&seckey_path)
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o640);
         }
-        "Generate new mnemonic" => loop {
-            let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+    }
+
+    #[test]
+    fn set_comment_keeps_encryption() {
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519");
+        let pubkey_path = testdir.path().join("id_ed25519.pub");
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+        let passphrase = SecretString::new("correct horse battery staple".to_string());
+
+        let mut tx = Transaction::without_temp_dir();
+        write_ssh_keypair(
+            &mut tx,
+            &seckey_path,
+            &pubkey_path,
+            keypair.clone(),
+            "old".to_string(),
+            None,
+            Some(&passphrase),
+            false,
+        )
+        .unwrap();
+        tx.commit();
+        let old = ssh_key::PrivateKey::read_openssh_file(&seckey_path).unwrap();
+
+        let mut tx = Transaction::new(tempfile::tempdir().unwrap());
+        let err = set_comment(
+            &mut tx,
+            &seckey_path,
+            &pubkey_path,
+            "new".to_string(),
+            false,
+            || Ok(SecretString::new("wrong".to_string())),
+        )
+        .unwrap_err();
+        assert!(err.to_string().starts_with("failed to decrypt"));
+        drop(tx);
+
+        let mut tx = Transaction::new(tempfile::tempdir().unwrap());
+        set_comment(
+            &mut tx,
+            &seckey_path,
+            &pubkey_path,
+            "new".to_string(),
+            false,
+            || Ok(passphrase.clone()),
+        )
+        .unwrap();
+        tx.commit();
+
+        let secret_key = ssh_key::PrivateKey::read_openssh_file(&seckey_path).unwrap();
+        assert!(secret_key.is_encrypted());
+        assert_eq!(secret_key.cipher(), old.cipher());
+        assert_eq!(secret_key.kdf().algorithm(), old.kdf().algorithm());
+        let decrypted = secret_key.decrypt(passphrase.expose_secret()).unwrap();
+        assert_eq!(decrypted.comment(), "new");
+        assert_eq!(
+            decrypted.key_data(),
+            &ssh_key::private::KeypairData::Ed25519(keypair)
+        );
+    }
 
-            println!("Your new 12-word mnemonic is:");
-            println!("  {}", mnemonic);
-            println!("Please write it down and store it in a safe place");
+    #[test]
+    fn verify_after_write() {
+        let testdir = tempfile::tempdir().unwrap();
+        for (format, raw_secret_key) in [
+            (KeyFormat::Openssh, RawSecretKey::Keypair),
+            (KeyFormat::Base64Openssh, RawSecretKey::Keypair),
+            (KeyFormat::Raw, RawSecretKey::Keypair),
+            (KeyFormat::Raw, RawSecretKey::Seed),
+        ] {
+            let seckey_path = testdir
+                .path()
+                .join(format!("{format:?}-{raw_secret_key:?}"));
+            let pubkey_path = seckey_path.with_extension("pub");
+            let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
 
-            let ans = inquire::Confirm::new("Do you want to regenerate a new mnemonic?")
-                .with_default(false)
-                .prompt()?;
-            if !ans {
-                break Ok(Zeroizing::new(mnemonic));
+            let mut tx = Transaction::without_temp_dir();
+            let public_key = match format {
+                KeyFormat::Openssh => write_ssh_keypair(
+                    &mut tx,
+                    &seckey_path,
+                    &pubkey_path,
+                    keypair,
+                    "me".to_string(),
+                    None,
+                    None,
+                    false,
+                ),
+                KeyFormat::Raw => write_raw_keypair(
+                    &mut tx,
+                    &seckey_path,
+                    &pubkey_path,
+                    keypair,
+                    raw_secret_key,
+                    "me".to_string(),
+                ),
+                KeyFormat::Base64Openssh => write_base64_keypair(
+                    &mut tx,
+                    &seckey_path,
+                    &pubkey_path,
+                    keypair,
+                    "me".to_string(),
+                    None,
+                    None,
+                    false,
+                ),
             }
-        },
-        _ => unreachable!(),
+            .unwrap();
+            tx.commit();
+
+            verify_written_keys(format, &seckey_path, &pubkey_path, public_key.key_data()).unwrap();
+        }
+
+        let other = ssh_key::public::KeyData::Ed25519(
+            ssh_key::private::Ed25519Keypair::from_seed(&[8; 32]).public,
+        );
+        let seckey_path = testdir.path().join("Openssh-Keypair");
+        let err = verify_written_keys(
+            KeyFormat::Openssh,
+            &seckey_path,
+            &seckey_path.with_extension("pub"),
+            &other,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("not the written"));
+    }
+
+    #[test]
+    fn raw_keypair() {
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519.sk");
+        let pubkey_path = testdir.path().join("id_ed25519.pk");
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+
+        let mut tx = Transaction::without_temp_dir();
+        write_raw_keypair(
+            &mut tx,
+            &seckey_path,
+            &pubkey_path,
+            keypair.clone(),
+            RawSecretKey::Keypair,
+            "me".to_string(),
+        )
+        .unwrap();
+        tx.commit();
+
+        let openssh = ssh_key::PrivateKey::new(
+            ssh_key::private::KeypairData::Ed25519(keypair.clone()),
+            "me",
+        )
+        .unwrap();
+        let openssh_public = openssh.public_key().key_data().ed25519().unwrap();
+        assert_eq!(
+            std::fs::read(&pubkey_path).unwrap(),
+            openssh_public.as_ref()
+        );
+        assert_eq!(std::fs::read(&seckey_path).unwrap(), keypair.to_bytes());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&seckey_path)
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn assume_yes_for_generation() {
+        const MNEMONIC: &str =
+            "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        let mut generated = 0;
+        let mut prompted = 0;
+
+        let mnemonic = generate_mnemonic(
+            || {
+                generated += 1;
+                Ok(Mnemonic::parse(MNEMONIC)?)
+            },
+            || {
+                prompted += 1;
+                Ok(true)
+            },
+            true,
+        )
+        .unwrap();
+
+        assert_eq!(mnemonic.to_string(), MNEMONIC);
+        assert_eq!((generated, prompted), (1, 0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mnemonic_from_fifo() {
+        use std::io::Write;
+        use std::os::unix::ffi::OsStrExt;
+
+        let testdir = tempfile::tempdir().unwrap();
+        let fifo_path = testdir.path().join("mnemonic");
+        let c_path = std::ffi::CString::new(fifo_path.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+        let writer = std::thread::spawn({
+            let fifo_path = fifo_path.clone();
+            move || {
+                // Opening blocks until the reader opens the other end.
+                let mut fifo = std::fs::OpenOptions::new()
+                    .write(true)
+                    .open(fifo_path)
+                    .unwrap();
+                fifo.write_all(b"legal winner thank year wave sausage ")
+                    .unwrap();
+                std::thread::sleep(Duration::from_millis(100));
+                fifo.write_all(b"worth useful legal winner thank yellow\n")
+                    .unwrap();
+            }
+        });
+        let mnemonic = read_mnemonic_file(&fifo_path).unwrap();
+        writer.join().unwrap();
+        assert_eq!(
+            mnemonic.expose_secret(),
+            "legal winner thank year wave sausage worth useful legal winner thank yellow"
+        );
+        mnemonic::parse(mnemonic.expose_secret(), None).unwrap();
+    }
+
+    #[test]
+    fn mnemonic_reprompted_after_typo() {
+        let mut entries = [
+            "legal winner thank year wave sausage wort useful legal winner thank yellow",
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        ]
+        .into_iter();
+        let mnemonic = input_mnemonic(
+            || Ok(SecretString::new(entries.next().unwrap().to_string())),
+            None,
+            3,
+        )
+        .unwrap();
+        assert_eq!(
+            mnemonic.to_string(),
+            "legal winner thank year wave sausage worth useful legal winner thank yellow"
+        );
+        assert_eq!(entries.next(), None);
+
+        let mut entries = ["legal", "winner"].into_iter();
+        let err = input_mnemonic(
+            || Ok(SecretString::new(entries.next().unwrap().to_string())),
+            None,
+            2,
+        )
+        .unwrap_err();
+        assert!(err.is::<mnemonic::ParseError>());
+    }
+
+    #[test]
+    fn application_in_comment() {
+        let application: Application = "ssh:github.com".parse().unwrap();
+        let comment = with_application("me@laptop".to_string(), Some(&application));
+        assert_eq!(comment, "me@laptop app=ssh:github.com");
+        let field = comment
+            .split(' ')
+            .find_map(|field| field.strip_prefix("app="));
+        assert_eq!(field, Some("ssh:github.com"));
+
+        assert!("github.com".parse::<Application>().is_err());
+        assert!("1ssh:github.com".parse::<Application>().is_err());
+        assert!("ssh:".parse::<Application>().is_err());
+        assert!("ssh:git hub".parse::<Application>().is_err());
+    }
+
+    #[test]
+    fn mnemonic_word_by_word() {
+        let mut entries = [
+            "lega", "winn", "than", "year", "wav", "xyz", "sausa", "wort", "usef", "legal", "winn",
+            "thank", "yell",
+        ]
+        .into_iter();
+        let mut asked = Vec::new();
+        let mnemonic = input_words(
+            |i| {
+                asked.push(i);
+                Ok(entries.next().unwrap().to_string())
+            },
+            Language::English,
+            12,
+        )
+        .unwrap();
+        assert_eq!(
+            mnemonic.to_string(),
+            "legal winner thank year wave sausage worth useful legal winner thank yellow"
+        );
+        // The sixth word is asked for again after the typo.
+        assert_eq!(asked[5..7], [6, 6]);
+        assert_eq!(asked.len(), 13);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn strict_loose_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let testdir = tempfile::tempdir().unwrap();
+        let seckey_path = testdir.path().join("id_ed25519");
+        assert_eq!(mode::loose_dir_warning(&seckey_path), None);
+
+        std::fs::set_permissions(testdir.path(), std::fs::Permissions::from_mode(0o777)).unwrap();
+        let err = warn(true, mode::loose_dir_warning(&seckey_path)).unwrap_err();
+        assert!(err.to_string().contains("is writable by other users"));
+        warn(false, mode::loose_dir_warning(&seckey_path)).unwrap();
+    }
+
+    #[test]
+    fn github_api_payload() {
+        let keypair = ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]);
+        let public_key =
+            ssh_key::PublicKey::new(ssh_key::public::KeyData::Ed25519(keypair.public), "me");
+
+        let payload: serde_json::Value =
+            serde_json::from_str(&api_payload(ApiProvider::Github, &public_key).unwrap()).unwrap();
+        assert_eq!(payload["key"], public_key.to_openssh().unwrap());
+        assert_eq!(payload["title"], "me");
+    }
+
+    #[test]
+    fn shell_env_exports() {
+        let vars = [
+            ("SSH_KEY_PATH", "/home/me/my keys/id_ed25519"),
+            ("SSH_KEY_FINGERPRINT", "SHA256:it's"),
+        ];
+        let bash = shell_env(ShellEnv::Bash, &vars);
+        assert_eq!(
+            bash,
+            "export SSH_KEY_PATH='/home/me/my keys/id_ed25519'\n\
+             export SSH_KEY_FINGERPRINT='SHA256:it'\\''s'\n"
+        );
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "{bash}printf '%s|%s' \"$SSH_KEY_PATH\" \"$SSH_KEY_FINGERPRINT\""
+            ))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "/home/me/my keys/id_ed25519|SHA256:it's"
+        );
+
+        assert_eq!(
+            shell_env(ShellEnv::Fish, &vars),
+            "set -gx SSH_KEY_PATH '/home/me/my keys/id_ed25519'\n\
+             set -gx SSH_KEY_FINGERPRINT 'SHA256:it\\'s'\n"
+        );
+    }
+
+    #[test]
+    fn github_preset() {
+        assert_eq!(
+            Preset::Github.comment(),
+            format!("{} (github)", ssh_default_comment())
+        );
+        assert_eq!(
+            Preset::Github.upload_hint(Path::new("/home/me/.ssh/id_ed25519.pub")),
+            "Add the contents of /home/me/.ssh/id_ed25519.pub at https://github.com/settings/ssh/new"
+        );
+    }
+
+    #[test]
+    fn next_steps_reference_public_key() {
+        let pubkey_path = Path::new("/home/me/.ssh/id_ed25519.pub");
+        assert_eq!(next_steps(None, pubkey_path, "me@laptop", None), [
+            "ssh-copy-id -i /home/me/.ssh/id_ed25519.pub <user>@<host>"
+        ]);
+
+        let target: probe::Target = "git@example.com:2222".parse().unwrap();
+        assert_eq!(
+            next_steps(
+                Some(Preset::Github),
+                Path::new("/home/me/my keys/id_ed25519.pub"),
+                "me@laptop (github)",
+                Some(&target)
+            ),
+            [
+                "gh ssh-key add '/home/me/my keys/id_ed25519.pub' --title 'me@laptop (github)'",
+                "ssh-copy-id -i '/home/me/my keys/id_ed25519.pub' -p 2222 git@example.com",
+            ]
+        );
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn ssh_keygen_compat_report() {
+        let keypair = ssh_key::private::KeypairData::Ed25519(
+            ssh_key::private::Ed25519Keypair::from_seed(&[7; 32]),
+        );
+        let public_key =
+            ssh_key::PublicKey::new(ssh_key::public::KeyData::try_from(&keypair).unwrap(), "me");
+
+        let report = ssh_keygen_report(
+            Path::new("/home/me/.ssh/id_ed25519"),
+            Path::new("/home/me/.ssh/id_ed25519.pub"),
+            &public_key,
+        );
+        let mut lines = report.lines();
+        assert_eq!(
+            lines.next(),
+            Some("Your identification has been saved in /home/me/.ssh/id_ed25519")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("Your public key has been saved in /home/me/.ssh/id_ed25519.pub")
+        );
+        assert_eq!(lines.next(), Some("The key fingerprint is:"));
+        assert_eq!(
+            lines.next(),
+            Some(format!("{} me", public_key.fingerprint(Default::default())).as_str())
+        );
+        assert_eq!(lines.next(), Some("The key's randomart image is:"));
+        assert_eq!(lines.next(), Some("+--[ED25519 256]--+"));
     }
 }
@@ -0,0 +1,194 @@
+use bip39::Mnemonic;
+use secrecy::ExposeSecret;
+use secrecy::SecretString;
+use serde::Serialize;
+use ssh_key::HashAlg;
+use ssh_key::PublicKey;
+
+use crate::derivation::Derivation;
+use crate::derivation::Domain;
+use crate::mnemonic::WordlistLanguage;
+use crate::passphrase::Combine;
+use crate::KeyType;
+
+/// The non-secret description of a generated key, written next to it as a
+/// `.meta.json` sidecar.
+#[derive(Debug, Serialize)]
+pub(crate) struct KeyMetadata {
+    pub(crate) algorithm: String,
+    pub(crate) comment: String,
+    pub(crate) fingerprint: String,
+    pub(crate) derivation: Derivation,
+    /// The HKDF label, only present with [`Derivation::Hkdf`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) derivation_label: Option<String>,
+}
+
+impl KeyMetadata {
+    pub(crate) fn new(public_key: &PublicKey, derivation: Derivation, domain: Domain) -> Self {
+        Self {
+            algorithm: public_key.algorithm().to_string(),
+            comment: public_key.comment().to_string(),
+            fingerprint: public_key.fingerprint(HashAlg::Sha256).to_string(),
+            derivation,
+            derivation_label: (derivation == Derivation::Hkdf).then(|| domain.label()),
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> serde_json::Result<String> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        Ok(json)
+    }
+}
+
+/// Everything but the secrets needed to derive the same key again, as
+/// printed by `--print-derivation`.
+#[derive(Debug, Serialize)]
+pub(crate) struct DerivationRecord {
+    pub(crate) key_type: KeyType,
+    pub(crate) language: WordlistLanguage,
+    pub(crate) word_count: usize,
+    pub(crate) derivation: Derivation,
+    /// The HKDF label, only present with [`Derivation::Hkdf`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) derivation_label: Option<String>,
+    /// The domain given with `--domain`, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) domain: Option<String>,
+    /// Whether a passphrase was used, never its value.
+    pub(crate) passphrase: bool,
+    /// How several passphrases were combined, only present if there were.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) combine_passphrases: Option<Combine>,
+    /// Whether the comment was appended to the passphrase, as
+    /// `--salt-with-comment` does.
+    pub(crate) salt_with_comment: bool,
+}
+
+impl DerivationRecord {
+    pub(crate) fn new(
+        key_type: KeyType,
+        mnemonic: &Mnemonic,
+        passphrase: &SecretString,
+        combine_passphrases: Option<Combine>,
+        derivation: Derivation,
+        domain: Domain,
+        user_domain: Option<String>,
+    ) -> Self {
+        Self {
+            key_type,
+            language: mnemonic.language().into(),
+            word_count: mnemonic.word_count(),
+            derivation,
+            derivation_label: (derivation == Derivation::Hkdf).then(|| domain.label()),
+            domain: user_domain,
+            passphrase: !passphrase.expose_secret().is_empty(),
+            combine_passphrases,
+            salt_with_comment: false,
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> serde_json::Result<String> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        Ok(json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ssh_key::private::Ed25519Keypair;
+    use ssh_key::private::KeypairData;
+    use ssh_key::PrivateKey;
+
+    use super::*;
+    use crate::derivation;
+
+    #[test]
+    fn sidecar() {
+        let keypair = Ed25519Keypair::from_seed(&[7; 32]);
+        let secret_key = PrivateKey::new(KeypairData::Ed25519(keypair.clone()), "me").unwrap();
+
+        let json = KeyMetadata::new(
+            secret_key.public_key(),
+            Derivation::Hkdf,
+            derivation::SSH_ED25519,
+        )
+        .to_json()
+        .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(
+            value["fingerprint"],
+            secret_key
+                .public_key()
+                .fingerprint(HashAlg::Sha256)
+                .to_string()
+        );
+        assert_eq!(value["algorithm"], "ssh-ed25519");
+        assert_eq!(value["comment"], "me");
+        assert_eq!(value["derivation"], "hkdf");
+        assert_eq!(value["derivation_label"], "bip39-keygen/v1/ssh/ed25519");
+
+        let openssh = secret_key.to_openssh(Default::default()).unwrap();
+        let base64_lines = openssh.lines().filter(|l| !l.starts_with("-----"));
+        for line in base64_lines {
+            assert!(!json.contains(line));
+        }
+        assert!(!json.contains(&hex::encode(keypair.private.to_bytes())));
+    }
+
+    #[test]
+    fn sidecar_legacy_has_no_label() {
+        let keypair = Ed25519Keypair::from_seed(&[7; 32]);
+        let secret_key = PrivateKey::new(KeypairData::Ed25519(keypair), "me").unwrap();
+
+        let json = KeyMetadata::new(
+            secret_key.public_key(),
+            Derivation::Legacy,
+            derivation::SSH_ED25519,
+        )
+        .to_json()
+        .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["derivation"], "legacy");
+        assert!(value.get("derivation_label").is_none());
+    }
+
+    #[test]
+    fn derivation_record() {
+        let mnemonic = Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+        let passphrase = SecretString::new("hunter2".to_string());
+
+        let json = DerivationRecord::new(
+            KeyType::Ed25519,
+            &mnemonic,
+            &passphrase,
+            None,
+            Derivation::Hkdf,
+            derivation::SSH_ED25519,
+            Some("github".to_string()),
+        )
+        .to_json()
+        .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["key_type"], "ed25519");
+        assert_eq!(value["language"], "english");
+        assert_eq!(value["word_count"], 12);
+        assert_eq!(value["derivation"], "hkdf");
+        assert_eq!(value["derivation_label"], "bip39-keygen/v1/ssh/ed25519");
+        assert_eq!(value["domain"], "github");
+        assert_eq!(value["passphrase"], true);
+        assert!(value.get("combine_passphrases").is_none());
+        assert_eq!(value["salt_with_comment"], false);
+        assert!(!json.contains("hunter2"));
+        assert!(!json.contains("legal"));
+    }
+}
@@ -0,0 +1,119 @@
+use base64ct::Base64;
+use base64ct::Encoding;
+use blake2::digest::consts::U32;
+use blake2::digest::consts::U8;
+use blake2::Blake2b;
+use blake2::Digest;
+use ssh_key::private::Ed25519Keypair;
+use zeroize::Zeroizing;
+
+const SIG_ALG: &[u8; 2] = b"Ed";
+const CHK_ALG: &[u8; 2] = b"B2";
+/// Marks the secret key as unencrypted, as written by `minisign -G -W`.
+const KDF_NONE: &[u8; 2] = &[0, 0];
+
+/// An ed25519 key pair in the [minisign](https://jedisct1.github.io/minisign/)
+/// key file layout.
+///
+/// minisign normally picks a random key id. To keep the files reproducible
+/// from a mnemonic, the key id is the 8-byte BLAKE2b digest of the public
+/// key instead.
+pub(crate) struct KeyPair {
+    key_id: [u8; 8],
+    keypair: Ed25519Keypair,
+}
+
+impl KeyPair {
+    pub(crate) fn new(keypair: Ed25519Keypair) -> Self {
+        let key_id = Blake2b::<U8>::digest(keypair.public.as_ref()).into();
+        Self { key_id, keypair }
+    }
+
+    /// Formats the key id the way minisign displays it.
+    pub(crate) fn key_id(&self) -> String {
+        format!("{:016X}", u64::from_le_bytes(self.key_id))
+    }
+
+    /// Serializes the public key file, usually saved as `minisign.pub`.
+    pub(crate) fn to_public_key_file(&self) -> String {
+        let mut bytes = Vec::with_capacity(42);
+        bytes.extend_from_slice(SIG_ALG);
+        bytes.extend_from_slice(&self.key_id);
+        bytes.extend_from_slice(self.keypair.public.as_ref());
+
+        format!(
+            "untrusted comment: minisign public key {}\n{}\n",
+            self.key_id(),
+            Base64::encode_string(&bytes)
+        )
+    }
+
+    /// Serializes the unencrypted secret key file, usually saved as
+    /// `minisign.key`.
+    pub(crate) fn to_secret_key_file(&self) -> Zeroizing<String> {
+        let sk = Zeroizing::new(self.keypair.to_bytes());
+
+        let mut checksum = Blake2b::<U32>::new();
+        checksum.update(SIG_ALG);
+        checksum.update(self.key_id);
+        checksum.update(sk.as_ref());
+
+        let mut bytes = Zeroizing::new(Vec::with_capacity(158));
+        bytes.extend_from_slice(SIG_ALG);
+        bytes.extend_from_slice(KDF_NONE);
+        bytes.extend_from_slice(CHK_ALG);
+        // KDF salt, opslimit and memlimit are unused without encryption.
+        bytes.extend_from_slice(&[0; 32 + 8 + 8]);
+        bytes.extend_from_slice(&self.key_id);
+        bytes.extend_from_slice(sk.as_ref());
+        bytes.extend_from_slice(&checksum.finalize());
+
+        Zeroizing::new(format!(
+            "untrusted comment: minisign secret key {}\n{}\n",
+            self.key_id(),
+            Base64::encode_string(&bytes)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn keypair() -> KeyPair {
+        KeyPair::new(Ed25519Keypair::from_seed(&[7; 32]))
+    }
+
+    #[test]
+    fn public_key_verifies_secret_key_signature() {
+        let keypair = keypair();
+
+        let pk = ::minisign::PublicKeyBox::from_string(&keypair.to_public_key_file())
+            .unwrap()
+            .into_public_key()
+            .unwrap();
+        let sk = ::minisign::SecretKeyBox::from_string(&keypair.to_secret_key_file())
+            .unwrap()
+            .into_secret_key(Some(String::new()))
+            .unwrap();
+
+        let signature =
+            ::minisign::sign(Some(&pk), &sk, Cursor::new(b"hello"), None, None).unwrap();
+        ::minisign::verify(&pk, &signature, Cursor::new(b"hello"), true, false, false).unwrap();
+        assert!(
+            ::minisign::verify(&pk, &signature, Cursor::new(b"hellO"), true, false, false).is_err()
+        );
+    }
+
+    #[test]
+    fn key_id_is_deterministic() {
+        assert_eq!(keypair().key_id(), keypair().key_id());
+        assert!(keypair().to_public_key_file().starts_with(&format!(
+            "untrusted comment: minisign public key {}\n",
+            keypair().key_id()
+        )));
+    }
+}
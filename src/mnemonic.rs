@@ -0,0 +1,354 @@
+use std::fmt;
+
+use bip39::Language;
+use bip39::Mnemonic;
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// The BIP39 wordlists, as accepted on the command line.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum WordlistLanguage {
+    English,
+    SimplifiedChinese,
+    TraditionalChinese,
+    Czech,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Spanish,
+}
+
+impl From<WordlistLanguage> for Language {
+    fn from(language: WordlistLanguage) -> Self {
+        match language {
+            WordlistLanguage::English => Language::English,
+            WordlistLanguage::SimplifiedChinese => Language::SimplifiedChinese,
+            WordlistLanguage::TraditionalChinese => Language::TraditionalChinese,
+            WordlistLanguage::Czech => Language::Czech,
+            WordlistLanguage::French => Language::French,
+            WordlistLanguage::Italian => Language::Italian,
+            WordlistLanguage::Japanese => Language::Japanese,
+            WordlistLanguage::Korean => Language::Korean,
+            WordlistLanguage::Spanish => Language::Spanish,
+        }
+    }
+}
+
+impl From<Language> for WordlistLanguage {
+    fn from(language: Language) -> Self {
+        match language {
+            Language::English => WordlistLanguage::English,
+            Language::SimplifiedChinese => WordlistLanguage::SimplifiedChinese,
+            Language::TraditionalChinese => WordlistLanguage::TraditionalChinese,
+            Language::Czech => WordlistLanguage::Czech,
+            Language::French => WordlistLanguage::French,
+            Language::Italian => WordlistLanguage::Italian,
+            Language::Japanese => WordlistLanguage::Japanese,
+            Language::Korean => WordlistLanguage::Korean,
+            Language::Spanish => WordlistLanguage::Spanish,
+        }
+    }
+}
+
+impl WordlistLanguage {
+    /// Returns the name accepted on the command line, such as `simplified-chinese`.
+    pub(crate) fn name(&self) -> String {
+        self.to_possible_value()
+            .expect("no language is skipped")
+            .get_name()
+            .to_string()
+    }
+}
+
+/// The language of printed mnemonics: one wordlist, or `all` of them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum OutputLanguage {
+    One(WordlistLanguage),
+    All,
+}
+
+impl std::str::FromStr for OutputLanguage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(Self::All);
+        }
+        WordlistLanguage::from_str(s, true).map(Self::One)
+    }
+}
+
+/// Renders the entropy of `mnemonic` with every wordlist, in the order of
+/// [`Language::all`]. The mnemonics share the entropy, but as the seed is
+/// made from the words, each of them derives different keys.
+pub(crate) fn translations(mnemonic: &Mnemonic) -> Vec<Mnemonic> {
+    let entropy = zeroize::Zeroizing::new(mnemonic.to_entropy());
+    Language::all()
+        .iter()
+        .map(|&language| {
+            Mnemonic::from_entropy_in(language, &entropy)
+                .expect("the entropy of a valid mnemonic is valid")
+        })
+        .collect()
+}
+
+/// A mnemonic that could not be parsed.
+#[derive(Debug)]
+pub(crate) enum ParseError {
+    /// The word at `index`, counting from 0, is in none of the wordlists.
+    UnknownWord {
+        index: usize,
+        word: String,
+        suggestion: Option<&'static str>,
+    },
+    /// Every word is in a wordlist, but no wordlist has all of them: the
+    /// first words are in the first language, the next one only in the
+    /// second.
+    MixedLanguages(Language, Language),
+    Invalid(bip39::Error),
+}
+
+impl ParseError {
+    fn new(error: bip39::Error, s: &str, hint: Option<Language>) -> Self {
+        if let Some((first, second)) = mixed_languages(s) {
+            return Self::MixedLanguages(first, second);
+        }
+        let bip39::Error::UnknownWord(index) = error else {
+            return Self::Invalid(error);
+        };
+        let Some(word) = s.split_whitespace().nth(index) else {
+            return Self::Invalid(error);
+        };
+        let languages = match &hint {
+            Some(hint) => std::slice::from_ref(hint),
+            None => Language::all(),
+        };
+        Self::UnknownWord {
+            index,
+            word: word.to_string(),
+            suggestion: closest_word(word, languages),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownWord {
+                index,
+                word,
+                suggestion,
+            } => {
+                write!(
+                    f,
+                    "word {} \"{word}\" is not in the BIP39 wordlist",
+                    index + 1
+                )?;
+                match suggestion {
+                    Some(suggestion) => write!(f, ", did you mean \"{suggestion}\"?"),
+                    None => Ok(()),
+                }
+            }
+            Self::MixedLanguages(first, second) => write!(
+                f,
+                "words appear to be from mixed languages ({} and {})",
+                WordlistLanguage::from(*first).name(),
+                WordlistLanguage::from(*second).name()
+            ),
+            Self::Invalid(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses a mnemonic in any compiled-in language.
+///
+/// If a `hint` is given, that language is tried first and the others are
+/// only detected from the words if it fails.
+pub(crate) fn parse(s: &str, hint: Option<Language>) -> Result<Mnemonic, ParseError> {
+    parse_with(s, hint, |language, s| Mnemonic::parse_in(language, s))
+        .map_err(|error| ParseError::new(error, s, hint))
+}
+
+/// Returns two languages the words of `s` are from if each word is in some
+/// wordlist but no wordlist has all of them.
+fn mixed_languages(s: &str) -> Option<(Language, Language)> {
+    let mut candidates = Language::all().to_vec();
+    for word in s.split_whitespace() {
+        let languages: Vec<Language> = Language::all()
+            .iter()
+            .copied()
+            .filter(|language| language.find_word(word).is_some())
+            .collect();
+        let &second = languages.first()?;
+        let common: Vec<Language> = candidates
+            .iter()
+            .copied()
+            .filter(|language| languages.contains(language))
+            .collect();
+        if common.is_empty() {
+            return Some((candidates[0], second));
+        }
+        candidates = common;
+    }
+    None
+}
+
+/// Finds the word of `languages` closest to `word` by Levenshtein distance.
+fn closest_word(word: &str, languages: &[Language]) -> Option<&'static str> {
+    languages
+        .iter()
+        .flat_map(|language| language.word_list())
+        .min_by_key(|candidate| levenshtein(word, candidate))
+        .copied()
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(a != *b);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn parse_with(
+    s: &str,
+    hint: Option<Language>,
+    mut parse_in: impl FnMut(Language, &str) -> Result<Mnemonic, bip39::Error>,
+) -> Result<Mnemonic, bip39::Error> {
+    if let Some(hint) = hint {
+        if let Ok(mnemonic) = parse_in(hint, s) {
+            return Ok(mnemonic);
+        }
+    }
+    let language = Mnemonic::language_of(s)?;
+    parse_in(language, s)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn translations_share_entropy() {
+        let mnemonic = Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+
+        let translations = translations(&mnemonic);
+        assert_eq!(translations.len(), Language::all().len());
+        for (translation, &language) in translations.iter().zip(Language::all()) {
+            assert_eq!(translation.language(), language);
+            assert_eq!(translation.to_entropy(), mnemonic.to_entropy());
+            assert_eq!(
+                Mnemonic::from_entropy_in(language, &translation.to_entropy()).unwrap(),
+                *translation
+            );
+        }
+        assert_eq!(
+            "all".parse::<OutputLanguage>().unwrap(),
+            OutputLanguage::All
+        );
+        assert_eq!(
+            "simplified-chinese".parse::<OutputLanguage>().unwrap(),
+            OutputLanguage::One(WordlistLanguage::SimplifiedChinese)
+        );
+    }
+
+    const FRENCH: &str = "abaisser abaisser abaisser abaisser abaisser abaisser abaisser abaisser \
+                          abaisser abaisser abaisser abeille";
+
+    #[test]
+    fn misspelled_word_suggestion() {
+        let err = parse(
+            "legal winner thank year wave sausage wrth useful legal winner thank yellow",
+            None,
+        )
+        .unwrap_err();
+
+        let ParseError::UnknownWord {
+            index, suggestion, ..
+        } = &err
+        else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(*index, 6);
+        assert_eq!(*suggestion, Some("worth"));
+        assert_eq!(
+            err.to_string(),
+            "word 7 \"wrth\" is not in the BIP39 wordlist, did you mean \"worth\"?"
+        );
+    }
+
+    #[test]
+    fn mixed_languages_error() {
+        let err = parse(
+            "legal winner thank year wave sausage abeille useful legal winner thank yellow",
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "words appear to be from mixed languages (english and french)"
+        );
+
+        assert!(matches!(
+            parse(
+                "legal winner thank year wave sausage wrth useful legal winner thank yellow",
+                None
+            ),
+            Err(ParseError::UnknownWord { .. })
+        ));
+    }
+
+    #[test]
+    fn levenshtein_distance() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("abc", "abc"), 0);
+    }
+
+    #[test]
+    fn parse_without_hint() {
+        let mnemonic = parse(FRENCH, None).unwrap();
+        assert_eq!(mnemonic.language(), Language::French);
+    }
+
+    #[test]
+    fn parse_with_hint_tries_hint_only() {
+        let mut attempts = Vec::new();
+        let mnemonic = parse_with(FRENCH, Some(Language::French), |language, s| {
+            attempts.push(language);
+            Mnemonic::parse_in(language, s)
+        })
+        .unwrap();
+
+        assert_eq!(mnemonic.language(), Language::French);
+        assert_eq!(attempts, [Language::French]);
+    }
+
+    #[test]
+    fn parse_with_wrong_hint_falls_back() {
+        let mut attempts = Vec::new();
+        let mnemonic = parse_with(FRENCH, Some(Language::English), |language, s| {
+            attempts.push(language);
+            Mnemonic::parse_in(language, s)
+        })
+        .unwrap();
+
+        assert_eq!(mnemonic.language(), Language::French);
+        assert_eq!(attempts, [Language::English, Language::French]);
+    }
+}
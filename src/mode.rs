@@ -0,0 +1,162 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::bail;
+
+/// A `chmod`-style symbolic mode such as `u=rw,go=`.
+///
+/// Each comma-separated clause is zero or more of `ugoa` (all if none),
+/// one of `=`, `+` or `-`, then zero or more of `rwx`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SymbolicMode(Vec<Clause>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Clause {
+    /// The bits of the classes the clause applies to.
+    who: u32,
+    op: char,
+    /// The `rwx` bits as the owner's, before shifting into `who`.
+    perms: u32,
+}
+
+impl FromStr for SymbolicMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(parse_clause)
+            .collect::<anyhow::Result<_>>()
+            .map(Self)
+    }
+}
+
+fn parse_clause(clause: &str) -> anyhow::Result<Clause> {
+    let Some(op_index) = clause.find(['=', '+', '-']) else {
+        bail!("invalid mode clause \"{clause}\", expected one of =, + or -");
+    };
+    let (who, rest) = clause.split_at(op_index);
+    let mut who_bits = 0;
+    for c in who.chars() {
+        who_bits |= match c {
+            'u' => 0o700,
+            'g' => 0o070,
+            'o' => 0o007,
+            'a' => 0o777,
+            _ => bail!("invalid class '{c}' in mode clause \"{clause}\", expected u, g, o or a"),
+        };
+    }
+    let mut perms = 0;
+    for c in rest[1..].chars() {
+        perms |= match c {
+            'r' => 0o444,
+            'w' => 0o222,
+            'x' => 0o111,
+            _ => bail!("invalid permission '{c}' in mode clause \"{clause}\", expected r, w or x"),
+        };
+    }
+    Ok(Clause {
+        who: if who.is_empty() { 0o777 } else { who_bits },
+        op: rest.as_bytes()[0] as char,
+        perms,
+    })
+}
+
+/// The numeric `<uid>:<gid>` to give written files to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Owner {
+    pub(crate) uid: u32,
+    pub(crate) gid: u32,
+}
+
+impl FromStr for Owner {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((uid, gid)) = s.split_once(':') else {
+            bail!("expected <uid>:<gid>");
+        };
+        let parse = |id: &str, what: &str| {
+            if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+                bail!("invalid {what} \"{id}\", expected a number");
+            }
+            id.parse::<u32>()
+                .map_err(|e| anyhow::anyhow!("invalid {what} \"{id}\": {e}"))
+        };
+        Ok(Self {
+            uid: parse(uid, "uid")?,
+            gid: parse(gid, "gid")?,
+        })
+    }
+}
+
+impl SymbolicMode {
+    /// Applies the clauses in order to the permission bits of `mode`.
+    pub(crate) fn apply(&self, mut mode: u32) -> u32 {
+        for clause in &self.0 {
+            let perms = clause.perms & clause.who;
+            mode = match clause.op {
+                '=' => (mode & !clause.who) | perms,
+                '+' => mode | perms,
+                _ => mode & !perms,
+            };
+        }
+        mode & 0o777
+    }
+}
+
+/// Returns a warning if the directory of `path` is writable by group or
+/// others, who could then replace the key written there.
+pub(crate) fn loose_dir_warning(path: &Path) -> Option<String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = path.parent()?;
+    let mode = std::fs::metadata(dir).ok()?.permissions().mode();
+    (mode & 0o022 != 0).then(|| {
+        format!(
+            "{} is writable by other users, who could replace the key written there",
+            dir.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn apply(mode: &str, to: u32) -> u32 {
+        mode.parse::<SymbolicMode>().unwrap().apply(to)
+    }
+
+    #[test]
+    fn symbolic_modes() {
+        assert_eq!(apply("u=rw,go=", 0o644), 0o600);
+        assert_eq!(apply("u=rw,go=", 0o777), 0o600);
+        assert_eq!(apply("go-r", 0o644), 0o600);
+        assert_eq!(apply("u+x,g+r", 0o600), 0o740);
+        assert_eq!(apply("=r", 0o777), 0o444);
+        assert_eq!(apply("a=", 0o777), 0);
+    }
+
+    #[test]
+    fn owner() {
+        assert_eq!("1000:100".parse::<Owner>().unwrap(), Owner {
+            uid: 1000,
+            gid: 100
+        });
+        assert!("1000".parse::<Owner>().is_err());
+        assert!("root:root".parse::<Owner>().is_err());
+        assert!("+1:1".parse::<Owner>().is_err());
+        assert!("1:".parse::<Owner>().is_err());
+        assert!("1:4294967296".parse::<Owner>().is_err());
+    }
+
+    #[test]
+    fn invalid_modes() {
+        assert!("u=rw,".parse::<SymbolicMode>().is_err());
+        assert!("0600".parse::<SymbolicMode>().is_err());
+        assert!("z=rw".parse::<SymbolicMode>().is_err());
+        assert!("u=rwz".parse::<SymbolicMode>().is_err());
+        assert!("u".parse::<SymbolicMode>().is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// File systems kept in memory, whose files are lost on reboot.
+const VOLATILE_FS_TYPES: &[&str] = &["tmpfs", "ramfs"];
+
+/// Returns a warning if `path` is on a file system kept in memory, where a
+/// long-term key is lost on reboot.
+///
+/// This is best-effort: if the mounts cannot be read, there is no warning.
+pub(crate) fn check_persistent_fs(path: &Path) -> Option<String> {
+    volatile_fs_warning(path, || fs::read_to_string("/proc/mounts"))
+}
+
+fn volatile_fs_warning(
+    path: &Path,
+    read_mounts: impl FnOnce() -> io::Result<String>,
+) -> Option<String> {
+    let path = resolve(path);
+    let mounts = read_mounts().ok()?;
+    // The deepest mount point holds the path, and of several stacked on the
+    // same one, the last.
+    let (_, fs_type) = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let mount_point = unescape(fields.nth(1)?);
+            let fs_type = fields.next()?;
+            path.starts_with(&mount_point)
+                .then_some((mount_point, fs_type))
+        })
+        .max_by_key(|(mount_point, _)| mount_point.components().count())?;
+    VOLATILE_FS_TYPES.contains(&fs_type).then(|| {
+        format!(
+            "{} is on a {fs_type} file system, it will be lost on reboot. Pass \
+             --allow-volatile-fs to write it there without this warning",
+            path.display()
+        )
+    })
+}
+
+/// Resolves the symlinks of the closest existing ancestor of `path`, as the
+/// file itself is usually not written yet.
+fn resolve(path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        if let Ok(resolved) = ancestor.canonicalize() {
+            let rest = path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+            return resolved.join(rest);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Unescapes the octal escapes of spaces and other whitespace in a mount
+/// point from `/proc/mounts`.
+fn unescape(field: &str) -> PathBuf {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(i) = rest.find('\\') {
+        unescaped.push_str(&rest[..i]);
+        let code = rest
+            .get(i + 1..i + 4)
+            .and_then(|code| u8::from_str_radix(code, 8).ok());
+        match code {
+            Some(code) => {
+                unescaped.push(char::from(code));
+                rest = &rest[i + 4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[i + 1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    PathBuf::from(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const MOUNTS: &str = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+                          tmpfs /tmp tmpfs rw,nosuid,nodev 0 0\n\
+                          /dev/sda2 /tmp/my\\040disk ext4 rw,relatime 0 0\n";
+
+    #[test]
+    fn warns_for_tmpfs() {
+        let mounts = || Ok(MOUNTS.to_string());
+        let warning = volatile_fs_warning(Path::new("/tmp/keys/id_ed25519"), mounts).unwrap();
+        assert!(warning.contains("is on a tmpfs file system"));
+
+        let path = Path::new("/tmp/my disk/id_ed25519");
+        assert_eq!(volatile_fs_warning(path, mounts), None);
+        let path = Path::new("/home/me/.ssh/id_ed25519");
+        assert_eq!(volatile_fs_warning(path, mounts), None);
+        assert_eq!(
+            volatile_fs_warning(path, || Err(io::Error::from(io::ErrorKind::NotFound))),
+            None
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt::Write;
+
+use bip39::Mnemonic;
+use zeroize::Zeroizing;
+
+/// Renders `mnemonic` as an A4 SVG page for a paper backup, with the words
+/// numbered in two columns and `id` to tell backups apart.
+pub(crate) fn render_svg(mnemonic: &Mnemonic, id: &str) -> Zeroizing<String> {
+    const ROW_HEIGHT: usize = 12;
+    const TOP: usize = 60;
+
+    let words: Vec<&str> = mnemonic.word_iter().collect();
+    let rows = words.len().div_ceil(2);
+
+    let mut svg = Zeroizing::new(String::new());
+    svg.push_str(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="210mm" height="297mm" viewBox="0 0 210 297" font-family="monospace">"#,
+        "\n",
+        r#"  <rect width="210" height="297" fill="white"/>"#,
+        "\n",
+        r#"  <text x="20" y="30" font-size="9" font-weight="bold">BIP39 mnemonic</text>"#,
+        "\n",
+    ));
+    writeln!(
+        svg,
+        r#"  <text x="20" y="42" font-size="5">{} words, id {}. Anyone with this page can derive your keys.</text>"#,
+        words.len(),
+        escape(id)
+    )
+    .expect("writing to a String cannot fail");
+    for (i, word) in words.iter().enumerate() {
+        let (x, row) = if i < rows { (20, i) } else { (110, i - rows) };
+        writeln!(
+            svg,
+            r#"  <text x="{x}" y="{}" font-size="7">{:>2}. {}</text>"#,
+            TOP + row * ROW_HEIGHT,
+            i + 1,
+            escape(word)
+        )
+        .expect("writing to a String cannot fail");
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn svg_contains_words_in_order() {
+        let mnemonic = Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+        let svg = render_svg(&mnemonic, "deadbeef");
+
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.ends_with("</svg>\n"));
+
+        let mut rest = svg.as_str();
+        for (i, word) in mnemonic.word_iter().enumerate() {
+            let entry = format!("{:>2}. {word}</text>", i + 1);
+            let at = rest.find(&entry).unwrap();
+            rest = &rest[at + entry.len()..];
+        }
+    }
+}
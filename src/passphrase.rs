@@ -0,0 +1,165 @@
+use std::fmt::Write;
+
+use clap::ValueEnum;
+use hkdf::Hkdf;
+use secrecy::ExposeSecret;
+use secrecy::SecretString;
+use serde::Serialize;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// How several passphrases are combined into the one BIP39 passphrase.
+///
+/// Both modes sort the passphrases by their UTF-8 bytes first, so the order
+/// they are given in does not matter.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Combine {
+    /// Join the sorted passphrases with NUL characters. A single passphrase
+    /// is used as is
+    #[default]
+    SortedConcat,
+    /// Use the lowercase hex of
+    /// `HKDF-SHA256(salt = "bip39-keygen/v1/passphrases", ikm, info = "")`, where `ikm` is
+    /// each sorted passphrase prefixed with its byte length as a big-endian u64
+    Hkdf,
+}
+
+const SALT: &[u8] = b"bip39-keygen/v1/passphrases";
+
+/// Combines `passphrases` into the passphrase fed to the BIP39 seed.
+pub(crate) fn combine(passphrases: &[SecretString], combine: Combine) -> SecretString {
+    let mut sorted: Vec<&str> = passphrases
+        .iter()
+        .map(|p| p.expose_secret().as_str())
+        .collect();
+    sorted.sort_unstable();
+
+    match combine {
+        Combine::SortedConcat => SecretString::new(sorted.join("\0")),
+        Combine::Hkdf => {
+            let mut ikm = Zeroizing::new(Vec::new());
+            for passphrase in sorted {
+                ikm.extend_from_slice(&(passphrase.len() as u64).to_be_bytes());
+                ikm.extend_from_slice(passphrase.as_bytes());
+            }
+            let mut okm = Zeroizing::new([0u8; 32]);
+            Hkdf::<Sha256>::new(Some(SALT), &ikm)
+                .expand(&[], okm.as_mut())
+                .expect("32 bytes is a valid length for HKDF-SHA256");
+            let mut hex = String::with_capacity(64);
+            for b in okm.iter() {
+                write!(hex, "{b:02x}").expect("writing to a String cannot fail");
+            }
+            SecretString::new(hex)
+        }
+    }
+}
+
+/// The fewest characters a passphrase that encrypts a key file may have.
+const MIN_ENCRYPTION_LEN: usize = 12;
+
+/// Fails if the passphrase that encrypts a key file is weak: shorter than
+/// [`MIN_ENCRYPTION_LEN`] characters, or made of only one kind of character
+/// out of lowercase letters, uppercase letters, digits and others.
+///
+/// The BIP39 passphrase is not subject to this, since it may be empty on
+/// purpose, such as when the mnemonic alone is kept offline.
+pub(crate) fn check_encryption_strength(passphrase: &str) -> anyhow::Result<()> {
+    let len = passphrase.chars().count();
+    if len < MIN_ENCRYPTION_LEN {
+        anyhow::bail!(
+            "the encryption passphrase is weak: it has {len} characters, at least \
+             {MIN_ENCRYPTION_LEN} are needed"
+        );
+    }
+    let kinds = [
+        passphrase.chars().any(|c| c.is_lowercase()),
+        passphrase.chars().any(|c| c.is_uppercase()),
+        passphrase.chars().any(|c| c.is_ascii_digit()),
+        passphrase
+            .chars()
+            .any(|c| !c.is_lowercase() && !c.is_uppercase() && !c.is_ascii_digit()),
+    ];
+    if kinds.iter().filter(|&&kind| kind).count() < 2 {
+        anyhow::bail!(
+            "the encryption passphrase is weak: it only has one kind of character, mix in \
+             uppercase letters, digits, spaces or symbols"
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bip39::Mnemonic;
+
+    use super::*;
+    use crate::derivation;
+
+    fn passphrases(passphrases: &[&str]) -> Vec<SecretString> {
+        passphrases
+            .iter()
+            .map(|p| SecretString::new(p.to_string()))
+            .collect()
+    }
+
+    fn derive(passphrases: &[&str], mode: Combine) -> ssh_key::public::Ed25519PublicKey {
+        let mnemonic = Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+        crate::derive_ed25519_keypair(
+            &mnemonic,
+            &combine(&self::passphrases(passphrases), mode),
+            derivation::Derivation::Legacy,
+            derivation::SSH_ED25519,
+            None,
+        )
+        .public
+    }
+
+    #[test]
+    fn sorted_concat_ignores_order() {
+        let key = derive(&["alice", "bob", "carol"], Combine::SortedConcat);
+        assert_eq!(
+            key,
+            derive(&["carol", "alice", "bob"], Combine::SortedConcat)
+        );
+        assert_ne!(key, derive(&["alice", "bob"], Combine::SortedConcat));
+    }
+
+    #[test]
+    fn single_passphrase_is_unchanged() {
+        let combined = combine(&passphrases(&["alice"]), Combine::SortedConcat);
+        assert_eq!(combined.expose_secret(), "alice");
+    }
+
+    #[test]
+    fn weak_encryption_passphrase() {
+        let err = check_encryption_strength("hunter2").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the encryption passphrase is weak: it has 7 characters, at least 12 are needed"
+        );
+        assert!(check_encryption_strength("aaaaaaaaaaaaaaaa").is_err());
+        assert!(check_encryption_strength("").is_err());
+        check_encryption_strength("correct horse battery staple").unwrap();
+        check_encryption_strength("Tr0ub4dor&3xyz").unwrap();
+    }
+
+    #[test]
+    fn hkdf_is_unambiguous() {
+        let combined = combine(&passphrases(&["b", "a"]), Combine::Hkdf);
+        assert_eq!(
+            combined.expose_secret(),
+            combine(&passphrases(&["a", "b"]), Combine::Hkdf).expose_secret()
+        );
+        assert_eq!(combined.expose_secret().len(), 64);
+        assert_ne!(
+            combined.expose_secret(),
+            combine(&passphrases(&["ab"]), Combine::Hkdf).expose_secret()
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::bail;
+use anyhow::Context;
+use async_trait::async_trait;
+use russh::client;
+use russh_keys::key;
+
+/// The host to probe, given as `[<user>@]<host>[:<port>]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Target {
+    user: String,
+    host: String,
+    port: u16,
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (user, host_port) = match s.rsplit_once('@') {
+            Some((user, host_port)) => (user.to_string(), host_port),
+            None => (whoami::username(), s),
+        };
+        let (host, port) = match host_port.strip_prefix('[') {
+            // A bracketed IPv6 address, optionally followed by a port.
+            Some(rest) => match rest.split_once(']') {
+                Some((host, "")) => (host, None),
+                Some((host, port)) => match port.strip_prefix(':') {
+                    Some(port) => (host, Some(port)),
+                    None => bail!("expected :<port> after ]"),
+                },
+                None => bail!("missing ] after the IPv6 address"),
+            },
+            None => match host_port.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (host_port, None),
+            },
+        };
+        if user.is_empty() || host.is_empty() {
+            bail!("expected [<user>@]<host>[:<port>]");
+        }
+        let port = match port {
+            Some(port) => port.parse().context("invalid port")?,
+            None => 22,
+        };
+        Ok(Self {
+            user,
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.host.contains(':') {
+            write!(f, "{}@[{}]:{}", self.user, self.host, self.port)
+        } else {
+            write!(f, "{}@{}:{}", self.user, self.host, self.port)
+        }
+    }
+}
+
+impl Target {
+    /// Returns the `ssh` destination of the target, with `-p <port>` before it
+    /// unless it is the default port.
+    pub(crate) fn ssh_destination(&self) -> String {
+        if self.port == 22 {
+            format!("{}@{}", self.user, self.host)
+        } else {
+            format!("-p {} {}@{}", self.port, self.user, self.host)
+        }
+    }
+}
+
+/// Accepts only host keys already listed in `~/.ssh/known_hosts`, which is
+/// never written to.
+struct KnownHosts {
+    host: String,
+    port: u16,
+}
+
+#[async_trait]
+impl client::Handler for KnownHosts {
+    type Error = anyhow::Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &key::PublicKey,
+    ) -> Result<bool, Self::Error> {
+        let known = russh_keys::check_known_hosts(&self.host, self.port, server_public_key)?;
+        if !known {
+            bail!("the host key of {} is not in known_hosts", self.host);
+        }
+        Ok(true)
+    }
+}
+
+/// Checks that `target` accepts `secret_key` for public key authentication,
+/// without opening a session. Gives up after `timeout`.
+pub(crate) fn probe(
+    target: &Target,
+    secret_key: &ssh_key::PrivateKey,
+    timeout: Duration,
+) -> anyhow::Result<bool> {
+    let openssh = secret_key.to_openssh(Default::default())?;
+    let keypair = Arc::new(russh_keys::decode_secret_key(&openssh, None)?);
+    let config = Arc::new(client::Config {
+        inactivity_timeout: Some(timeout),
+        ..Default::default()
+    });
+    let handler = KnownHosts {
+        host: target.host.clone(),
+        port: target.port,
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let authenticate = async {
+            let mut session =
+                client::connect(config, (target.host.as_str(), target.port), handler).await?;
+            let authenticated = session
+                .authenticate_publickey(&target.user, keypair)
+                .await?;
+            session
+                .disconnect(russh::Disconnect::ByApplication, "", "")
+                .await?;
+            anyhow::Ok(authenticated)
+        };
+        tokio::time::timeout(timeout, authenticate)
+            .await
+            .with_context(|| format!("timed out probing {target}"))?
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn parse_target() {
+        let target: Target = "git@example.com".parse().unwrap();
+        assert_eq!(target, Target {
+            user: "git".to_string(),
+            host: "example.com".to_string(),
+            port: 22,
+        });
+
+        let target: Target = "me@[::1]:2222".parse().unwrap();
+        assert_eq!(target.host, "::1");
+        assert_eq!(target.port, 2222);
+        assert_eq!(target.to_string(), "me@[::1]:2222");
+
+        let target: Target = "example.com:2222".parse().unwrap();
+        assert_eq!(target.user, whoami::username());
+
+        assert!("me@".parse::<Target>().is_err());
+        assert!("me@host:ssh".parse::<Target>().is_err());
+        assert!("me@[::1".parse::<Target>().is_err());
+    }
+
+    /// Needs `BIP39_KEYGEN_TEST_HOST` to accept the key in
+    /// `BIP39_KEYGEN_TEST_KEY`, run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn probe_host() {
+        let target: Target = std::env::var("BIP39_KEYGEN_TEST_HOST")
+            .unwrap()
+            .parse()
+            .unwrap();
+        let key_path = std::env::var("BIP39_KEYGEN_TEST_KEY").unwrap();
+        let secret_key = ssh_key::PrivateKey::read_openssh_file(key_path.as_ref()).unwrap();
+
+        assert!(probe(&target, &secret_key, Duration::from_secs(5)).unwrap());
+    }
+}
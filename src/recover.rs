@@ -0,0 +1,247 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::transaction;
+
+/// The prefix of the temp dirs transactions keep their backups in.
+pub(crate) const TEMP_DIR_PREFIX: &str = "bip39-keygen";
+
+/// Creates a temp dir for a transaction to keep its backups in under `dir`,
+/// only accessible by the current user, so [`find_orphans`] trusts it.
+pub(crate) fn create_temp_dir(dir: &Path) -> io::Result<tempfile::TempDir> {
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(TEMP_DIR_PREFIX);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(fs::Permissions::from_mode(0o700));
+    }
+    builder.tempdir_in(dir)
+}
+
+/// A backup left behind by a transaction that neither committed nor rolled
+/// back, because its process died.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Orphan {
+    pub(crate) backup: PathBuf,
+    /// Where the backup was taken from, unless it could not be recorded.
+    pub(crate) original: Option<PathBuf>,
+    /// The version of the transaction the backup was taken at.
+    version: u64,
+}
+
+impl Orphan {
+    /// Whether `self` is a later backup of the same original as `earlier`,
+    /// taken in the same transaction. It then holds what the transaction
+    /// wrote itself, not what the original was before.
+    pub(crate) fn supersedes(&self, earlier: &Orphan) -> bool {
+        self.original.is_some()
+            && self.original == earlier.original
+            && self.backup.parent() == earlier.backup.parent()
+            && self.version > earlier.version
+    }
+}
+
+/// Finds the backups in the transaction temp dirs under `temp_dir`, sorted
+/// by temp dir and then in the order they were taken.
+///
+/// Only temp dirs of the current user that nobody else can access are
+/// trusted, since anybody can create one in a shared temp dir. A transaction
+/// that is still running looks the same, so this is only meaningful while no
+/// other run is in progress.
+pub(crate) fn find_orphans(temp_dir: &Path) -> io::Result<Vec<Orphan>> {
+    let mut orphans = Vec::new();
+    for txdir in fs::read_dir(temp_dir)? {
+        let txdir = txdir?;
+        if !txdir
+            .file_name()
+            .to_string_lossy()
+            .starts_with(TEMP_DIR_PREFIX)
+            || !is_own_private_dir(&txdir.path())
+        {
+            continue;
+        }
+        // Skips a temp dir that went away or cannot be read.
+        let Ok(entries) = fs::read_dir(txdir.path()) else {
+            continue;
+        };
+        for entry in entries {
+            let backup = entry?.path();
+            let version = backup
+                .file_name()
+                .and_then(|name| name.to_str()?.rsplit_once(".backup.")?.1.parse().ok());
+            let Some(version) = version else {
+                continue;
+            };
+            let original = match fs::read_to_string(transaction::origin_path(&backup)) {
+                Ok(original) => Some(PathBuf::from(original)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+            };
+            orphans.push(Orphan {
+                backup,
+                original,
+                version,
+            });
+        }
+    }
+    orphans.sort_by(|a, b| (a.backup.parent(), a.version).cmp(&(b.backup.parent(), b.version)));
+    Ok(orphans)
+}
+
+/// Whether `path` is a directory, not a symlink to one, owned by the
+/// effective user and only accessible by them, as [`create_temp_dir`]
+/// creates it.
+#[cfg(unix)]
+fn is_own_private_dir(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::symlink_metadata(path).is_ok_and(|metadata| {
+        metadata.is_dir()
+            && metadata.uid() == unsafe { libc::geteuid() }
+            && metadata.mode() & 0o777 == 0o700
+    })
+}
+
+#[cfg(not(unix))]
+fn is_own_private_dir(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir())
+}
+
+/// Moves the backup of `orphan` back to its original path, replacing what
+/// the interrupted transaction wrote there, as rolling back would have.
+/// The temp dir is removed once it holds no more backups.
+pub(crate) fn restore(orphan: &Orphan) -> io::Result<()> {
+    let Some(original) = &orphan.original else {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "the original path of {} is unknown",
+                orphan.backup.display()
+            ),
+        ));
+    };
+    if orphan.backup.is_dir() && original.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists, move it away first", original.display()),
+        ));
+    }
+    if fs::rename(&orphan.backup, original).is_err() {
+        // The backup of a replaced file may be on another file system.
+        fs::copy(&orphan.backup, original)?;
+        fs::remove_file(&orphan.backup)?;
+    }
+    remove_origin(orphan)
+}
+
+/// Removes the backup of `orphan` without restoring it, such as when it is
+/// superseded by an earlier backup of the same original.
+pub(crate) fn discard(orphan: &Orphan) -> io::Result<()> {
+    if orphan.backup.is_dir() {
+        fs::remove_dir_all(&orphan.backup)?;
+    } else {
+        fs::remove_file(&orphan.backup)?;
+    }
+    remove_origin(orphan)
+}
+
+fn remove_origin(orphan: &Orphan) -> io::Result<()> {
+    match fs::remove_file(transaction::origin_path(&orphan.backup)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    if let Some(txdir) = orphan.backup.parent() {
+        // Fails as long as other backups are left.
+        let _ = fs::remove_dir(txdir);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::transaction::Transaction;
+
+    #[test]
+    fn restore_orphaned_backup() {
+        let testdir = tempfile::tempdir().unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let filepath = testdir.path().join("id_ed25519");
+        fs::write(&filepath, "original").unwrap();
+
+        let txdir = create_temp_dir(temp_dir.path()).unwrap();
+        let txdir_path = txdir.path().to_path_buf();
+        let mut tx = Transaction::new(txdir);
+        tx.write_file(&filepath, "partial").unwrap();
+        // The process dies before the transaction commits or rolls back.
+        std::mem::forget(tx);
+
+        let orphans = find_orphans(temp_dir.path()).unwrap();
+        assert_eq!(orphans.len(), 1);
+        assert_eq!(orphans[0].original.as_ref(), Some(&filepath));
+        assert!(orphans[0].backup.starts_with(&txdir_path));
+
+        restore(&orphans[0]).unwrap();
+        assert_eq!(fs::read_to_string(&filepath).unwrap(), "original");
+        assert!(!txdir_path.exists());
+        assert!(find_orphans(temp_dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn earliest_backup_first() {
+        let testdir = tempfile::tempdir().unwrap();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let filepath = testdir.path().join("id_ed25519");
+        fs::write(&filepath, "original").unwrap();
+
+        let mut tx = Transaction::new(create_temp_dir(temp_dir.path()).unwrap());
+        for i in 0..11 {
+            tx.write_file(&filepath, format!("partial {i}")).unwrap();
+        }
+        std::mem::forget(tx);
+
+        let orphans = find_orphans(temp_dir.path()).unwrap();
+        assert_eq!(orphans.len(), 11);
+        // `.backup.10` is taken after `.backup.2`.
+        assert!(orphans.windows(2).all(|w| w[0].version < w[1].version));
+        assert!(orphans[1..].iter().all(|o| o.supersedes(&orphans[0])));
+        restore(&orphans[0]).unwrap();
+        for orphan in &orphans[1..] {
+            discard(orphan).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&filepath).unwrap(), "original");
+        assert!(find_orphans(temp_dir.path()).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn untrusted_temp_dirs_are_skipped() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let planted = temp_dir.path().join(format!("{TEMP_DIR_PREFIX}-planted"));
+        fs::create_dir(&planted).unwrap();
+        fs::write(planted.join("authorized_keys.backup.0"), "attacker").unwrap();
+        fs::write(
+            planted.join("authorized_keys.backup.0.origin"),
+            "/root/.ssh/authorized_keys",
+        )
+        .unwrap();
+        fs::set_permissions(&planted, fs::Permissions::from_mode(0o777)).unwrap();
+        // A private dir elsewhere, linked into the temp dir.
+        let elsewhere = tempfile::tempdir().unwrap();
+        let linked = create_temp_dir(elsewhere.path()).unwrap();
+        fs::write(linked.path().join("authorized_keys.backup.0"), "attacker").unwrap();
+        std::os::unix::fs::symlink(
+            linked.path(),
+            temp_dir.path().join(format!("{TEMP_DIR_PREFIX}-link")),
+        )
+        .unwrap();
+
+        assert!(find_orphans(temp_dir.path()).unwrap().is_empty());
+    }
+}
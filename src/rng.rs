@@ -0,0 +1,44 @@
+use std::fs;
+use std::io;
+
+/// Below this many bits in the kernel entropy pool, even a 12-word mnemonic
+/// may get less entropy than it encodes.
+const MIN_ENTROPY_AVAIL: u32 = 128;
+
+/// Returns a warning if the kernel reports little entropy available, as on
+/// embedded systems early in the boot, where the RNG may not be seeded well
+/// yet.
+///
+/// This is best-effort: if the entropy cannot be read, there is no warning.
+pub(crate) fn check_entropy_quality() -> Option<String> {
+    entropy_warning(|| fs::read_to_string("/proc/sys/kernel/random/entropy_avail"))
+}
+
+fn entropy_warning(read: impl FnOnce() -> io::Result<String>) -> Option<String> {
+    let available: u32 = read().ok()?.trim().parse().ok()?;
+    (available < MIN_ENTROPY_AVAIL).then(|| {
+        format!(
+            "the kernel only has {available} bits of entropy available, consider waiting \
+             for more before generating a mnemonic"
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn warns_below_threshold() {
+        let warning = entropy_warning(|| Ok("42\n".to_string())).unwrap();
+        assert!(warning.contains("only has 42 bits"));
+
+        assert_eq!(entropy_warning(|| Ok("256\n".to_string())), None);
+        assert_eq!(entropy_warning(|| Ok("garbage".to_string())), None);
+        assert_eq!(
+            entropy_warning(|| Err(io::Error::from(io::ErrorKind::NotFound))),
+            None
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::process::ExitStatus;
+
+/// Resets the SELinux context of written files with `restorecon`, so that
+/// for example sshd can read host keys saved under `/etc/ssh`.
+///
+/// This is best-effort: if `restorecon` is missing or fails, a warning is
+/// returned and the files are left as they are.
+pub(crate) fn restorecon(paths: &[&Path]) -> Option<String> {
+    restorecon_with(paths, |command| command.status())
+}
+
+fn restorecon_with(
+    paths: &[&Path],
+    mut run: impl FnMut(&mut Command) -> io::Result<ExitStatus>,
+) -> Option<String> {
+    let mut command = Command::new("restorecon");
+    command.arg("--").args(paths);
+    match run(&mut command) {
+        Ok(status) if status.success() => None,
+        Ok(status) => Some(format!("restorecon failed with {status}")),
+        Err(e) => Some(format!("could not run restorecon: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::ffi::OsStr;
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+
+    #[test]
+    fn restorecon_is_invoked_for_keys() {
+        let seckey_path = Path::new("/etc/ssh/ssh_host_ed25519_key");
+        let pubkey_path = Path::new("/etc/ssh/ssh_host_ed25519_key.pub");
+
+        let mut invocations = Vec::new();
+        let warning = restorecon_with(&[seckey_path, pubkey_path], |command| {
+            invocations.push((
+                command.get_program().to_owned(),
+                command.get_args().map(OsStr::to_owned).collect::<Vec<_>>(),
+            ));
+            Ok(ExitStatus::from_raw(0))
+        });
+
+        assert_eq!(warning, None);
+        assert_eq!(invocations, [("restorecon".into(), vec![
+            "--".into(),
+            seckey_path.into(),
+            pubkey_path.into()
+        ])]);
+    }
+
+    #[test]
+    fn restorecon_failure_is_not_fatal() {
+        let warning = restorecon_with(&[Path::new("/etc/ssh/ssh_host_ed25519_key")], |_| {
+            Err(io::Error::from(io::ErrorKind::NotFound))
+        });
+        assert!(warning.unwrap().starts_with("could not run restorecon"));
+    }
+}
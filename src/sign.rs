@@ -0,0 +1,66 @@
+use ssh_key::HashAlg;
+use ssh_key::LineEnding;
+use ssh_key::PrivateKey;
+use ssh_key::PublicKey;
+use ssh_key::SshSig;
+
+/// Creates a detached signature of `msg` in the format produced by
+/// `ssh-keygen -Y sign`.
+pub(crate) fn sign(secret_key: &PrivateKey, namespace: &str, msg: &[u8]) -> anyhow::Result<String> {
+    let signature = secret_key.sign(namespace, HashAlg::Sha512, msg)?;
+    Ok(signature.to_pem(LineEnding::LF)?)
+}
+
+/// Verifies a detached signature in the format produced by
+/// `ssh-keygen -Y sign`.
+pub(crate) fn verify(
+    public_key: &PublicKey,
+    namespace: &str,
+    msg: &[u8],
+    signature: &str,
+) -> anyhow::Result<()> {
+    let signature = SshSig::from_pem(signature)?;
+    public_key.verify(namespace, msg, &signature)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ssh_key::private::Ed25519Keypair;
+    use ssh_key::private::KeypairData;
+
+    use super::*;
+
+    fn secret_key() -> PrivateKey {
+        PrivateKey::new(
+            KeypairData::Ed25519(Ed25519Keypair::from_seed(&[7; 32])),
+            "",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn sign_then_verify() {
+        let secret_key = secret_key();
+        let signature = sign(&secret_key, "file", b"hello").unwrap();
+
+        verify(secret_key.public_key(), "file", b"hello", &signature).unwrap();
+    }
+
+    #[test]
+    fn verify_tampered_message() {
+        let secret_key = secret_key();
+        let signature = sign(&secret_key, "file", b"hello").unwrap();
+
+        assert!(verify(secret_key.public_key(), "file", b"hellO", &signature).is_err());
+    }
+
+    #[test]
+    fn verify_other_namespace() {
+        let secret_key = secret_key();
+        let signature = sign(&secret_key, "file", b"hello").unwrap();
+
+        assert!(verify(secret_key.public_key(), "git", b"hello", &signature).is_err());
+    }
+}
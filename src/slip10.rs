@@ -0,0 +1,358 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::bail;
+use hmac::Hmac;
+use hmac::Mac;
+use sha2::Sha512;
+use zeroize::Zeroizing;
+
+/// Offset of the first hardened child index.
+pub(crate) const HARDENED_OFFSET: u32 = 0x8000_0000;
+
+const ED25519_CURVE_KEY: &[u8] = b"ed25519 seed";
+
+/// A single component of a [`DerivationPath`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum ChildIndex {
+    Normal(u32),
+    Hardened(u32),
+}
+
+/// A BIP32-style derivation path such as `m/44'/0'`, where `'` or `h`
+/// marks a hardened index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DerivationPath(Vec<ChildIndex>);
+
+/// Why a [`DerivationPath`] could not be parsed. `position` counts the
+/// components after `m` from 1.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum PathError {
+    /// The path does not start with `m`, but with `found`.
+    MissingMaster { found: String },
+    /// Two slashes follow each other, or the path ends with one.
+    EmptyComponent { position: usize },
+    /// The component is not a decimal index, optionally followed by `'` or
+    /// `h`.
+    NotAnIndex { position: usize, component: String },
+    /// The index does not fit below [`HARDENED_OFFSET`].
+    IndexTooLarge { position: usize, component: String },
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingMaster { found } if found.is_empty() => {
+                write!(f, "derivation path is empty, it should start with \"m\"")
+            }
+            Self::MissingMaster { found } => {
+                write!(
+                    f,
+                    "derivation path should start with \"m\", not \"{found}\""
+                )
+            }
+            Self::EmptyComponent { position } => {
+                write!(f, "component {position} of the derivation path is empty")
+            }
+            Self::NotAnIndex {
+                position,
+                component,
+            } => write!(
+                f,
+                "component {position} \"{component}\" of the derivation path is not an index \
+                 such as 0 or 0'"
+            ),
+            Self::IndexTooLarge {
+                position,
+                component,
+            } => write!(
+                f,
+                "component {position} \"{component}\" of the derivation path exceeds the \
+                 largest index {}",
+                HARDENED_OFFSET - 1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+impl FromStr for DerivationPath {
+    type Err = PathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut components = s.split('/');
+        match components.next() {
+            Some("m") => {}
+            found => {
+                return Err(PathError::MissingMaster {
+                    found: found.unwrap_or_default().to_string(),
+                });
+            }
+        }
+        components
+            .enumerate()
+            .map(|(i, component)| {
+                let position = i + 1;
+                let (index, hardened) = match component.strip_suffix(['\'', 'h']) {
+                    Some(index) => (index, true),
+                    None => (component, false),
+                };
+                if component.is_empty() {
+                    return Err(PathError::EmptyComponent { position });
+                }
+                // `u32::from_str` also takes a leading `+`.
+                if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(PathError::NotAnIndex {
+                        position,
+                        component: component.to_string(),
+                    });
+                }
+                match index.parse::<u32>() {
+                    Ok(index) if index < HARDENED_OFFSET && hardened => {
+                        Ok(ChildIndex::Hardened(index))
+                    }
+                    Ok(index) if index < HARDENED_OFFSET => Ok(ChildIndex::Normal(index)),
+                    _ => Err(PathError::IndexTooLarge {
+                        position,
+                        component: component.to_string(),
+                    }),
+                }
+            })
+            .collect::<Result<_, _>>()
+            .map(DerivationPath)
+    }
+}
+
+impl DerivationPath {
+    /// Fails unless every index is hardened, as SLIP-0010 requires for
+    /// ed25519.
+    pub(crate) fn check_hardened(&self) -> anyhow::Result<()> {
+        if self
+            .0
+            .iter()
+            .all(|index| matches!(index, ChildIndex::Hardened(_)))
+        {
+            return Ok(());
+        }
+        let hardened = DerivationPath(
+            self.0
+                .iter()
+                .map(
+                    |(ChildIndex::Normal(index) | ChildIndex::Hardened(index))| {
+                        ChildIndex::Hardened(*index)
+                    },
+                )
+                .collect(),
+        );
+        bail!("ed25519 keys only support hardened derivation, use {hardened} instead of {self}")
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m")?;
+        for index in &self.0 {
+            match index {
+                ChildIndex::Normal(index) => write!(f, "/{index}")?,
+                ChildIndex::Hardened(index) => write!(f, "/{index}'")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An ed25519 extended private key as defined by
+/// [SLIP-0010](https://github.com/satoshilabs/slips/blob/master/slip-0010.md).
+///
+/// ed25519 only supports hardened derivation, so every child index is
+/// offset by [`HARDENED_OFFSET`] before being hashed.
+pub(crate) struct ExtendedKey {
+    key: Zeroizing<[u8; 32]>,
+    chain_code: Zeroizing<[u8; 32]>,
+}
+
+impl ExtendedKey {
+    /// Generates the master node from a seed.
+    pub(crate) fn master(seed: &[u8]) -> Self {
+        Self::from_hmac(ED25519_CURVE_KEY, &[seed])
+    }
+
+    /// Derives the hardened child at `index`, which must be below
+    /// [`HARDENED_OFFSET`].
+    pub(crate) fn derive_hardened(&self, index: u32) -> Self {
+        assert!(index < HARDENED_OFFSET, "index should be below 2^31");
+        let index = (index | HARDENED_OFFSET).to_be_bytes();
+        Self::from_hmac(self.chain_code.as_ref(), &[&[0], self.key.as_ref(), &index])
+    }
+
+    /// Derives the descendant at `path`, failing on non-hardened indices.
+    pub(crate) fn derive_path(&self, path: &DerivationPath) -> anyhow::Result<Self> {
+        path.check_hardened()?;
+        let mut key = Self {
+            key: self.key.clone(),
+            chain_code: self.chain_code.clone(),
+        };
+        for index in &path.0 {
+            match index {
+                ChildIndex::Hardened(index) => key = key.derive_hardened(*index),
+                ChildIndex::Normal(_) => unreachable!("path is checked to be hardened"),
+            }
+        }
+        Ok(key)
+    }
+
+    /// Returns the 32-byte private key, usable as an ed25519 seed.
+    pub(crate) fn key(&self) -> &[u8; 32] {
+        &self.key
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    fn from_hmac(hmac_key: &[u8], data: &[&[u8]]) -> Self {
+        let mut mac =
+            Hmac::<Sha512>::new_from_slice(hmac_key).expect("HMAC can take key of any size");
+        for d in data {
+            mac.update(d);
+        }
+        let i = Zeroizing::new(<[u8; 64]>::from(mac.finalize().into_bytes()));
+
+        let mut key = Zeroizing::new([0u8; 32]);
+        let mut chain_code = Zeroizing::new([0u8; 32]);
+        key.copy_from_slice(&i[..32]);
+        chain_code.copy_from_slice(&i[32..]);
+        Self { key, chain_code }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // Test vector 1 for ed25519 from SLIP-0010.
+    const SEED: [u8; 16] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e,
+        0x0f,
+    ];
+
+    #[test]
+    fn master_matches_test_vector() {
+        let master = ExtendedKey::master(&SEED);
+        assert_eq!(
+            hex::encode(master.chain_code()),
+            "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb"
+        );
+        assert_eq!(
+            hex::encode(master.key()),
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+        );
+    }
+
+    #[test]
+    fn child_matches_test_vector() {
+        let child = ExtendedKey::master(&SEED).derive_hardened(0);
+        assert_eq!(
+            hex::encode(child.chain_code()),
+            "8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69"
+        );
+        assert_eq!(
+            hex::encode(child.key()),
+            "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3"
+        );
+    }
+
+    #[test]
+    fn derive_path_matches_test_vector() {
+        let path: DerivationPath = "m/0'/1'".parse().unwrap();
+        let key = ExtendedKey::master(&SEED).derive_path(&path).unwrap();
+        assert_eq!(
+            hex::encode(key.key()),
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2"
+        );
+    }
+
+    #[test]
+    fn parse_path() {
+        let path: DerivationPath = "m/0'/1h/2".parse().unwrap();
+        assert_eq!(path.0, [
+            ChildIndex::Hardened(0),
+            ChildIndex::Hardened(1),
+            ChildIndex::Normal(2)
+        ]);
+        assert_eq!(path.to_string(), "m/0'/1'/2");
+
+        assert!("0'/1'".parse::<DerivationPath>().is_err());
+        assert!("m/x".parse::<DerivationPath>().is_err());
+        assert_eq!("m".parse::<DerivationPath>().unwrap().to_string(), "m");
+    }
+
+    #[test]
+    fn malformed_paths() {
+        let err = |s: &str| s.parse::<DerivationPath>().unwrap_err().to_string();
+
+        assert_eq!(
+            err("44'/0'"),
+            "derivation path should start with \"m\", not \"44'\""
+        );
+        assert_eq!(
+            err(""),
+            "derivation path is empty, it should start with \"m\""
+        );
+        assert_eq!(
+            err("m/44'//0'"),
+            "component 2 of the derivation path is empty"
+        );
+        assert_eq!(err("m/0'/"), "component 2 of the derivation path is empty");
+        assert_eq!(
+            err("m/44'/x'"),
+            "component 2 \"x'\" of the derivation path is not an index such as 0 or 0'"
+        );
+        assert_eq!(
+            err("m/+1"),
+            "component 1 \"+1\" of the derivation path is not an index such as 0 or 0'"
+        );
+        assert_eq!(
+            err("m/'"),
+            "component 1 \"'\" of the derivation path is not an index such as 0 or 0'"
+        );
+        assert_eq!(
+            err("m/0/2147483648'"),
+            "component 2 \"2147483648'\" of the derivation path exceeds the largest index \
+             2147483647"
+        );
+        assert_eq!(
+            err("m/99999999999"),
+            "component 1 \"99999999999\" of the derivation path exceeds the largest index \
+             2147483647"
+        );
+    }
+
+    #[test]
+    fn hardened_guard() {
+        let path: DerivationPath = "m/0'/1'".parse().unwrap();
+        path.check_hardened().unwrap();
+
+        let path: DerivationPath = "m/0/1".parse().unwrap();
+        let err = path.check_hardened().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ed25519 keys only support hardened derivation, use m/0'/1' instead of m/0/1"
+        );
+        assert!(ExtendedKey::master(&SEED).derive_path(&path).is_err());
+    }
+
+    #[test]
+    fn children_are_reproducible_and_distinct() {
+        let master = ExtendedKey::master(&SEED);
+
+        let child0 = master.derive_hardened(0);
+        let child1 = master.derive_hardened(1);
+
+        assert_eq!(child0.key(), master.derive_hardened(0).key());
+        assert_ne!(child0.key(), child1.key());
+    }
+}
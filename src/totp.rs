@@ -0,0 +1,100 @@
+use hkdf::Hkdf;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+const SALT: &[u8] = b"bip39-keygen/v1/totp";
+
+/// The RFC 4648 base32 alphabet that authenticator apps expect.
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Derives the 160-bit TOTP secret of `account` from a BIP39 seed, as
+/// `HKDF-SHA256(salt = "bip39-keygen/v1/totp", ikm = seed, info = account)`,
+/// so that every account label gets an independent secret.
+pub(crate) fn derive_secret(seed: &[u8; 64], account: &str) -> Zeroizing<[u8; 20]> {
+    let mut secret = Zeroizing::new([0u8; 20]);
+    Hkdf::<Sha256>::new(Some(SALT), seed)
+        .expand(account.as_bytes(), secret.as_mut())
+        .expect("20 bytes is a valid length for HKDF-SHA256");
+    secret
+}
+
+/// Encodes `bytes` as unpadded RFC 4648 base32.
+pub(crate) fn base32(bytes: &[u8]) -> Zeroizing<String> {
+    let mut encoded = Zeroizing::new(String::with_capacity((bytes.len() * 8).div_ceil(5)));
+    for chunk in bytes.chunks(5) {
+        let mut buf = [0u8; 5];
+        buf[..chunk.len()].copy_from_slice(chunk);
+        let bits = u64::from_be_bytes([0, 0, 0, buf[0], buf[1], buf[2], buf[3], buf[4]]);
+        for i in 0..(chunk.len() * 8).div_ceil(5) {
+            let index = (bits >> (35 - 5 * i)) & 0x1f;
+            encoded.push(char::from(BASE32_ALPHABET[index as usize]));
+        }
+    }
+    encoded
+}
+
+/// Returns the `otpauth://totp/` URI of `secret` that authenticator apps
+/// import, with the SHA1, 6-digit, 30-second parameters they all support.
+pub(crate) fn otpauth_uri(account: &str, issuer: Option<&str>, secret: &[u8]) -> Zeroizing<String> {
+    let label = match issuer {
+        Some(issuer) => format!("{}:{}", percent_encode(issuer), percent_encode(account)),
+        None => percent_encode(account),
+    };
+    let mut uri = Zeroizing::new(format!(
+        "otpauth://totp/{label}?secret={}&algorithm=SHA1&digits=6&period=30",
+        *base32(secret)
+    ));
+    if let Some(issuer) = issuer {
+        uri.push_str(&format!("&issuer={}", percent_encode(issuer)));
+    }
+    uri
+}
+
+/// Percent-encodes everything but the RFC 3986 unreserved characters.
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(b).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use bip39::Mnemonic;
+
+    use super::*;
+
+    #[test]
+    fn reproducible_secret() {
+        let mnemonic = Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+        let seed = mnemonic.to_seed("");
+
+        let secret = derive_secret(&seed, "me@example.com");
+        assert_eq!(derive_secret(&seed, "me@example.com"), secret);
+        assert_ne!(derive_secret(&seed, "other@example.com"), secret);
+        assert_eq!(base32(secret.as_ref()).len(), 32);
+
+        let uri = otpauth_uri("me@example.com", Some("Example Co"), secret.as_ref());
+        assert_eq!(
+            *uri,
+            format!(
+                "otpauth://totp/Example%20Co:me%40example.com?secret={}\
+                 &algorithm=SHA1&digits=6&period=30&issuer=Example%20Co",
+                *base32(secret.as_ref())
+            )
+        );
+
+        // RFC 4648, section 10, without the padding.
+        assert_eq!(*base32(b"f"), "MY");
+        assert_eq!(*base32(b"foob"), "MZXW6YQ");
+        assert_eq!(*base32(b"foobar"), "MZXW6YTBOI");
+    }
+}
@@ -3,6 +3,76 @@ use std::io;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use zeroize::Zeroizing;
+
+/// The extension of the files recording the original path of a backup.
+pub(crate) const ORIGIN_EXTENSION: &str = "origin";
+
+/// Returns the file recording the original path of `backup_path`.
+pub(crate) fn origin_path(backup_path: &Path) -> PathBuf {
+    let mut path = backup_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(ORIGIN_EXTENSION);
+    PathBuf::from(path)
+}
+
+/// A directory opened once, so that the files [`Transaction::write_file_at`]
+/// writes stay in it, even if its path is replaced meanwhile, such as by a
+/// symlink planted in a world-writable parent.
+#[cfg(unix)]
+#[derive(Debug)]
+pub(crate) struct Dir {
+    fd: std::os::fd::OwnedFd,
+    /// The path the directory was opened at, for messages and backups.
+    path: PathBuf,
+}
+
+#[cfg(unix)]
+impl Dir {
+    /// Opens the directory at `path`, which must not be a symlink.
+    pub(crate) fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let path = path.into();
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECTORY | libc::O_NOFOLLOW)
+            .open(&path)?;
+        Ok(Self {
+            fd: file.into(),
+            path,
+        })
+    }
+
+    fn try_clone(&self) -> io::Result<Self> {
+        Ok(Self {
+            fd: self.fd.try_clone()?,
+            path: self.path.clone(),
+        })
+    }
+}
+
+/// Describes how a [`Transaction`] preserves the files it removes or
+/// overwrites.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum BackupMode {
+    /// Moves the original into the temp dir so it can be restored on
+    /// rollback.
+    #[default]
+    TempDir,
+    /// Deletes the original directly. Rolling back still undoes created
+    /// files and directories, but the removed contents are lost.
+    None,
+    /// Keeps the contents and permissions of removed files in memory and
+    /// rewrites them on rollback, failing once the backups would exceed
+    /// `max_bytes` in total. Directories and symlinks cannot be backed up
+    /// this way.
+    Memory { max_bytes: usize },
+}
 
 /// A Transaction tracks changes to the file system, allowing them to
 /// be rolled back in case of an error.
@@ -10,20 +80,65 @@ pub(crate) struct Transaction {
     operations: Vec<Operation>,
     version: i32,
     committed: bool,
+    backup_mode: BackupMode,
+    follow_symlinks: bool,
+    io_retries: u32,
+    mtime: Option<SystemTime>,
+    backup_memory: usize,
 
-    temp_dir: tempfile::TempDir,
+    temp_dir: Option<tempfile::TempDir>,
 }
 
 impl Transaction {
     pub(crate) fn new(temp_dir: tempfile::TempDir) -> Self {
+        let mut tx = Self::without_temp_dir();
+        tx.temp_dir = Some(temp_dir);
+        tx
+    }
+
+    /// Creates a Transaction that never touches the temp dir, which is
+    /// only usable with [`BackupMode::None`] or [`BackupMode::Memory`].
+    pub(crate) fn without_temp_dir() -> Self {
         Self {
             operations: Vec::new(),
             version: 0,
             committed: false,
-            temp_dir,
+            backup_mode: BackupMode::default(),
+            follow_symlinks: false,
+            io_retries: 0,
+            mtime: None,
+            backup_memory: 0,
+            temp_dir: None,
         }
     }
 
+    pub(crate) fn with_backup_mode(mut self, backup_mode: BackupMode) -> Self {
+        self.backup_mode = backup_mode;
+        self
+    }
+
+    /// By default, writing to a symlink replaces the link itself with a
+    /// regular file. With `follow_symlinks`, the link is resolved and its
+    /// final target is written (and backed up) instead.
+    pub(crate) fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Sets how many times creating or renaming a file is retried, with
+    /// exponential backoff, after a transient error such as `Interrupted`.
+    pub(crate) fn with_io_retries(mut self, io_retries: u32) -> Self {
+        self.io_retries = io_retries;
+        self
+    }
+
+    /// Sets the modification and access times of written files, instead of
+    /// the time they are written at.
+    pub(crate) fn with_mtime(mut self, mtime: Option<SystemTime>) -> Self {
+        self.mtime = mtime;
+        self
+    }
+
     pub(crate) fn commit(&mut self) {
         self.committed = true;
     }
@@ -34,6 +149,9 @@ impl Transaction {
         }
         while let Some(op) = self.operations.pop() {
             op.rollback()?;
+            if let Operation::RestoreFile { contents, .. } = &op {
+                self.backup_memory -= contents.len();
+            }
             self.version -= 1;
             if self.version == version {
                 break;
@@ -47,6 +165,31 @@ impl Transaction {
         self.version
     }
 
+    /// Returns the backups in the temp dir of the files and directories
+    /// removed or replaced so far, as `(original, backup)` pairs, for
+    /// recovering the originals by hand.
+    pub(crate) fn backups(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.operations
+            .iter()
+            .filter_map(|op| match op {
+                Operation::RemoveFile { removed, backup }
+                | Operation::RemoveDir { removed, backup } => {
+                    Some((removed.clone(), backup.clone()))
+                }
+                Operation::Rename {
+                    to,
+                    backup: Some(backup),
+                    ..
+                } => Some((to.clone(), backup.clone())),
+                #[cfg(unix)]
+                Operation::RemoveFileAt { dir, name, backup } => {
+                    Some((dir.path.join(name), backup.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
     pub(crate) fn create_dir(&mut self, path: impl Into<PathBuf>) -> io::Result<()> {
         let path = path.into();
         fs::create_dir(&path)?;
@@ -81,15 +224,115 @@ impl Transaction {
         path: impl Into<PathBuf>,
         contents: impl AsRef<[u8]>,
     ) -> io::Result<()> {
-        let path = path.into();
+        self.write(path.into(), contents.as_ref(), None)
+    }
+
+    /// Like [`Transaction::write_file`], but the file is only readable and
+    /// writable by its owner on Unix.
+    pub(crate) fn write_private_file(
+        &mut self,
+        path: impl Into<PathBuf>,
+        contents: impl AsRef<[u8]>,
+    ) -> io::Result<()> {
+        self.write(path.into(), contents.as_ref(), Some(0o600))
+    }
+
+    fn write(&mut self, mut path: PathBuf, contents: &[u8], mode: Option<u32>) -> io::Result<()> {
+        if self.follow_symlinks {
+            path = resolve_symlink(path)?;
+        }
         if let Some(dirname) = path.parent() {
             self.create_dir_all(dirname)?;
         }
+        // Opens the directory once, so that the file ends up in it even if its
+        // path is replaced meanwhile. Directories reached through a symlink
+        // and backups in memory are only supported by paths.
+        #[cfg(unix)]
+        if let (Some(dirname), Some(name)) = (path.parent(), path.file_name()) {
+            let dirname = if dirname.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dirname
+            };
+            if !matches!(self.backup_mode, BackupMode::Memory { .. })
+                && !fs::symlink_metadata(dirname)?.is_symlink()
+            {
+                let dir = Dir::open(dirname)?;
+                return self.write_file_at(&dir, name, contents, mode);
+            }
+        }
 
+        let mut removed = false;
         let mut file = loop {
-            match fs::File::create_new(&path) {
-                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            match self.retry(|| create_new(&path, mode)) {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && !removed => {
                     self.remove_file(&path)?;
+                    removed = true;
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    // Someone created the file again right after it was
+                    // removed, possibly as a symlink to somewhere else.
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} was created again while writing it", path.display()),
+                    ));
+                }
+                Err(e) => {
+                    return Err(e);
+                }
+                Ok(file) => {
+                    break file;
+                }
+            }
+        };
+
+        file.write_all(contents)?;
+        file.flush()?;
+        drop(file);
+        self.change(Operation::WriteFile(path.clone()));
+        if let Some(mtime) = self.mtime {
+            self.set_times(path, mtime, mtime)?;
+        }
+        Ok(())
+    }
+
+    /// Like [`Transaction::write_file`] with `mode`, but creates `name`
+    /// relative to `dir`, without resolving the path of `dir` again. Missing
+    /// directories are not created, and symlinks are never followed.
+    #[cfg(unix)]
+    pub(crate) fn write_file_at(
+        &mut self,
+        dir: &Dir,
+        name: impl AsRef<Path>,
+        contents: impl AsRef<[u8]>,
+        mode: Option<u32>,
+    ) -> io::Result<()> {
+        let name = name.as_ref();
+        if !matches!(name.components().collect::<Vec<_>>()[..], [
+            std::path::Component::Normal(_)
+        ]) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a file name", name.display()),
+            ));
+        }
+        let dir_clone = dir.try_clone()?;
+
+        let mut removed = false;
+        let mut file = loop {
+            match self.retry(|| create_new_at(dir, name, mode)) {
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && !removed => {
+                    self.remove_file_at(dir, name)?;
+                    removed = true;
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!(
+                            "{} was created again while writing it",
+                            dir.path.join(name).display()
+                        ),
+                    ));
                 }
                 Err(e) => {
                     return Err(e);
@@ -99,10 +342,180 @@ impl Transaction {
                 }
             }
         };
+        self.change(Operation::WriteFileAt {
+            dir: dir_clone,
+            name: name.to_path_buf(),
+        });
 
         file.write_all(contents.as_ref())?;
         file.flush()?;
-        self.change(Operation::WriteFile(path));
+        if let Some(mtime) = self.mtime {
+            // Rolling back removes the file, so its times need no restoring.
+            file.set_times(fs::FileTimes::new().set_modified(mtime).set_accessed(mtime))?;
+        }
+        Ok(())
+    }
+
+    /// Removes the file or symlink `name` in `dir` before
+    /// [`Transaction::write_file_at`] replaces it.
+    #[cfg(unix)]
+    fn remove_file_at(&mut self, dir: &Dir, name: &Path) -> io::Result<()> {
+        use std::os::fd::AsRawFd;
+
+        let c_name = c_path(name)?;
+        let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+        cvt(unsafe {
+            libc::fstatat(
+                dir.fd.as_raw_fd(),
+                c_name.as_ptr(),
+                stat.as_mut_ptr(),
+                libc::AT_SYMLINK_NOFOLLOW,
+            )
+        })?;
+        let file_type = unsafe { stat.assume_init() }.st_mode & libc::S_IFMT;
+        if file_type != libc::S_IFREG && file_type != libc::S_IFLNK {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{} not a file or symlink", dir.path.join(name).display()),
+            ));
+        }
+        match self.backup_mode {
+            BackupMode::TempDir => {}
+            BackupMode::None => {
+                return cvt(unsafe { libc::unlinkat(dir.fd.as_raw_fd(), c_name.as_ptr(), 0) });
+            }
+            BackupMode::Memory { .. } => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "{} would be replaced through a directory handle, which cannot be backed up in memory",
+                        dir.path.join(name).display()
+                    ),
+                ));
+            }
+        }
+        let backup_path = self.backup_path(&dir.path.join(name))?;
+        let c_backup_path = c_path(&backup_path)?;
+
+        let dir_clone = dir.try_clone()?;
+        self.retry(|| {
+            cvt(unsafe {
+                libc::renameat(
+                    dir.fd.as_raw_fd(),
+                    c_name.as_ptr(),
+                    libc::AT_FDCWD,
+                    c_backup_path.as_ptr(),
+                )
+            })
+        })?;
+        self.change(Operation::RemoveFileAt {
+            dir: dir_clone,
+            name: name.to_path_buf(),
+            backup: backup_path,
+        });
+        Ok(())
+    }
+
+    /// Renames the file at `from` to `to`, atomically replacing any file at
+    /// `to`. The replaced file is copied to the temp dir first, so that
+    /// rollback can move `from` back and restore it.
+    pub(crate) fn rename(
+        &mut self,
+        from: impl Into<PathBuf>,
+        to: impl Into<PathBuf>,
+    ) -> io::Result<()> {
+        let (from, to) = (from.into(), to.into());
+        if !from.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{} not a file", from.display()),
+            ));
+        }
+        let backup = match (to.symlink_metadata(), self.backup_mode) {
+            (Err(e), _) if e.kind() == io::ErrorKind::NotFound => None,
+            (Err(e), _) => return Err(e),
+            (Ok(_), BackupMode::None) => None,
+            (Ok(metadata), _) if !metadata.is_file() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("{} not a file", to.display()),
+                ));
+            }
+            (Ok(_), BackupMode::TempDir) => {
+                let backup = self.backup_path(&to)?;
+                fs::copy(&to, &backup)?;
+                Some(backup)
+            }
+            (Ok(_), BackupMode::Memory { .. }) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "{} would be replaced by a rename, which cannot be backed up in memory",
+                        to.display()
+                    ),
+                ));
+            }
+        };
+
+        self.retry(|| fs::rename(&from, &to))?;
+        self.change(Operation::Rename { from, to, backup });
+        Ok(())
+    }
+
+    /// Changes the permissions of `path`, restoring the previous ones on
+    /// rollback.
+    pub(crate) fn set_permissions(
+        &mut self,
+        path: impl Into<PathBuf>,
+        permissions: fs::Permissions,
+    ) -> io::Result<()> {
+        let path = path.into();
+        let previous = fs::metadata(&path)?.permissions();
+        fs::set_permissions(&path, permissions)?;
+        self.change(Operation::SetPermissions { path, previous });
+        Ok(())
+    }
+
+    /// Sets the modification and access times of `path`, restoring the
+    /// previous ones on rollback.
+    pub(crate) fn set_times(
+        &mut self,
+        path: impl Into<PathBuf>,
+        mtime: SystemTime,
+        atime: SystemTime,
+    ) -> io::Result<()> {
+        let path = path.into();
+        let metadata = fs::metadata(&path)?;
+        let previous = fs::FileTimes::new()
+            .set_modified(metadata.modified()?)
+            .set_accessed(metadata.accessed()?);
+        set_file_times(
+            &path,
+            fs::FileTimes::new().set_modified(mtime).set_accessed(atime),
+        )?;
+        self.change(Operation::SetTimes { path, previous });
+        Ok(())
+    }
+
+    /// Changes the owner of `path` to `uid` and `gid`, restoring the previous
+    /// owner on rollback.
+    #[cfg(unix)]
+    pub(crate) fn set_owner(
+        &mut self,
+        path: impl Into<PathBuf>,
+        uid: u32,
+        gid: u32,
+    ) -> io::Result<()> {
+        use std::os::unix::fs::MetadataExt;
+
+        let path = path.into();
+        let metadata = fs::symlink_metadata(&path)?;
+        std::os::unix::fs::lchown(&path, Some(uid), Some(gid))?;
+        self.change(Operation::SetOwner {
+            path,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+        });
         Ok(())
     }
 
@@ -114,9 +527,14 @@ impl Transaction {
                 format!("{} not a file or symlink", path.display()),
             ));
         }
-        let backup_path = self.backup_path(&path);
+        match self.backup_mode {
+            BackupMode::TempDir => {}
+            BackupMode::None => return fs::remove_file(&path),
+            BackupMode::Memory { max_bytes } => return self.remove_file_to_memory(path, max_bytes),
+        }
+        let backup_path = self.backup_path(&path)?;
 
-        fs::rename(&path, &backup_path)?;
+        self.retry(|| fs::rename(&path, &backup_path))?;
         self.change(Operation::RemoveFile {
             removed: path,
             backup: backup_path,
@@ -129,14 +547,29 @@ impl Transaction {
         let path = path.into();
         if !path.is_dir() {
             return Err(io::Error::new(
-                io::ErrorKind::NotADirectory,
+                io::ErrorKind::Other,
                 format!("{} not a directory", path.display()),
             ));
         }
+        match self.backup_mode {
+            BackupMode::TempDir => {}
+            // Without a backup, only an empty directory is removed, so that
+            // nothing is lost that a rollback could not restore.
+            BackupMode::None => return fs::remove_dir(&path),
+            BackupMode::Memory { .. } => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "{} is a directory, which cannot be backed up in memory",
+                        path.display()
+                    ),
+                ));
+            }
+        }
 
-        let backup_path = self.backup_path(&path);
+        let backup_path = self.backup_path(&path)?;
 
-        fs::rename(&path, &backup_path)?;
+        self.retry(|| fs::rename(&path, &backup_path))?;
         self.change(Operation::RemoveDir {
             removed: path,
             backup: backup_path,
@@ -144,13 +577,74 @@ impl Transaction {
         Ok(())
     }
 
-    fn backup_path(&self, path: &Path) -> PathBuf {
+    fn retry<T>(&self, mut f: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+        let mut backoff = Duration::from_millis(10);
+        let mut retries = 0;
+        loop {
+            match f() {
+                Err(e) if is_transient(&e) && retries < self.io_retries => {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    retries += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    fn remove_file_to_memory(&mut self, path: PathBuf, max_bytes: usize) -> io::Result<()> {
+        if path.is_symlink() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "{} is a symlink, which cannot be backed up in memory",
+                    path.display()
+                ),
+            ));
+        }
+        let contents = Zeroizing::new(fs::read(&path)?);
+        if self.backup_memory + contents.len() > max_bytes {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "backing up {} would exceed the in-memory backup limit of {max_bytes} bytes",
+                    path.display()
+                ),
+            ));
+        }
+        let permissions = fs::metadata(&path)?.permissions();
+
+        self.retry(|| fs::remove_file(&path))?;
+        self.backup_memory += contents.len();
+        self.change(Operation::RestoreFile {
+            removed: path,
+            contents,
+            permissions,
+        });
+        Ok(())
+    }
+
+    /// Returns where to back up `path` in the temp dir, and records `path`
+    /// next to it in an [`ORIGIN_EXTENSION`] file, so that the backup can be
+    /// restored even if the process dies before rolling back.
+    fn backup_path(&self, path: &Path) -> io::Result<PathBuf> {
+        let Some(temp_dir) = &self.temp_dir else {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("no temp dir to back up {}", path.display()),
+            ));
+        };
         let mut filename = path
             .file_name()
             .expect("path should have a file name")
             .to_owned();
         filename.push(format!(".backup.{}", self.version));
-        self.temp_dir.path().join(filename)
+        let backup_path = temp_dir.path().join(filename);
+
+        if let Some(origin) = std::path::absolute(path)?.to_str() {
+            fs::write(origin_path(&backup_path), origin)?;
+        }
+        Ok(backup_path)
     }
 
     fn change(&mut self, op: Operation) {
@@ -159,10 +653,126 @@ impl Transaction {
     }
 }
 
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+    )
+}
+
+/// Follows `path` through any chain of symlinks. Unlike
+/// [`fs::canonicalize`], the final target does not need to exist.
+fn resolve_symlink(mut path: PathBuf) -> io::Result<PathBuf> {
+    const MAX_SYMLINKS: usize = 40;
+
+    for _ in 0..MAX_SYMLINKS {
+        if !path.is_symlink() {
+            return Ok(path);
+        }
+        let target = fs::read_link(&path)?;
+        path = match path.parent() {
+            Some(parent) => parent.join(target),
+            None => target,
+        };
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        format!("too many levels of symbolic links: {}", path.display()),
+    ))
+}
+
+/// Creates a file that must not exist yet, with `mode` on Unix.
+///
+/// On Unix the file is also opened with `O_NOFOLLOW`, so that a symlink
+/// planted at `path` is never written through, even by a platform where
+/// `O_EXCL` alone would follow it.
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn create_new(path: &Path, mode: Option<u32>) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        options.custom_flags(libc::O_NOFOLLOW);
+        if let Some(mode) = mode {
+            options.mode(mode);
+        }
+    }
+    options.open(path)
+}
+
+/// Like [`create_new`], but creates `name` relative to `dir`.
+#[cfg(unix)]
+fn create_new_at(dir: &Dir, name: &Path, mode: Option<u32>) -> io::Result<fs::File> {
+    use std::os::fd::AsRawFd;
+    use std::os::fd::FromRawFd;
+
+    let c_name = c_path(name)?;
+    let fd = unsafe {
+        libc::openat(
+            dir.fd.as_raw_fd(),
+            c_name.as_ptr(),
+            libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+            mode.unwrap_or(0o666) as libc::c_uint,
+        )
+    };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { fs::File::from_raw_fd(fd) })
+}
+
+#[cfg(unix)]
+fn c_path(path: &Path) -> io::Result<std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Ok(std::ffi::CString::new(path.as_os_str().as_bytes())?)
+}
+
+/// Turns the `-1` a libc function returns on failure into its error.
+#[cfg(unix)]
+fn cvt(ret: libc::c_int) -> io::Result<()> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Sets the times of the file at `path`. Unix allows it through a read-only
+/// handle, so that it works on files that are not writable.
+fn set_file_times(path: &Path, times: fs::FileTimes) -> io::Result<()> {
+    let file = if cfg!(unix) {
+        fs::File::open(path)?
+    } else {
+        fs::OpenOptions::new().write(true).open(path)?
+    };
+    file.set_times(times)
+}
+
 impl Drop for Transaction {
     fn drop(&mut self) {
+        // Listed first, since rolling back forgets the operations it undoes.
+        let backups = self.backups();
         if let Err(e) = self.rollback_to(0) {
-            panic!("failed to rollback: {e}");
+            // The ones already restored are no longer there.
+            let left: String = backups
+                .iter()
+                .filter(|(_, backup)| backup.symlink_metadata().is_ok())
+                .map(|(original, backup)| {
+                    format!(
+                        "\n{} is backed up at {}",
+                        original.display(),
+                        backup.display()
+                    )
+                })
+                .collect();
+            // Unwinding would otherwise remove the backups with the temp dir.
+            if let Some(temp_dir) = self.temp_dir.take() {
+                let _ = temp_dir.into_path();
+            }
+            panic!("failed to rollback: {e}{left}");
         }
     }
 }
@@ -180,6 +790,41 @@ enum Operation {
         removed: PathBuf,
         backup: PathBuf,
     },
+    RestoreFile {
+        removed: PathBuf,
+        contents: Zeroizing<Vec<u8>>,
+        permissions: fs::Permissions,
+    },
+    SetPermissions {
+        path: PathBuf,
+        previous: fs::Permissions,
+    },
+    SetTimes {
+        path: PathBuf,
+        previous: fs::FileTimes,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+        backup: Option<PathBuf>,
+    },
+    #[cfg(unix)]
+    SetOwner {
+        path: PathBuf,
+        uid: u32,
+        gid: u32,
+    },
+    #[cfg(unix)]
+    WriteFileAt {
+        dir: Dir,
+        name: PathBuf,
+    },
+    #[cfg(unix)]
+    RemoveFileAt {
+        dir: Dir,
+        name: PathBuf,
+        backup: PathBuf,
+    },
 }
 
 impl Operation {
@@ -189,6 +834,52 @@ impl Operation {
             Operation::WriteFile(p) => fs::remove_file(p),
             Operation::RemoveFile { removed, backup }
             | Operation::RemoveDir { removed, backup } => fs::rename(backup, removed),
+            Operation::RestoreFile {
+                removed,
+                contents,
+                permissions,
+            } => {
+                fs::write(removed, contents)?;
+                fs::set_permissions(removed, permissions.clone())
+            }
+            Operation::SetPermissions { path, previous } => {
+                fs::set_permissions(path, previous.clone())
+            }
+            Operation::SetTimes { path, previous } => set_file_times(path, *previous),
+            Operation::Rename { from, to, backup } => {
+                fs::rename(to, from)?;
+                match backup {
+                    // The temp dir may be on another file system, so the
+                    // backup is copied back rather than renamed.
+                    Some(backup) => fs::copy(backup, to).map(drop),
+                    None => Ok(()),
+                }
+            }
+            #[cfg(unix)]
+            Operation::SetOwner { path, uid, gid } => {
+                std::os::unix::fs::lchown(path, Some(*uid), Some(*gid))
+            }
+            #[cfg(unix)]
+            Operation::WriteFileAt { dir, name } => {
+                use std::os::fd::AsRawFd;
+
+                let c_name = c_path(name)?;
+                cvt(unsafe { libc::unlinkat(dir.fd.as_raw_fd(), c_name.as_ptr(), 0) })
+            }
+            #[cfg(unix)]
+            Operation::RemoveFileAt { dir, name, backup } => {
+                use std::os::fd::AsRawFd;
+
+                let (c_name, c_backup) = (c_path(name)?, c_path(backup)?);
+                cvt(unsafe {
+                    libc::renameat(
+                        libc::AT_FDCWD,
+                        c_backup.as_ptr(),
+                        dir.fd.as_raw_fd(),
+                        c_name.as_ptr(),
+                    )
+                })
+            }
         }
     }
 }
@@ -236,6 +927,83 @@ mod tests {
         assert!(filepath.is_file());
     }
 
+    #[test]
+    fn backups_of_removed_files() {
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+        let mut tx = Transaction::new(txdir);
+
+        let filepath1 = testdir.path().join("a");
+        let filepath2 = testdir.path().join("b");
+        fs::write(&filepath1, "a").unwrap();
+        fs::write(&filepath2, "b").unwrap();
+        tx.write_file(testdir.path().join("c"), "c").unwrap();
+        tx.remove_file(&filepath1).unwrap();
+        tx.remove_file(&filepath2).unwrap();
+
+        let backups = tx.backups();
+        assert_eq!(
+            backups
+                .iter()
+                .map(|(original, _)| original)
+                .collect::<Vec<_>>(),
+            [&filepath1, &filepath2]
+        );
+        assert_eq!(fs::read_to_string(&backups[0].1).unwrap(), "a");
+        assert_eq!(fs::read_to_string(&backups[1].1).unwrap(), "b");
+    }
+
+    #[test]
+    fn failed_rollback_keeps_backups() {
+        let testdir = tempfile::tempdir().unwrap();
+        let filepath = testdir.path().join("a");
+        fs::write(&filepath, "original").unwrap();
+
+        let mut tx = Transaction::new(tempfile::tempdir().unwrap());
+        tx.write_file(&filepath, "new").unwrap();
+        let backup = tx.backups()[0].1.clone();
+        // Rolling back the write fails, before the original is restored.
+        fs::remove_file(&filepath).unwrap();
+
+        let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| drop(tx)))
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert!(panic.ends_with(&format!(
+            "\n{} is backed up at {}",
+            filepath.display(),
+            backup.display()
+        )));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "original");
+        fs::remove_dir_all(backup.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn rename_replaces_then_rollback() {
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let from = testdir.path().join("new");
+        let to = testdir.path().join("current");
+        fs::write(&from, "new").unwrap();
+        fs::write(&to, "old").unwrap();
+
+        let mut tx = Transaction::new(txdir);
+        tx.rename(&from, &to).unwrap();
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(&to).unwrap(), "new");
+        drop(tx);
+
+        assert_eq!(fs::read_to_string(&from).unwrap(), "new");
+        assert_eq!(fs::read_to_string(&to).unwrap(), "old");
+    }
+
     #[test]
     fn remove_file_that_not_exists() {
         let testdir = tempfile::tempdir().unwrap();
@@ -269,6 +1037,22 @@ mod tests {
         assert!(!testdir.path().join("foo").exists());
     }
 
+    #[test]
+    fn remove_dir_without_backup() {
+        let testdir = tempfile::tempdir().unwrap();
+        let mut tx = Transaction::without_temp_dir().with_backup_mode(BackupMode::None);
+
+        fs::create_dir_all(testdir.path().join("foo")).unwrap();
+        fs::write(testdir.path().join("foo/bar"), "").unwrap();
+        assert!(tx.remove_dir(testdir.path().join("foo")).is_err());
+        assert!(testdir.path().join("foo/bar").exists());
+
+        fs::remove_file(testdir.path().join("foo/bar")).unwrap();
+        tx.remove_dir(testdir.path().join("foo")).unwrap();
+        tx.commit();
+        assert!(!testdir.path().join("foo").exists());
+    }
+
     #[test]
     fn remove_dir_then_rollback() {
         let testdir = tempfile::tempdir().unwrap();
@@ -302,6 +1086,27 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[test]
+    fn remove_dir_on_file() {
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let mut tx = Transaction::new(txdir);
+
+        let filepath = testdir.path().join("foo");
+        fs::write(&filepath, "").unwrap();
+
+        let err = tx.remove_dir(&filepath).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("{} not a directory", filepath.display())
+        );
+        assert!(filepath.is_file());
+    }
+
     #[test]
     fn write_file() {
         let testdir = tempfile::tempdir().unwrap();
@@ -379,4 +1184,449 @@ mod tests {
 
         assert_eq!(fs::read_to_string(filepath).unwrap(), contents1);
     }
+
+    #[test]
+    fn write_file_that_exists_without_backup() {
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let mut tx = Transaction::new(txdir).with_backup_mode(BackupMode::None);
+
+        let filepath = &testdir.path().join("a");
+        fs::write(filepath, "hi").unwrap();
+        tx.write_file(filepath, "ih").unwrap();
+
+        assert_eq!(
+            fs::read_dir(tx.temp_dir.as_ref().unwrap().path())
+                .unwrap()
+                .count(),
+            0
+        );
+        tx.commit();
+        assert_eq!(fs::read_to_string(filepath).unwrap(), "ih");
+    }
+
+    #[test]
+    fn write_file_that_exists_without_backup_then_rollback() {
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let mut tx = Transaction::new(txdir).with_backup_mode(BackupMode::None);
+
+        let filepath = &testdir.path().join("a");
+        fs::write(filepath, "hi").unwrap();
+        tx.write_file(filepath, "ih").unwrap();
+        drop(tx);
+
+        assert!(!filepath.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_file_through_symlink() {
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let mut tx = Transaction::new(txdir).with_follow_symlinks(true);
+
+        let target = testdir.path().join("vault/a");
+        let link = testdir.path().join("a");
+        fs::create_dir(testdir.path().join("vault")).unwrap();
+        fs::write(&target, "hi").unwrap();
+        std::os::unix::fs::symlink("vault/a", &link).unwrap();
+
+        tx.write_file(&link, "ih").unwrap();
+        tx.commit();
+
+        assert!(link.is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "ih");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_file_through_symlink_then_rollback() {
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let mut tx = Transaction::new(txdir).with_follow_symlinks(true);
+
+        let target = testdir.path().join("b");
+        let link = testdir.path().join("a");
+        fs::write(&target, "hi").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        tx.write_file(&link, "ih").unwrap();
+        drop(tx);
+
+        assert!(link.is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "hi");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_file_at_dir_handle() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+        let keys = testdir.path().join("keys");
+        let moved = testdir.path().join("moved");
+        let elsewhere = testdir.path().join("elsewhere");
+        fs::create_dir(&keys).unwrap();
+        fs::create_dir(&elsewhere).unwrap();
+        fs::write(keys.join("b"), "old").unwrap();
+
+        let dir = Dir::open(&keys).unwrap();
+        // The directory is swapped for a symlink after it was opened.
+        fs::rename(&keys, &moved).unwrap();
+        std::os::unix::fs::symlink(&elsewhere, &keys).unwrap();
+
+        let mut tx = Transaction::new(txdir);
+        tx.write_file_at(&dir, "a", "secret", Some(0o600)).unwrap();
+        tx.write_file_at(&dir, "b", "new", None).unwrap();
+        assert!(tx.write_file_at(&dir, "../c", "", None).is_err());
+
+        assert_eq!(fs::read_to_string(moved.join("a")).unwrap(), "secret");
+        let mode = fs::metadata(moved.join("a")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(moved.join("b")).unwrap(), "new");
+        assert_eq!(fs::read_dir(&elsewhere).unwrap().count(), 0);
+
+        drop(tx);
+        assert!(!moved.join("a").exists());
+        assert_eq!(fs::read_to_string(moved.join("b")).unwrap(), "old");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_file_through_symlinked_dir() {
+        let testdir = tempfile::tempdir().unwrap();
+        let keys = testdir.path().join("keys");
+        let linked = testdir.path().join("linked");
+        fs::create_dir(&keys).unwrap();
+        std::os::unix::fs::symlink(&keys, &linked).unwrap();
+
+        // Writes relative to the opened directory, or by path through the
+        // symlink, which cannot be opened as a directory handle.
+        let mut tx = Transaction::new(tempfile::tempdir().unwrap());
+        tx.write_private_file(keys.join("a"), "a").unwrap();
+        tx.write_file(linked.join("b"), "b").unwrap();
+        tx.commit();
+        assert_eq!(fs::read_to_string(keys.join("a")).unwrap(), "a");
+        assert_eq!(fs::read_to_string(keys.join("b")).unwrap(), "b");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_file_replaces_symlink_by_default() {
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let mut tx = Transaction::new(txdir);
+
+        let target = testdir.path().join("b");
+        let link = testdir.path().join("a");
+        fs::write(&target, "hi").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        tx.write_file(&link, "ih").unwrap();
+        tx.commit();
+
+        assert!(!link.is_symlink());
+        assert_eq!(fs::read_to_string(&link).unwrap(), "ih");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "hi");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn create_new_refuses_planted_symlink() {
+        let testdir = tempfile::tempdir().unwrap();
+
+        // A dangling symlink, as an attacker would plant in a world-writable
+        // directory to have the key written wherever they like.
+        let target = testdir.path().join("b");
+        let link = testdir.path().join("a");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        assert!(create_new(&link, Some(0o600)).is_err());
+        assert!(!target.exists());
+
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+        let mut tx = Transaction::new(txdir);
+        tx.write_private_file(&link, "secret").unwrap();
+        tx.commit();
+
+        assert!(!link.is_symlink());
+        assert_eq!(fs::read_to_string(&link).unwrap(), "secret");
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn retry_transient_error() {
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let tx = Transaction::new(txdir).with_io_retries(2);
+
+        let mut calls = 0;
+        let res = tx.retry(|| {
+            calls += 1;
+            if calls < 3 {
+                Err(io::Error::from(io::ErrorKind::Interrupted))
+            } else {
+                Ok(())
+            }
+        });
+        assert!(res.is_ok());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn retry_gives_up() {
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let tx = Transaction::new(txdir).with_io_retries(2);
+
+        let mut calls = 0;
+        let res: io::Result<()> = tx.retry(|| {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::WouldBlock))
+        });
+        assert!(res.is_err());
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn retry_non_transient_error() {
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let tx = Transaction::new(txdir).with_io_retries(2);
+
+        let mut calls = 0;
+        let res: io::Result<()> = tx.retry(|| {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        });
+        assert!(res.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn write_file_that_exists_with_memory_backup() {
+        let testdir = tempfile::tempdir().unwrap();
+
+        let mut tx =
+            Transaction::without_temp_dir().with_backup_mode(BackupMode::Memory { max_bytes: 2 });
+
+        let filepath = &testdir.path().join("a");
+        fs::write(filepath, "hi").unwrap();
+        tx.write_file(filepath, "ih").unwrap();
+        tx.commit();
+
+        assert_eq!(fs::read_to_string(filepath).unwrap(), "ih");
+    }
+
+    #[test]
+    fn write_file_that_exists_with_memory_backup_then_rollback() {
+        let testdir = tempfile::tempdir().unwrap();
+
+        let mut tx =
+            Transaction::without_temp_dir().with_backup_mode(BackupMode::Memory { max_bytes: 2 });
+
+        let filepath = &testdir.path().join("a");
+        fs::write(filepath, "hi").unwrap();
+        tx.write_file(filepath, "ih").unwrap();
+        drop(tx);
+
+        assert_eq!(fs::read_to_string(filepath).unwrap(), "hi");
+    }
+
+    #[test]
+    fn write_file_that_exceeds_memory_backup() {
+        let testdir = tempfile::tempdir().unwrap();
+
+        let mut tx =
+            Transaction::without_temp_dir().with_backup_mode(BackupMode::Memory { max_bytes: 3 });
+
+        let filepath1 = &testdir.path().join("a");
+        let filepath2 = &testdir.path().join("b");
+        fs::write(filepath1, "hi").unwrap();
+        fs::write(filepath2, "hi").unwrap();
+        tx.write_file(filepath1, "ih").unwrap();
+        assert!(tx.write_file(filepath2, "ih").is_err());
+        drop(tx);
+
+        assert_eq!(fs::read_to_string(filepath1).unwrap(), "hi");
+        assert_eq!(fs::read_to_string(filepath2).unwrap(), "hi");
+    }
+
+    #[test]
+    fn write_file_with_mtime() {
+        let testdir = tempfile::tempdir().unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let filepaths = [testdir.path().join("a"), testdir.path().join("b")];
+        for filepath in &filepaths {
+            let txdir = tempfile::Builder::new()
+                .prefix("bip39-keygen")
+                .tempdir()
+                .unwrap();
+            let mut tx = Transaction::new(txdir).with_mtime(Some(mtime));
+            tx.write_file(filepath, "hi").unwrap();
+            tx.commit();
+        }
+
+        for filepath in &filepaths {
+            let metadata = fs::metadata(filepath).unwrap();
+            assert_eq!(metadata.modified().unwrap(), mtime);
+            assert_eq!(metadata.accessed().unwrap(), mtime);
+        }
+    }
+
+    #[test]
+    fn set_times_then_rollback() {
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+        let filepath = testdir.path().join("a");
+        fs::write(&filepath, "hi").unwrap();
+        let previous = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        set_file_times(
+            &filepath,
+            fs::FileTimes::new()
+                .set_modified(previous)
+                .set_accessed(previous),
+        )
+        .unwrap();
+
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let atime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_001);
+        let mut tx = Transaction::new(txdir);
+        tx.set_times(&filepath, mtime, atime).unwrap();
+        let metadata = fs::metadata(&filepath).unwrap();
+        assert_eq!(metadata.modified().unwrap(), mtime);
+        assert_eq!(metadata.accessed().unwrap(), atime);
+
+        tx.rollback_to(0).unwrap();
+        let metadata = fs::metadata(&filepath).unwrap();
+        assert_eq!(metadata.modified().unwrap(), previous);
+        assert_eq!(metadata.accessed().unwrap(), previous);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_private_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let mut tx = Transaction::new(txdir);
+
+        let filepath = testdir.path().join("a");
+        fs::write(&filepath, "hi").unwrap();
+        fs::set_permissions(&filepath, fs::Permissions::from_mode(0o644)).unwrap();
+        tx.write_private_file(&filepath, "ih").unwrap();
+        tx.commit();
+
+        let metadata = fs::metadata(&filepath).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&filepath).unwrap(), "ih");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn set_permissions_then_rollback() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let mut tx = Transaction::new(txdir);
+
+        let filepath = testdir.path().join("a");
+        fs::write(&filepath, "hi").unwrap();
+        fs::set_permissions(&filepath, fs::Permissions::from_mode(0o644)).unwrap();
+        let mode: crate::mode::SymbolicMode = "u=rw,go=".parse().unwrap();
+        tx.set_permissions(&filepath, fs::Permissions::from_mode(mode.apply(0o644)))
+            .unwrap();
+        let metadata = fs::metadata(&filepath).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+        tx.rollback_to(0).unwrap();
+        let metadata = fs::metadata(&filepath).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o644);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn set_owner_then_rollback() {
+        use std::os::unix::fs::MetadataExt;
+
+        let testdir = tempfile::tempdir().unwrap();
+        let txdir = tempfile::Builder::new()
+            .prefix("bip39-keygen")
+            .tempdir()
+            .unwrap();
+
+        let mut tx = Transaction::new(txdir);
+
+        let filepath = testdir.path().join("a");
+        fs::write(&filepath, "hi").unwrap();
+        let metadata = fs::metadata(&filepath).unwrap();
+        // Without CAP_CHOWN a file can only be given to its own owner.
+        let (uid, gid) = if unsafe { libc::geteuid() } == 0 {
+            (1234, 1234)
+        } else {
+            (metadata.uid(), metadata.gid())
+        };
+
+        tx.set_owner(&filepath, uid, gid).unwrap();
+        let changed = fs::metadata(&filepath).unwrap();
+        assert_eq!((changed.uid(), changed.gid()), (uid, gid));
+
+        tx.rollback_to(0).unwrap();
+        let restored = fs::metadata(&filepath).unwrap();
+        assert_eq!(
+            (restored.uid(), restored.gid()),
+            (metadata.uid(), metadata.gid())
+        );
+    }
 }
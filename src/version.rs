@@ -1,5 +1,12 @@
 use std::sync::LazyLock;
 
+use bip39::Language;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::mnemonic::WordlistLanguage;
+use crate::KeyType;
+
 /// Defines the application version.
 pub static VERSION: LazyLock<String> = LazyLock::new(|| {
     format!(
@@ -8,3 +15,49 @@ pub static VERSION: LazyLock<String> = LazyLock::new(|| {
         option_env!("GIT_COMMIT").unwrap_or("unknown")
     )
 });
+
+/// What this build supports, for bug reports and compatibility checks.
+#[derive(Debug, Serialize)]
+pub(crate) struct Info {
+    version: &'static str,
+    git_commit: &'static str,
+    build_timestamp: &'static str,
+    languages: Vec<WordlistLanguage>,
+    key_types: Vec<KeyType>,
+}
+
+impl Info {
+    pub(crate) fn new() -> Self {
+        Info {
+            version: &VERSION,
+            git_commit: option_env!("GIT_COMMIT").unwrap_or("unknown"),
+            build_timestamp: option_env!("VERGEN_BUILD_TIMESTAMP").unwrap_or("unknown"),
+            languages: Language::all().iter().copied().map(Into::into).collect(),
+            key_types: KeyType::value_variants().to_vec(),
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn info_contains_version() {
+        let json = Info::new().to_json().unwrap();
+        let info: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(info["version"], VERSION.as_str());
+        assert_eq!(info["key_types"], serde_json::json!(["ed25519"]));
+        assert!(
+            info["languages"]
+                .as_array()
+                .unwrap()
+                .contains(&serde_json::json!("simplified-chinese"))
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use base64ct::Base64;
+use base64ct::Encoding;
+use curve25519_dalek::MontgomeryPoint;
+use zeroize::Zeroizing;
+
+/// A WireGuard key pair, as `wg genkey` and `wg pubkey` print it.
+pub(crate) struct Keypair {
+    private_key: Zeroizing<[u8; 32]>,
+    public_key: [u8; 32],
+}
+
+impl Keypair {
+    /// Clamps 32 bytes of key material into an X25519 scalar, as WireGuard
+    /// does, and computes its public key.
+    pub(crate) fn from_key(key: &[u8; 32]) -> Self {
+        let mut private_key = Zeroizing::new(*key);
+        private_key[0] &= 248;
+        private_key[31] &= 127;
+        private_key[31] |= 64;
+        let public_key = MontgomeryPoint::mul_base_clamped(*private_key).to_bytes();
+        Self {
+            private_key,
+            public_key,
+        }
+    }
+
+    /// The base64 private key, for the `PrivateKey` of a WireGuard config.
+    pub(crate) fn private_key(&self) -> Zeroizing<String> {
+        Zeroizing::new(Base64::encode_string(self.private_key.as_ref()))
+    }
+
+    /// The base64 public key, for the `PublicKey` of the peers.
+    pub(crate) fn public_key(&self) -> String {
+        Base64::encode_string(&self.public_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn public_key_of_clamped_private_key() {
+        // Alice's key pair from RFC 7748, section 6.1.
+        let key: [u8; 32] =
+            hex::decode("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let keypair = Keypair::from_key(&key);
+        assert_eq!(
+            hex::encode(Base64::decode_vec(&keypair.public_key()).unwrap()),
+            "8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"
+        );
+
+        let private_key = Base64::decode_vec(&keypair.private_key()).unwrap();
+        assert_eq!(private_key[0] & 7, 0);
+        assert_eq!(private_key[31] & 0xc0, 0x40);
+        assert_eq!(Keypair::from_key(&key).private_key(), keypair.private_key());
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+
+use anyhow::bail;
+use bip39::Language;
+use sha2::Digest;
+use sha2::Sha256;
+
+/// The SHA256 of each wordlist file in the BIP39 repository, one word per
+/// line with a trailing newline.
+const EXPECTED: &[(Language, &str)] = &[
+    (
+        Language::English,
+        "2f5eed53a4727b4bf8880d8f3f199efc90e58503646d9ff8eff3a2ed3b24dbda",
+    ),
+    (
+        Language::SimplifiedChinese,
+        "5c5942792bd8340cb8b27cd592f1015edf56a8c5b26276ee18a482428e7c5726",
+    ),
+    (
+        Language::TraditionalChinese,
+        "417b26b3d8500a4ae3d59717d7011952db6fc2fb84b807f3f94ac734e89c1b5f",
+    ),
+    (
+        Language::Czech,
+        "7e80e161c3e93d9554c2efb78d4e3cebf8fc727e9c52e03b83b94406bdcc95fc",
+    ),
+    (
+        Language::French,
+        "ebc3959ab7801a1df6bac4fa7d970652f1df76b683cd2f4003c941c63d517e59",
+    ),
+    (
+        Language::Italian,
+        "d392c49fdb700a24cd1fceb237c1f65dcc128f6b34a8aacb58b59384b5c648c2",
+    ),
+    (
+        Language::Japanese,
+        "2eed0aef492291e061633d7ad8117f1a2b03eb80a29d0e4e3117ac2528d05ffd",
+    ),
+    (
+        Language::Korean,
+        "9e95f86c167de88f450f0aaf89e87f6624a57f973c67b516e338e8e8b8897f60",
+    ),
+    (
+        Language::Spanish,
+        "46846a5a0139d1e3cb77293e521c2865f7bcdb82c44e8d0a06a2cd0ecba48c0b",
+    ),
+];
+
+/// Checks that every compiled-in wordlist has 2048 unique words and
+/// matches the published wordlist.
+pub(crate) fn verify_integrity() -> anyhow::Result<()> {
+    for &language in Language::all() {
+        let Some(&(_, expected)) = EXPECTED.iter().find(|(l, _)| *l == language) else {
+            bail!("no known digest for the {language:?} wordlist");
+        };
+        let words = language.word_list();
+        if words.iter().collect::<HashSet<_>>().len() != words.len() {
+            bail!("the {language:?} wordlist contains duplicate words");
+        }
+        if hex_digest(language) != expected {
+            bail!("the {language:?} wordlist does not match the BIP39 wordlist");
+        }
+    }
+    Ok(())
+}
+
+/// Lists the 2048 words of `language`, one numbered word per line, to print
+/// as a recovery aid.
+pub(crate) fn numbered(language: Language) -> String {
+    use std::fmt::Write;
+
+    let mut list = String::new();
+    for (i, word) in language.word_list().iter().enumerate() {
+        writeln!(list, "{:>4}. {word}", i + 1).expect("writing to a String cannot fail");
+    }
+    list
+}
+
+/// Returns the word of `language` that `input` is, or that it is the only
+/// one to start with, such as `legal` for `lega`.
+pub(crate) fn resolve(language: Language, input: &str) -> Option<&'static str> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+    let words = language.word_list();
+    if let Some(word) = words.iter().find(|word| **word == input) {
+        return Some(word);
+    }
+    let mut matches = words.iter().filter(|word| word.starts_with(input));
+    let first = matches.next()?;
+    matches.next().is_none().then_some(first)
+}
+
+/// Completes the words of a wordlist as they are typed.
+#[derive(Clone)]
+pub(crate) struct Completer {
+    pub(crate) language: Language,
+}
+
+impl Completer {
+    /// At most this many words are suggested.
+    const MAX_SUGGESTIONS: usize = 8;
+}
+
+impl inquire::Autocomplete for Completer {
+    fn get_suggestions(&mut self, input: &str) -> Result<Vec<String>, inquire::CustomUserError> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .language
+            .word_list()
+            .iter()
+            .filter(|word| word.starts_with(input))
+            .take(Self::MAX_SUGGESTIONS)
+            .map(|word| word.to_string())
+            .collect())
+    }
+
+    fn get_completion(
+        &mut self,
+        input: &str,
+        highlighted_suggestion: Option<String>,
+    ) -> Result<inquire::autocompletion::Replacement, inquire::CustomUserError> {
+        Ok(highlighted_suggestion.or_else(|| resolve(self.language, input).map(str::to_string)))
+    }
+}
+
+fn hex_digest(language: Language) -> String {
+    let mut hasher = Sha256::new();
+    for word in language.word_list() {
+        hasher.update(word.as_bytes());
+        hasher.update(b"\n");
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use inquire::Autocomplete;
+
+    use super::*;
+
+    #[test]
+    fn english_digest() {
+        assert_eq!(
+            hex_digest(Language::English),
+            "2f5eed53a4727b4bf8880d8f3f199efc90e58503646d9ff8eff3a2ed3b24dbda"
+        );
+    }
+
+    #[test]
+    fn numbered_english() {
+        let list = numbered(Language::English);
+        let lines: Vec<&str> = list.lines().collect();
+        assert_eq!(lines.len(), 2048);
+        assert_eq!(lines[0], "   1. abandon");
+        assert_eq!(lines[2047], "2048. zoo");
+    }
+
+    #[test]
+    fn resolve_prefixes() {
+        assert_eq!(resolve(Language::English, "lega"), Some("legal"));
+        assert_eq!(resolve(Language::English, " legal "), Some("legal"));
+        assert_eq!(resolve(Language::English, "wa"), None);
+        assert_eq!(resolve(Language::English, "xyz"), None);
+        // `leg` is a word and the start of `legal`.
+        assert_eq!(resolve(Language::English, "leg"), Some("leg"));
+
+        let mut completer = Completer {
+            language: Language::English,
+        };
+        assert_eq!(completer.get_suggestions("leg").unwrap(), [
+            "leg", "legal", "legend"
+        ]);
+        assert_eq!(
+            completer.get_completion("winn", None).unwrap(),
+            Some("winner".to_string())
+        );
+    }
+
+    #[test]
+    fn compiled_in_wordlists_are_intact() {
+        verify_integrity().unwrap();
+    }
+}
@@ -0,0 +1,35 @@
+use std::process::Command;
+use std::process::Stdio;
+
+const TEST_MNEMONIC: &str =
+    "legal winner thank year wave sausage worth useful legal winner thank yellow";
+
+#[test]
+fn stdout_only_holds_exports() {
+    let testdir = tempfile::tempdir().unwrap();
+    let seckey_path = testdir.path().join("id_ed25519");
+    let output = Command::new(env!("CARGO_BIN_EXE_bip39-keygen"))
+        .args(["ssh", "-t", "ed25519", "-m", TEST_MNEMONIC, "-N", "--yes"])
+        .arg("-f")
+        .arg(&seckey_path)
+        .args(["--emit-shell-env", "bash"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let names: Vec<&str> = stdout
+        .lines()
+        .map(|line| {
+            let export = line.strip_prefix("export ").expect(line);
+            export.split_once('=').expect(line).0
+        })
+        .collect();
+    assert_eq!(names, [
+        "SSH_KEY_PATH",
+        "SSH_PUBLIC_KEY_PATH",
+        "SSH_KEY_FINGERPRINT"
+    ]);
+    assert!(stdout.contains(&*seckey_path.to_string_lossy()));
+}